@@ -1,52 +1,46 @@
-use std::sync::{Mutex, Arc};
+use std::sync::Mutex;
 use mongodb::{Collection, Database};
 
-use crate::{common::{raw_stock::RawStock, enums::AlgoTypes, redis_client::RedisClient}, 
-order_manager::{self, trade_signal_keeper::{TradeSignal, TradeSignalsKeeper}, order_dispatcher::Order}};
+use crate::{common::{raw_stock::RawStock, redis_client::RedisClient},
+order_manager::{self, trade_signal_keeper::{TradeSignal, TradeSignalsKeeper}, position_sizer::PositionSizer, risk_gate::RiskGate}};
 
-use super::hammer_pattern::{HammerCandle, HammerPatternUtil};
+use super::strategy::{MarketContext, StrategyRegistry};
 
 
-pub async fn ingest_raw_stock_data(raw_stock: &RawStock, tradeable_algo_types: Vec<AlgoTypes>, 
-    mut hammer_ledger: HammerPatternUtil, 
-    hammer_candle_collection: Collection<HammerCandle>,
-    mut trade_keeper: TradeSignalsKeeper, 
+//the registry, the signal keeper and the order manager are the worker's own long lived instances => nothing is rebuilt per candle
+pub async fn ingest_raw_stock_data(raw_stock: &RawStock, market_context: &MarketContext,
+    strategy_registry: &mut StrategyRegistry,
+    trade_keeper: &mut TradeSignalsKeeper,
     trade_signal_collection: Collection<TradeSignal>,
-    mut order_manager: order_manager::order_dispatcher::OrderManager,
+    order_manager: &mut order_manager::order_dispatcher::OrderManager,
     orders_collection: Collection<order_manager::order_dispatcher::Order>,
+    risk_gate: &RiskGate,
+    position_sizer: &PositionSizer,
     redis_client: &Mutex<RedisClient>,
     _database_instance: Database,
-    shared_order_ledger: Arc<Mutex<Vec<Order>>>
 ){
 
-    // mut hammer_ledger: HammerPatternUtil, hammer_candle_collection: Collection<HammerCandle>
     //TODO: this is just to consume the data
     // I do need to make it more configurable using threads here, so that I can run multiple algorithms at the same time
 
-    for tradeable_algo_type in tradeable_algo_types.iter() {
-        match tradeable_algo_type {
-            AlgoTypes::HammerPatternAlgo => {
-                let trade_signal_option = hammer_ledger
-                .calculate_and_add_ledger(&raw_stock, hammer_candle_collection.clone())
-                .await; 
-                // println!("Trade Signal Option: {:?}", trade_signal_option);
-                match trade_signal_option {
-                    Some(trade_signal) => {
-                        trade_keeper
-                            .add_trade_signal(&trade_signal, trade_signal_collection.clone())
-                            .await;
-                        order_manager.check_and_dispatch_order(trade_signal,redis_client, orders_collection.clone(), shared_order_ledger.clone()).await;
-                    }
-                    None => {
-                        // println!("No Trading Signal Opportunity Found");
-                    }
-                }
-
-
-            },
-            AlgoTypes::ShootingStarPatternAlgo => {
-
-            },
+    for mut trade_signal in strategy_registry.on_candle(raw_stock, market_context).await {
+        //the strategy qty is replaced by the one sized against the capital and the SL distance
+        position_sizer.size_trade_signal(
+            &mut trade_signal,
+            order_manager.pnl_configuration.trade_capital as f32,
+            market_context.average_true_range,
+        );
+        if trade_signal.qty <= 0 {
+            println!(
+                "Trade signal skipped for {} by {} => position sizer returned 0 qty",
+                trade_signal.raw_stock.symbol, trade_signal.strategy_instance
+            );
+            continue;
         }
+
+        trade_keeper
+            .add_trade_signal(&trade_signal, trade_signal_collection.clone())
+            .await;
+        order_manager.check_and_dispatch_order(trade_signal, risk_gate, redis_client, orders_collection.clone()).await;
     }
-}
\ No newline at end of file
+}
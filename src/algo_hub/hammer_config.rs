@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    config_file,
+    enums::{AlgoTypes, TimeFrame},
+};
+
+//Every missing field falls back to the values the hammer strategy was originally tuned with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HammerConfig {
+    pub instance_name: String,
+
+    //candle detection
+    pub small_body_to_candle_ratio: f32,
+    pub small_body_lower_to_upper_wick_ratio: f32,
+    pub lower_to_upper_wick_ratio: f32,
+    pub min_lower_wick_to_body_ratio: f32,
+    pub max_upper_wick_to_body_ratio: f32,
+
+    //risk parameters
+    pub entry_price_ratio: f32,
+    pub sl_percentage: f32,
+    pub target_percentage: f32,
+    pub qty: i32,
+
+    //only take the long entry when the SMA trend of the candle is bullish
+    pub trade_with_trend: bool,
+
+    //candles after a signal during which new hammers on the same symbol are not traded, 0 => no cooldown
+    pub signal_cooldown_candles: usize,
+
+    //bars the instance trades on, every time frame listed gets its own worker
+    pub time_frames: Vec<TimeFrame>,
+}
+
+impl Default for HammerConfig {
+    fn default() -> HammerConfig {
+        HammerConfig {
+            instance_name: AlgoTypes::HammerPatternAlgo.to_string(),
+            small_body_to_candle_ratio: 0.25,
+            small_body_lower_to_upper_wick_ratio: 2.1,
+            lower_to_upper_wick_ratio: 2.0,
+            min_lower_wick_to_body_ratio: 1.75,
+            max_upper_wick_to_body_ratio: 1.5,
+            entry_price_ratio: 0.95,
+            sl_percentage: 5.0,
+            target_percentage: 10.0,
+            qty: 10,
+            trade_with_trend: false,
+            signal_cooldown_candles: 0,
+            time_frames: vec![TimeFrame::FiveMinutes],
+        }
+    }
+}
+
+impl HammerConfig {
+    pub const COLLECTION_NAME: &'static str = "hammer_configs";
+
+    pub async fn fetch_from_collection(hammer_config_collection: Collection<HammerConfig>) -> Vec<HammerConfig> {
+        let cursor = hammer_config_collection.find(doc! {}, None).await;
+        match cursor {
+            Ok(cursor) => match cursor.try_collect::<Vec<_>>().await {
+                Ok(hammer_configs) => hammer_configs,
+                Err(e) => {
+                    println!("Error while fetching hammer configs: {}", e);
+                    Vec::new()
+                }
+            },
+            Err(e) => {
+                println!("Error while fetching hammer configs: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    //file first, then the hammer_configs collection, otherwise a single instance with the default thresholds
+    //Err => two configs share an instance_name (a config without one is named HammerPatternAlgo), their signals and orders could not be told apart
+    pub async fn load_configs(file_path: &str, hammer_config_collection: Collection<HammerConfig>) -> Result<Vec<HammerConfig>, String> {
+        let hammer_configs = match config_file::load_from_file::<Vec<HammerConfig>>(file_path, HammerConfig::COLLECTION_NAME) {
+            Ok(hammer_configs) => hammer_configs,
+            Err(e) => {
+                println!("Not able to read hammer configs from {} => {:?}", file_path, e);
+                HammerConfig::fetch_from_collection(hammer_config_collection).await
+            }
+        };
+
+        if hammer_configs.is_empty() {
+            return Ok(vec![HammerConfig::default()]);
+        }
+        let mut instance_names = HashSet::new();
+        for hammer_config in hammer_configs.iter() {
+            if !instance_names.insert(hammer_config.instance_name.as_str()) {
+                return Err(format!("hammer instance_name {} is used by more than one config", hammer_config.instance_name));
+            }
+        }
+        Ok(hammer_configs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config_file(file_name: &str, file_content: &str) -> String {
+        let file_path = std::env::temp_dir().join(file_name);
+        std::fs::write(&file_path, file_content).unwrap();
+        file_path.to_string_lossy().to_string()
+    }
+
+    //the collection is only read when the file is missing, the client only connects on the first operation
+    async fn hammer_config_collection() -> Collection<HammerConfig> {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap();
+        mongo_client.database("algo_trading_test").collection(HammerConfig::COLLECTION_NAME)
+    }
+
+    #[tokio::test]
+    async fn unique_instance_names_are_loaded() {
+        let file_path = write_config_file(
+            "hammer_configs_unique_test.toml",
+            "[[hammer_configs]]\n\n[[hammer_configs]]\ninstance_name = \"HammerWideSL\"\nsl_percentage = 7.5\n",
+        );
+        let hammer_configs = HammerConfig::load_configs(file_path.as_str(), hammer_config_collection().await).await.unwrap();
+        assert_eq!(hammer_configs.len(), 2);
+        assert_eq!(hammer_configs[0], HammerConfig::default());
+        assert_eq!(hammer_configs[1].sl_percentage, 7.5);
+    }
+
+    #[tokio::test]
+    async fn duplicate_instance_names_are_rejected() {
+        let file_path = write_config_file(
+            "hammer_configs_duplicate_test.toml",
+            "[[hammer_configs]]\ninstance_name = \"HammerWideSL\"\n\n[[hammer_configs]]\ninstance_name = \"HammerWideSL\"\nsl_percentage = 7.5\n",
+        );
+        let result = HammerConfig::load_configs(file_path.as_str(), hammer_config_collection().await).await;
+        assert!(result.unwrap_err().contains("HammerWideSL"));
+
+        //a config without instance_name takes the default one
+        let file_path = write_config_file(
+            "hammer_configs_duplicate_default_test.toml",
+            "[[hammer_configs]]\nqty = 5\n\n[[hammer_configs]]\ninstance_name = \"HammerPatternAlgo\"\n",
+        );
+        let result = HammerConfig::load_configs(file_path.as_str(), hammer_config_collection().await).await;
+        assert!(result.unwrap_err().contains("HammerPatternAlgo"));
+    }
+}
@@ -1,14 +1,17 @@
 
 #[path = "../common/mod.rs"] mod common;
-use crate::common::enums::{AlgoTypes, TradeType, TimeFrame};
+use std::collections::HashMap;
+use crate::common::enums::{AlgoTypes, MarketTrend, TradeType, TimeFrame};
 use crate::common::number_parser::return_2_precision_for_float;
 use crate::common::raw_stock::RawStock;
 use crate::common::date_parser;
 use crate::order_manager::trade_signal_keeper::TradeSignal;
+use super::hammer_config::HammerConfig;
+use super::strategy::{MarketContext, Strategy};
+use futures::future::BoxFuture;
 use mongodb::bson::oid::ObjectId;
 use mongodb::Collection;
 use serde::{Deserialize, Serialize};
-const QTY:i32 = 10;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HammerCandle {
     pub symbol: String,
@@ -75,30 +78,48 @@ impl HammerCandle {
     
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone)]
 pub struct HammerPatternUtil {
-    pub hammer_pattern_ledger: Vec<HammerCandle>,
+    pub hammer_pattern_ledger: HashMap<String, Vec<HammerCandle>>, //keyed by symbol
+    pub hammer_config: HammerConfig,
+    unsaved_hammer_candles: Vec<HammerCandle>,
+    hammer_candle_collection: Option<Collection<HammerCandle>>, //None keeps the instance in memory only, e.g. optimizer runs
 }
 
 impl HammerPatternUtil {
-    pub fn new() -> HammerPatternUtil {
+    pub const COLLECTION_NAME: &'static str = "hammer_candles";
+
+    pub fn new(hammer_config: HammerConfig, hammer_candle_collection: Collection<HammerCandle>) -> HammerPatternUtil {
+        HammerPatternUtil {
+            hammer_pattern_ledger: HashMap::new(),
+            hammer_config,
+            unsaved_hammer_candles: Vec::new(),
+            hammer_candle_collection: Some(hammer_candle_collection),
+        }
+    }
+
+    pub fn new_in_memory(hammer_config: HammerConfig) -> HammerPatternUtil {
         HammerPatternUtil {
-            hammer_pattern_ledger: Vec::new(),
+            hammer_pattern_ledger: HashMap::new(),
+            hammer_config,
+            unsaved_hammer_candles: Vec::new(),
+            hammer_candle_collection: None,
         }
     }
 
     fn add_into_hammer_pattern_ledger(&mut self, candle: HammerCandle) -> () {
-        self.hammer_pattern_ledger.push(candle);
+        self.unsaved_hammer_candles.push(candle.clone());
+        self.hammer_pattern_ledger.entry(candle.symbol.clone()).or_default().push(candle);
     }
 
-    pub fn fetch_hammer_pattern_ledger(&self) -> Vec<HammerCandle> {
-        self.hammer_pattern_ledger.clone()
+    pub fn fetch_hammer_pattern_ledger(&self, symbol: &str) -> Vec<HammerCandle> {
+        self.hammer_pattern_ledger.get(symbol).cloned().unwrap_or_default()
     }
 
-    pub async fn calculate_and_add_ledger(&mut self, stock: &RawStock, hammer_candle_collection: Collection<HammerCandle>) -> Option<TradeSignal> {
+    pub fn calculate_and_add_ledger(&mut self, stock: &RawStock) -> Option<TradeSignal> {
         
         let (is_hammer_candle, calculated_body_size, is_green_candle) =
-        HammerPatternUtil::calculate_candle_metadata(stock.open, stock.high, stock.low, stock.close);
+        HammerPatternUtil::calculate_candle_metadata(&self.hammer_config, stock.open, stock.high, stock.low, stock.close);
 
         if is_hammer_candle {
             let hammer_candle = HammerCandle::new(
@@ -117,20 +138,34 @@ impl HammerPatternUtil {
                 ObjectId::new()
             );
 
-            match hammer_candle_collection.insert_one(hammer_candle.clone(), None).await{
+            self.add_into_hammer_pattern_ledger(hammer_candle);
+            self.check_for_trade_opportunity(stock.symbol.as_str())
+        }else{
+            None
+        }
+        
+    }
+
+    async fn save_hammer_candles(&mut self) {
+        let hammer_candle_collection = match &self.hammer_candle_collection {
+            Some(hammer_candle_collection) => hammer_candle_collection,
+            None => {
+                self.unsaved_hammer_candles.clear();
+                return;
+            }
+        };
+        for hammer_candle in self.unsaved_hammer_candles.drain(..) {
+            match hammer_candle_collection.insert_one(hammer_candle, None).await{
                 Ok(result) => {
                     println!("Hammer candle inserted into the database {:?}", result);
                 },
                 Err(e) => println!("Error while inserting hammer candle into the database => {:?}", e)
             }
-            self.add_into_hammer_pattern_ledger(hammer_candle);
-            self.check_for_trade_opportunity()
-        }else{
-            None
         }
-        
     }
+
     fn calculate_candle_metadata(
+        hammer_config: &HammerConfig,
         open: f32,
         high: f32,
         low: f32,
@@ -138,7 +173,7 @@ impl HammerPatternUtil {
     ) -> (bool, f32, bool) {
         let calculated_body_size: f32 = RawStock::candle_body_size(open, close);
         let is_hammer_candle =
-        HammerPatternUtil::calculate_hammer_candle(calculated_body_size, open, high, low, close);
+        HammerPatternUtil::calculate_hammer_candle(hammer_config, calculated_body_size, open, high, low, close);
         let is_green_candle = RawStock::calculate_if_green_candle(open, close);
 
         (
@@ -149,6 +184,7 @@ impl HammerPatternUtil {
     }
 
     fn calculate_hammer_candle(
+        hammer_config: &HammerConfig,
         calculated_body_size: f32,
         open: f32,
         high: f32,
@@ -179,18 +215,18 @@ impl HammerPatternUtil {
         // println!("upper_wick => {:?}", upper_wick);
         // println!("full_candle_height => {:?}", full_candle_height);
         // println!("body_to_full_candle_ratio => {:?}", body_to_full_candle_ratio);
-        if body_to_full_candle_ratio <= 0.25 {
-            lower_wick > (2.1 * upper_wick)
+        if body_to_full_candle_ratio <= hammer_config.small_body_to_candle_ratio {
+            lower_wick > (hammer_config.small_body_lower_to_upper_wick_ratio * upper_wick)
         } else {
-            lower_wick_to_body_ratio >= 1.75
-                && upper_wick_to_body_ratio <= 1.5
-                && (lower_wick > (2.0 * upper_wick))
+            lower_wick_to_body_ratio >= hammer_config.min_lower_wick_to_body_ratio
+                && upper_wick_to_body_ratio <= hammer_config.max_upper_wick_to_body_ratio
+                && (lower_wick > (hammer_config.lower_to_upper_wick_ratio * upper_wick))
         }
     }
 
     
-    pub fn check_for_trade_opportunity(&mut self) -> Option<TradeSignal> {
-        let previous_hammer_candle_exists = self.hammer_pattern_ledger.last();
+    pub fn check_for_trade_opportunity(&mut self, symbol: &str) -> Option<TradeSignal> {
+        let previous_hammer_candle_exists = self.hammer_pattern_ledger.get(symbol).and_then(|hammer_candles| hammer_candles.last());
         if previous_hammer_candle_exists.is_none()  {
             return None;
         }   
@@ -201,17 +237,18 @@ impl HammerPatternUtil {
             return None;
         }
 
+        let entry_price_ratio = self.hammer_config.entry_price_ratio;
         let (trade_position_type, entry_price) = match previous_hammer_candle.is_green_candle {
-            true => (TradeType::Long, return_2_precision_for_float(previous_hammer_candle.close*0.95)), //95% of the close price can be a good entry point
-            false => (TradeType::Long, return_2_precision_for_float(previous_hammer_candle.open*0.95)) //95% of the open price can be a good entry point
+            true => (TradeType::Long, return_2_precision_for_float(previous_hammer_candle.close*entry_price_ratio)), //default 95% of the close price can be a good entry point
+            false => (TradeType::Long, return_2_precision_for_float(previous_hammer_candle.open*entry_price_ratio)) //default 95% of the open price can be a good entry point
         }; //hammer candle always going to give long trades
 
         // let candle = previous_hammer_candle.clone();
         if entry_price > 0.0 {
-            let trade_sl = return_2_precision_for_float(entry_price*0.95); //5% SL
-            let trade_target = return_2_precision_for_float(entry_price*1.10); //10% Target
+            let trade_sl = return_2_precision_for_float(entry_price*(1.0 - self.hammer_config.sl_percentage/100.0)); //default 5% SL
+            let trade_target = return_2_precision_for_float(entry_price*(1.0 + self.hammer_config.target_percentage/100.0)); //default 10% Target
             // self.hammer_pattern_ledger.pop();
-            match HammerPatternUtil::create_trade_signal(previous_hammer_candle.symbol.clone(), previous_hammer_candle.date.clone(), previous_hammer_candle.close, previous_hammer_candle.high,previous_hammer_candle. low, previous_hammer_candle.open, previous_hammer_candle.volume,previous_hammer_candle.market_time_frame.clone(),trade_position_type, AlgoTypes::HammerPatternAlgo, entry_price, trade_sl, trade_target, previous_hammer_candle.id) {
+            match HammerPatternUtil::create_trade_signal(previous_hammer_candle.symbol.clone(), previous_hammer_candle.date.clone(), previous_hammer_candle.close, previous_hammer_candle.high,previous_hammer_candle. low, previous_hammer_candle.open, previous_hammer_candle.volume,previous_hammer_candle.market_time_frame.clone(),trade_position_type, AlgoTypes::HammerPatternAlgo, self.hammer_config.instance_name.clone(), entry_price, trade_sl, trade_target, self.hammer_config.qty, previous_hammer_candle.id) {
                 Some(trade_signal) => {
                     Some(trade_signal)
                 },
@@ -224,7 +261,7 @@ impl HammerPatternUtil {
 
     }
 
-    fn create_trade_signal(symbol: String, date: String, close: f32, high:f32, low:f32, open:f32, volume:i32, market_time_frame: TimeFrame, trade_position_type: TradeType, algo_type: AlgoTypes, entry_price: f32, trade_sl: f32, trade_target: f32, algo_id: ObjectId ) -> Option<TradeSignal> {
+    fn create_trade_signal(symbol: String, date: String, close: f32, high:f32, low:f32, open:f32, volume:i32, market_time_frame: TimeFrame, trade_position_type: TradeType, algo_type: AlgoTypes, strategy_instance: String, entry_price: f32, trade_sl: f32, trade_target: f32, qty: i32, algo_id: ObjectId ) -> Option<TradeSignal> {
         let trade_signal = TradeSignal::new(
             RawStock::new(
                 symbol,
@@ -238,12 +275,13 @@ impl HammerPatternUtil {
             ),
             trade_position_type,
             algo_type,
+            strategy_instance,
             date_parser::new_current_date_time_in_desired_stock_datetime_format(),
             entry_price,
             trade_sl,
             trade_target,
-            QTY,
-            entry_price*QTY as f32,
+            qty,
+            entry_price*qty as f32,
             ObjectId::new(),
             algo_id
 
@@ -253,6 +291,45 @@ impl HammerPatternUtil {
 
 }
 
+impl Strategy for HammerPatternUtil {
+    fn name(&self) -> String {
+        self.hammer_config.instance_name.clone()
+    }
+
+    fn algo_type(&self) -> AlgoTypes {
+        AlgoTypes::HammerPatternAlgo
+    }
+
+    fn time_frames(&self) -> Vec<TimeFrame> {
+        self.hammer_config.time_frames.clone()
+    }
+
+    fn on_candle(&mut self, raw_stock: &RawStock, market_context: &MarketContext) -> Vec<TradeSignal> {
+        let trade_signal = self.calculate_and_add_ledger(raw_stock);
+
+        //the hammer is still recorded, only the long entry against a non bullish trend is skipped
+        let is_bullish_trend = market_context.current_market_state.as_ref().map_or(false, |current_market_state| {
+            current_market_state.current_candle_market_trend == MarketTrend::Bullish
+        });
+        if self.hammer_config.trade_with_trend && !is_bullish_trend {
+            return Vec::new();
+        }
+        trade_signal.into_iter().collect()
+    }
+
+    fn pattern_count(&self, symbol: &str) -> usize {
+        self.hammer_pattern_ledger.get(symbol).map_or(0, |hammer_candles| hammer_candles.len())
+    }
+
+    fn signal_cooldown_candles(&self) -> usize {
+        self.hammer_config.signal_cooldown_candles
+    }
+
+    fn persist(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(self.save_hammer_candles())
+    }
+}
+
 // pub fn tick_receiver(stock: RawStock) {
     
 
@@ -279,3 +356,135 @@ impl HammerPatternUtil {
     //     // println!("Candle Metadata END");
     // }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //green hammer => small body at the top of a long lower wick
+    fn hammer_stock() -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), "2023-01-02 09:15:00".to_string(), 100.5, 100.6, 95.0, 100.0, 1000, TimeFrame::FiveMinutes)
+    }
+
+    //red hammer => the entry is taken off the open
+    fn red_hammer_stock() -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), "2023-01-02 09:20:00".to_string(), 100.0, 100.6, 95.0, 100.5, 1000, TimeFrame::FiveMinutes)
+    }
+
+    //lower wick only 1.5x the body => not a hammer with the default min_lower_wick_to_body_ratio of 1.75
+    fn short_wick_stock() -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), "2023-01-02 09:25:00".to_string(), 102.0, 102.5, 97.0, 100.0, 1000, TimeFrame::FiveMinutes)
+    }
+
+    //the collection is never reached, the client only connects on the first operation
+    async fn hammer_pattern_util(hammer_config: HammerConfig) -> HammerPatternUtil {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap();
+        HammerPatternUtil::new(
+            hammer_config,
+            mongo_client.database("algo_trading_test").collection(HammerPatternUtil::COLLECTION_NAME),
+        )
+    }
+
+    fn tuned_hammer_config() -> HammerConfig {
+        HammerConfig {
+            instance_name: "HammerTight".to_string(),
+            entry_price_ratio: 1.0,
+            sl_percentage: 2.0,
+            target_percentage: 4.0,
+            qty: 3,
+            ..HammerConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn configured_thresholds_levels_and_instance_are_used() {
+        let default_entry_price = return_2_precision_for_float(100.5 * 0.95);
+        let cases = vec![
+            //config, candle, expected (strategy instance, entry, sl, target, qty, total price)
+            (
+                HammerConfig::default(),
+                hammer_stock(),
+                Some((
+                    "HammerPatternAlgo",
+                    default_entry_price,
+                    return_2_precision_for_float(default_entry_price * 0.95),
+                    return_2_precision_for_float(default_entry_price * 1.10),
+                    10,
+                    default_entry_price * 10.0,
+                )),
+            ),
+            (tuned_hammer_config(), hammer_stock(), Some(("HammerTight", 100.5, 98.49, 104.52, 3, 301.5))),
+            (tuned_hammer_config(), red_hammer_stock(), Some(("HammerTight", 100.5, 98.49, 104.52, 3, 301.5))),
+            (HammerConfig::default(), short_wick_stock(), None),
+            (
+                HammerConfig {
+                    min_lower_wick_to_body_ratio: 1.25,
+                    ..tuned_hammer_config()
+                },
+                short_wick_stock(),
+                Some(("HammerTight", 102.0, 99.96, 106.08, 3, 306.0)),
+            ),
+            (
+                HammerConfig {
+                    small_body_lower_to_upper_wick_ratio: 60.0,
+                    ..tuned_hammer_config()
+                },
+                hammer_stock(),
+                None,
+            ),
+        ];
+        for (hammer_config, stock, expected_levels) in cases {
+            let mut hammer_pattern = hammer_pattern_util(hammer_config.clone()).await;
+            let trade_signal = hammer_pattern.calculate_and_add_ledger(&stock);
+            let levels = trade_signal.as_ref().map(|trade_signal| {
+                (
+                    trade_signal.strategy_instance.as_str(),
+                    trade_signal.entry_price,
+                    trade_signal.trade_sl,
+                    trade_signal.trade_target,
+                    trade_signal.qty,
+                    trade_signal.total_price,
+                )
+            });
+            assert_eq!(levels, expected_levels, "{:?} on {:?}", hammer_config, stock);
+            assert_eq!(hammer_pattern.pattern_count("ADANIGREEN"), expected_levels.map_or(0, |_| 1));
+            if let Some(trade_signal) = trade_signal {
+                assert_eq!(trade_signal.trade_position_type, TradeType::Long);
+                assert_eq!(trade_signal.trade_algo_type, AlgoTypes::HammerPatternAlgo);
+                assert_eq!(trade_signal.raw_stock.date, stock.date);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn renamed_instance_is_a_separate_strategy() {
+        let mut hammer_pattern = hammer_pattern_util(tuned_hammer_config()).await;
+        let mut renamed_hammer_pattern = hammer_pattern_util(HammerConfig {
+            instance_name: "HammerWide".to_string(),
+            ..tuned_hammer_config()
+        })
+        .await;
+        assert_eq!(hammer_pattern.name(), "HammerTight");
+        assert_eq!(renamed_hammer_pattern.name(), "HammerWide");
+
+        let trade_signals = hammer_pattern.on_candle(&hammer_stock(), &MarketContext::default());
+        let renamed_trade_signals = renamed_hammer_pattern.on_candle(&hammer_stock(), &MarketContext::default());
+        assert_eq!(trade_signals[0].strategy_instance, "HammerTight");
+        assert_eq!(renamed_trade_signals[0].strategy_instance, "HammerWide");
+        //same levels, only the instance differs => the dispatcher keys their orders apart
+        assert_eq!(trade_signals[0].entry_price, renamed_trade_signals[0].entry_price);
+    }
+
+    #[tokio::test]
+    async fn trade_with_trend_records_the_hammer_but_skips_the_signal() {
+        let mut hammer_pattern = hammer_pattern_util(HammerConfig {
+            trade_with_trend: true,
+            signal_cooldown_candles: 2,
+            ..tuned_hammer_config()
+        })
+        .await;
+        assert!(hammer_pattern.on_candle(&hammer_stock(), &MarketContext::default()).is_empty());
+        assert_eq!(hammer_pattern.pattern_count("ADANIGREEN"), 1);
+        assert_eq!(hammer_pattern.signal_cooldown_candles(), 2);
+    }
+}
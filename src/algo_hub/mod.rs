@@ -1,3 +1,7 @@
 pub mod hammer_pattern;
+pub mod hammer_config;
 pub mod algo_dispatcher;
-// pub mod shooting_star_pattern;
\ No newline at end of file
+pub mod shooting_star_pattern;
+pub mod shooting_star_config;
+pub mod strategy;
+pub mod strategy_state;
\ No newline at end of file
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::{config_file, enums::TimeFrame};
+
+//Every missing field falls back to the values the shooting star strategy was originally tuned with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShootingStarConfig {
+    //candle detection, the mirror of the hammer thresholds
+    pub small_body_to_candle_ratio: f32,
+    pub small_body_upper_to_lower_wick_ratio: f32,
+    pub upper_to_lower_wick_ratio: f32,
+    pub min_upper_wick_to_body_ratio: f32,
+    pub max_lower_wick_to_body_ratio: f32,
+
+    //risk parameters, the entry sits above the candle and the SL above the entry
+    pub entry_price_ratio: f32,
+    pub sl_percentage: f32,
+    pub target_percentage: f32,
+    pub qty: i32,
+
+    //candles after a signal during which new shooting stars on the same symbol are not traded, 0 => no cooldown
+    pub signal_cooldown_candles: usize,
+
+    //bars the strategy trades on, every time frame listed gets its own worker
+    pub time_frames: Vec<TimeFrame>,
+}
+
+impl Default for ShootingStarConfig {
+    fn default() -> ShootingStarConfig {
+        ShootingStarConfig {
+            small_body_to_candle_ratio: 0.25,
+            small_body_upper_to_lower_wick_ratio: 2.1,
+            upper_to_lower_wick_ratio: 2.0,
+            min_upper_wick_to_body_ratio: 1.75,
+            max_lower_wick_to_body_ratio: 1.5,
+            entry_price_ratio: 1.05,
+            sl_percentage: 5.0,
+            target_percentage: 10.0,
+            qty: 10,
+            signal_cooldown_candles: 0,
+            time_frames: vec![TimeFrame::FiveMinutes],
+        }
+    }
+}
+
+impl ShootingStarConfig {
+    pub const CONFIG_SECTION: &'static str = "shooting_star";
+
+    //.toml files hold a [shooting_star] table, .json files a plain object
+    pub fn load_config(file_path: &str) -> ShootingStarConfig {
+        config_file::load_config(file_path, ShootingStarConfig::CONFIG_SECTION)
+    }
+}
@@ -1,28 +1,63 @@
-#[derive(Debug, Clone, PartialEq, Default)]
-pub struct ShootingStarPattern {
+use std::collections::HashMap;
+
+use crate::common::enums::{AlgoTypes, TradeType, TimeFrame};
+use crate::common::number_parser::return_2_precision_for_float;
+use crate::common::raw_stock::RawStock;
+use crate::common::date_parser;
+use crate::order_manager::trade_signal_keeper::TradeSignal;
+use super::shooting_star_config::ShootingStarConfig;
+use super::strategy::{MarketContext, Strategy};
+use futures::future::BoxFuture;
+use mongodb::bson::oid::ObjectId;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShootingStarCandle {
+    pub symbol: String,
     pub date: String,
     pub open: f32,
     pub high: f32,
     pub low: f32,
     pub close: f32,
-    pub volume: f32,
+    pub volume: i32,
+    pub market_time_frame: TimeFrame,
     pub is_green_candle: bool,
     pub is_shooting_star: bool,
     pub body_size_ratio: f32,
     pub identified_at: String,
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
 }
-impl ShootingStarPattern {
-    pub fn new(date: String, open: f32, high: f32, low: f32, close: f32, volume: f32, is_green_candle: bool, is_shooting_star: bool, body_size_ratio: f32) -> ShootingStarPattern {
-        ShootingStarPattern {
+impl ShootingStarCandle {
+    pub fn new(
+        symbol: String,
+        date: String,
+        open: f32,
+        high: f32,
+        low: f32,
+        close: f32,
+        volume: i32,
+        market_time_frame: TimeFrame,
+        is_green_candle: bool,
+        is_shooting_star: bool,
+        body_size_ratio: f32,
+        identified_at: String,
+        id: ObjectId,
+    ) -> ShootingStarCandle {
+        ShootingStarCandle {
+            symbol,
             date,
             open,
             high,
             low,
             close,
             volume,
+            market_time_frame,
             is_green_candle,
             is_shooting_star,
-            body_size_ratio
+            body_size_ratio,
+            identified_at,
+            id
         }
     }
 
@@ -37,60 +72,269 @@ impl ShootingStarPattern {
     pub fn update_is_green_candle(&mut self, is_green_candle: bool) {
         self.is_green_candle = is_green_candle;
     }
+}
 
+#[derive(Debug, Clone)]
+pub struct ShootingStarPatternUtil {
+    pub shooting_star_pattern_ledger: HashMap<String, Vec<ShootingStarCandle>>, //keyed by symbol
+    unsaved_shooting_star_candles: Vec<ShootingStarCandle>,
+    shooting_star_candle_collection: Collection<ShootingStarCandle>,
+    shooting_star_config: ShootingStarConfig,
 }
-pub fn tick_receiver() {
 
-    let shooting_star_pattern_ledger = Vec::new();
+impl ShootingStarPatternUtil {
+    pub const COLLECTION_NAME: &'static str = "shooting_star_candles";
 
-    let (is_shooting_star_candle, calculated_body_size_ratio, is_green_candle) = calculate_candle_metadata(stock.open, stock.high, stock.low, stock.close);
+    pub fn new(shooting_star_config: ShootingStarConfig, shooting_star_candle_collection: Collection<ShootingStarCandle>) -> ShootingStarPatternUtil {
+        ShootingStarPatternUtil {
+            shooting_star_pattern_ledger: HashMap::new(),
+            unsaved_shooting_star_candles: Vec::new(),
+            shooting_star_candle_collection,
+            shooting_star_config,
+        }
+    }
 
-    if is_shooting_star_candle {
-        let mut shooting_star_pattern = ShootingStarPattern::new(stock.date, stock.open, stock.high, stock.low, stock.close, stock.volume, is_green_candle, is_shooting_star_candle, calculated_body_size_ratio);
-        // shooting_star_pattern.update_shooting_star(is_shooting_star_candle);
-        // shooting_star_pattern.update_body_size_ratio(calculated_body_size_ratio);
-        // shooting_star_pattern.update_is_green_candle(is_green_candle);
-        // stock_5_min_keeper.push(shooting_star_pattern);
+    fn add_into_shooting_star_pattern_ledger(&mut self, candle: ShootingStarCandle) {
+        self.unsaved_shooting_star_candles.push(candle.clone());
+        self.shooting_star_pattern_ledger.entry(candle.symbol.clone()).or_default().push(candle);
     }
 
-    fn add_into_shooting_star_pattern_ledger(candle: ShootingStarPattern) -> () {
-        shooting_star_pattern_ledger.push(candle);
+    pub fn fetch_shooting_star_pattern_ledger(&self, symbol: &str) -> Vec<ShootingStarCandle> {
+        self.shooting_star_pattern_ledger.get(symbol).cloned().unwrap_or_default()
+    }
+
+    pub fn calculate_and_add_ledger(&mut self, stock: &RawStock) -> Option<TradeSignal> {
+
+        let (is_shooting_star_candle, calculated_body_size, is_green_candle) =
+        ShootingStarPatternUtil::calculate_candle_metadata(&self.shooting_star_config, stock.open, stock.high, stock.low, stock.close);
+
+        if is_shooting_star_candle {
+            let shooting_star_candle = ShootingStarCandle::new(
+                stock.symbol.clone(),
+                stock.date.clone(),
+                stock.open,
+                stock.high,
+                stock.low,
+                stock.close,
+                stock.volume,
+                stock.market_time_frame.clone(),
+                is_green_candle,
+                is_shooting_star_candle,
+                calculated_body_size,
+                date_parser::new_current_date_time_in_desired_stock_datetime_format(),
+                ObjectId::new()
+            );
+
+            self.add_into_shooting_star_pattern_ledger(shooting_star_candle);
+            self.check_for_trade_opportunity(stock.symbol.as_str())
+        }else{
+            None
+        }
+
     }
 
-    pub fn fetch_shooting_star_pattern_ledger() -> Vec<ShootingStarPattern> {
-        shooting_star_pattern_ledger
+    async fn save_shooting_star_candles(&mut self) {
+        for shooting_star_candle in self.unsaved_shooting_star_candles.drain(..) {
+            match self.shooting_star_candle_collection.insert_one(shooting_star_candle, None).await{
+                Ok(result) => {
+                    println!("Shooting star candle inserted into the database {:?}", result);
+                },
+                Err(e) => println!("Error while inserting shooting star candle into the database => {:?}", e)
+            }
+        }
     }
 
-    fn calculate_candle_metadata(open: f32, high: f32, low: f32, close: f32) -> (bool, bool, f32, bool) {
-        let calculated_body_size_ratio:f32 = candle_body_size(open, close);
-        let is_shooting_star_candle = calculate_shooting_star_candle(calculated_body_size_ratio, open, high, low, close);
-        let is_green_candle = calculate_if_green_candle(open, close);
+    fn calculate_candle_metadata(
+        shooting_star_config: &ShootingStarConfig,
+        open: f32,
+        high: f32,
+        low: f32,
+        close: f32,
+    ) -> (bool, f32, bool) {
+        let calculated_body_size: f32 = RawStock::candle_body_size(open, close);
+        let is_shooting_star_candle =
+        ShootingStarPatternUtil::calculate_shooting_star_candle(shooting_star_config, calculated_body_size, open, high, low, close);
+        let is_green_candle = RawStock::calculate_if_green_candle(open, close);
 
-        (is_shooting_star_candle, calculated_body_size_ratio, is_green_candle)
-        
+        (
+            is_shooting_star_candle,
+            calculated_body_size,
+            is_green_candle,
+        )
     }
 
-    fn calculate_shooting_star_candle(calculated_body_size_ratio:f32,open: f32, high: f32, low: f32, close: f32) -> bool {
-        let lower_wick = if open > close { close - low } else { open - low };
-        let upper_wick = if open > close { high - open } else { high - close };
+    //mirror of the hammer check => long upper wick and a small lower wick
+    fn calculate_shooting_star_candle(
+        shooting_star_config: &ShootingStarConfig,
+        calculated_body_size: f32,
+        open: f32,
+        high: f32,
+        low: f32,
+        close: f32,
+    ) -> bool {
+        let lower_wick = if open > close {
+            close - low
+        } else {
+            open - low
+        };
+        let upper_wick = if open > close {
+            high - open
+        } else {
+            high - close
+        };
+
+        let lower_wick_to_body_ratio = lower_wick / calculated_body_size;
+        let upper_wick_to_body_ratio = upper_wick / calculated_body_size;
 
-        let lower_wick_to_body_ratio = lower_wick / calculated_body_size_ratio;
-        let upper_wick_to_body_ratio = upper_wick / calculated_body_size_ratio;
-        
         let full_candle_height = high - low;
-        let body_to_full_candle_ratio = calculated_body_size_ratio / full_candle_height;
-
-        // print!("lower_wick_to_body_ratio => {:?} ", lower_wick_to_body_ratio);
-        // print!("upper_wick_to_body_ratio => {:?} ", upper_wick_to_body_ratio);
-        // println!("calculated_body_size_ratio => {:?}", calculated_body_size_ratio);
-        // println!("lower_wick => {:?}", lower_wick);
-        // println!("upper_wick => {:?}", upper_wick);
-        // println!("full_candle_height => {:?}", full_candle_height);
-        // println!("body_to_full_candle_ratio => {:?}", body_to_full_candle_ratio);
-        if body_to_full_candle_ratio <= 0.25 {
-            upper_wick > (2.1 * lower_wick)
-        }else {
-            upper_wick_to_body_ratio >= 1.75 && lower_wick_to_body_ratio <= 1.5 && (upper_wick > (2.0 * lower_wick))
+        let body_to_full_candle_ratio = calculated_body_size / full_candle_height;
+
+        if body_to_full_candle_ratio <= shooting_star_config.small_body_to_candle_ratio {
+            upper_wick > (shooting_star_config.small_body_upper_to_lower_wick_ratio * lower_wick)
+        } else {
+            upper_wick_to_body_ratio >= shooting_star_config.min_upper_wick_to_body_ratio
+                && lower_wick_to_body_ratio <= shooting_star_config.max_lower_wick_to_body_ratio
+                && (upper_wick > (shooting_star_config.upper_to_lower_wick_ratio * lower_wick))
         }
     }
+
+    pub fn check_for_trade_opportunity(&mut self, symbol: &str) -> Option<TradeSignal> {
+        let previous_shooting_star_candle = self.shooting_star_pattern_ledger.get(symbol)?.last()?;
+
+        if date_parser::new_current_date_time_in_desired_stock_datetime_format() < previous_shooting_star_candle.date  {
+            return None;
+        }
+
+        let entry_price_ratio = self.shooting_star_config.entry_price_ratio;
+        let (trade_position_type, entry_price) = match previous_shooting_star_candle.is_green_candle {
+            true => (TradeType::Short, return_2_precision_for_float(previous_shooting_star_candle.open*entry_price_ratio)), //default 105% of the open price can be a good entry point
+            false => (TradeType::Short, return_2_precision_for_float(previous_shooting_star_candle.close*entry_price_ratio)) //default 105% of the close price can be a good entry point
+        }; //shooting star candle always going to give short trades
+
+        if entry_price > 0.0 {
+            let trade_sl = return_2_precision_for_float(entry_price*(1.0 + self.shooting_star_config.sl_percentage/100.0)); //default 5% SL above the entry
+            let trade_target = return_2_precision_for_float(entry_price*(1.0 - self.shooting_star_config.target_percentage/100.0)); //default 10% Target below the entry
+            ShootingStarPatternUtil::create_trade_signal(previous_shooting_star_candle.symbol.clone(), previous_shooting_star_candle.date.clone(), previous_shooting_star_candle.close, previous_shooting_star_candle.high, previous_shooting_star_candle.low, previous_shooting_star_candle.open, previous_shooting_star_candle.volume, previous_shooting_star_candle.market_time_frame.clone(), trade_position_type, AlgoTypes::ShootingStarPatternAlgo, self.name(), entry_price, trade_sl, trade_target, self.shooting_star_config.qty, previous_shooting_star_candle.id)
+        }else{
+            None
+        }
+
+    }
+
+    fn create_trade_signal(symbol: String, date: String, close: f32, high:f32, low:f32, open:f32, volume:i32, market_time_frame: TimeFrame, trade_position_type: TradeType, algo_type: AlgoTypes, strategy_instance: String, entry_price: f32, trade_sl: f32, trade_target: f32, qty: i32, algo_id: ObjectId ) -> Option<TradeSignal> {
+        let trade_signal = TradeSignal::new(
+            RawStock::new(
+                symbol,
+                date,
+                close,
+                high,
+                low,
+                open,
+                volume,
+                market_time_frame,
+            ),
+            trade_position_type,
+            algo_type,
+            strategy_instance,
+            date_parser::new_current_date_time_in_desired_stock_datetime_format(),
+            entry_price,
+            trade_sl,
+            trade_target,
+            qty,
+            entry_price*qty as f32,
+            ObjectId::new(),
+            algo_id
+        );
+        Some(trade_signal)
+    }
+
+}
+
+impl Strategy for ShootingStarPatternUtil {
+    fn name(&self) -> String {
+        AlgoTypes::ShootingStarPatternAlgo.to_string()
+    }
+
+    fn algo_type(&self) -> AlgoTypes {
+        AlgoTypes::ShootingStarPatternAlgo
+    }
+
+    fn on_candle(&mut self, raw_stock: &RawStock, _market_context: &MarketContext) -> Vec<TradeSignal> {
+        self.calculate_and_add_ledger(raw_stock).into_iter().collect()
+    }
+
+    fn pattern_count(&self, symbol: &str) -> usize {
+        self.shooting_star_pattern_ledger.get(symbol).map_or(0, |shooting_star_candles| shooting_star_candles.len())
+    }
+
+    fn time_frames(&self) -> Vec<TimeFrame> {
+        self.shooting_star_config.time_frames.clone()
+    }
+
+    fn signal_cooldown_candles(&self) -> usize {
+        self.shooting_star_config.signal_cooldown_candles
+    }
+
+    fn persist(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(self.save_shooting_star_candles())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //red shooting star => small body at the bottom of a long upper wick
+    fn shooting_star_stock() -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), "2023-01-02 09:15:00".to_string(), 99.0, 104.0, 98.8, 100.0, 1000, TimeFrame::FiveMinutes)
+    }
+
+    //the collection is never reached, the client only connects on the first operation
+    async fn shooting_star_pattern_util(shooting_star_config: ShootingStarConfig) -> ShootingStarPatternUtil {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap();
+        ShootingStarPatternUtil::new(
+            shooting_star_config,
+            mongo_client.database("algo_trading_test").collection(ShootingStarPatternUtil::COLLECTION_NAME),
+        )
+    }
+
+    #[tokio::test]
+    async fn default_config_keeps_the_original_levels() {
+        let mut shooting_star_pattern = shooting_star_pattern_util(ShootingStarConfig::default()).await;
+        let trade_signal = shooting_star_pattern.calculate_and_add_ledger(&shooting_star_stock()).unwrap();
+
+        let entry_price = return_2_precision_for_float(99.0 * 1.05);
+        assert_eq!(trade_signal.trade_position_type, TradeType::Short);
+        assert_eq!(trade_signal.entry_price, entry_price);
+        assert_eq!(trade_signal.trade_sl, return_2_precision_for_float(entry_price * 1.05));
+        assert_eq!(trade_signal.trade_target, return_2_precision_for_float(entry_price * 0.90));
+        assert_eq!(trade_signal.qty, 10);
+    }
+
+    #[tokio::test]
+    async fn configured_thresholds_and_levels_are_used() {
+        let mut shooting_star_pattern = shooting_star_pattern_util(ShootingStarConfig {
+            entry_price_ratio: 1.0,
+            sl_percentage: 2.0,
+            target_percentage: 4.0,
+            qty: 3,
+            ..ShootingStarConfig::default()
+        })
+        .await;
+        let trade_signal = shooting_star_pattern.calculate_and_add_ledger(&shooting_star_stock()).unwrap();
+        assert_eq!(trade_signal.entry_price, 99.0);
+        assert_eq!(trade_signal.trade_sl, 100.98);
+        assert_eq!(trade_signal.trade_target, 95.04);
+        assert_eq!(trade_signal.qty, 3);
+        assert_eq!(trade_signal.total_price, 297.0);
+
+        //a stricter wick ratio no longer sees the candle as a shooting star
+        let mut strict_shooting_star_pattern_util = shooting_star_pattern_util(ShootingStarConfig {
+            small_body_upper_to_lower_wick_ratio: 25.0,
+            ..ShootingStarConfig::default()
+        })
+        .await;
+        assert!(strict_shooting_star_pattern_util.calculate_and_add_ledger(&shooting_star_stock()).is_none());
+        assert_eq!(strict_shooting_star_pattern_util.pattern_count("ADANIGREEN"), 0);
+    }
 }
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use futures::future::BoxFuture;
+use mongodb::{Collection, Database};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    common::{
+        enums::{AlgoTypes, TimeFrame},
+        raw_stock::RawStock,
+    },
+    data_consumer::current_market_state::CurrentMarketState,
+    order_manager::trade_signal_keeper::TradeSignal,
+};
+
+use super::{
+    hammer_config::HammerConfig, hammer_pattern::HammerPatternUtil, shooting_star_config::ShootingStarConfig,
+    shooting_star_pattern::ShootingStarPatternUtil,
+    strategy_state::{StrategyState, StrategyStates},
+};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MarketContext {
+    pub current_market_state: Option<CurrentMarketState>,
+    pub average_true_range: Option<f32>,
+}
+
+impl MarketContext {
+    pub fn new(current_market_state: Option<CurrentMarketState>, average_true_range: Option<f32>) -> MarketContext {
+        MarketContext {
+            current_market_state,
+            average_true_range,
+        }
+    }
+}
+
+pub trait Strategy: Send {
+    fn name(&self) -> String;
+
+    fn algo_type(&self) -> AlgoTypes;
+
+    //bars the strategy is fed with, the candles of any other time frame never reach on_candle
+    fn time_frames(&self) -> Vec<TimeFrame> {
+        vec![TimeFrame::FiveMinutes]
+    }
+
+    fn on_candle(&mut self, raw_stock: &RawStock, market_context: &MarketContext) -> Vec<TradeSignal>;
+
+    //patterns identified so far on the symbol
+    fn pattern_count(&self, symbol: &str) -> usize;
+
+    //candles after a signal during which further signals on the same symbol are dropped
+    fn signal_cooldown_candles(&self) -> usize {
+        0
+    }
+
+    //persistence hook => called after every on_candle so a strategy can flush what it identified into its own collection
+    fn persist(&mut self) -> BoxFuture<'_, ()>;
+}
+
+//Config of every strategy keyed by the config section it is read from (hammer_configs, shooting_star, ..).
+//Loaded once in main.rs and shared through RootSystemConfig, every factory only reads its own section
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StrategyConfigs {
+    config_sections: HashMap<String, Value>,
+}
+
+impl StrategyConfigs {
+    pub fn new() -> StrategyConfigs {
+        StrategyConfigs::default()
+    }
+
+    pub fn with_section<T: Serialize>(mut self, config_section: &str, config: &T) -> StrategyConfigs {
+        self.config_sections.insert(config_section.to_string(), serde_json::to_value(config).unwrap());
+        self
+    }
+
+    //a missing section => the strategy's default config
+    pub fn section<T: DeserializeOwned + Default>(&self, config_section: &str) -> Result<T, String> {
+        match self.config_sections.get(config_section) {
+            Some(config) => serde_json::from_value(config.clone()).map_err(|e| e.to_string()),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+pub type BuildStrategies = fn(&StrategyConfigs, &Database) -> Result<Vec<Box<dyn Strategy>>, String>;
+
+//builds every instance of one algo type from the configs, keyed by the section they read
+pub struct StrategyFactory {
+    pub algo_type: AlgoTypes,
+    pub config_section: &'static str,
+    pub build_strategies: BuildStrategies,
+}
+
+//the registration table => a new algorithm only needs an AlgoTypes variant, its config section and an entry here
+pub const STRATEGY_FACTORIES: [StrategyFactory; 2] = [
+    StrategyFactory {
+        algo_type: AlgoTypes::HammerPatternAlgo,
+        config_section: HammerConfig::COLLECTION_NAME,
+        build_strategies: build_hammer_strategies,
+    },
+    StrategyFactory {
+        algo_type: AlgoTypes::ShootingStarPatternAlgo,
+        config_section: ShootingStarConfig::CONFIG_SECTION,
+        build_strategies: build_shooting_star_strategies,
+    },
+];
+
+//one hammer instance per config so differently tuned hammers can run side by side
+fn build_hammer_strategies(strategy_configs: &StrategyConfigs, database_instance: &Database) -> Result<Vec<Box<dyn Strategy>>, String> {
+    let mut hammer_configs: Vec<HammerConfig> = strategy_configs.section(HammerConfig::COLLECTION_NAME)?;
+    if hammer_configs.is_empty() {
+        hammer_configs.push(HammerConfig::default());
+    }
+    Ok(hammer_configs
+        .into_iter()
+        .map(|hammer_config| {
+            Box::new(HammerPatternUtil::new(
+                hammer_config,
+                database_instance.collection(HammerPatternUtil::COLLECTION_NAME),
+            )) as Box<dyn Strategy>
+        })
+        .collect())
+}
+
+fn build_shooting_star_strategies(strategy_configs: &StrategyConfigs, database_instance: &Database) -> Result<Vec<Box<dyn Strategy>>, String> {
+    Ok(vec![Box::new(ShootingStarPatternUtil::new(
+        strategy_configs.section(ShootingStarConfig::CONFIG_SECTION)?,
+        database_instance.collection(ShootingStarPatternUtil::COLLECTION_NAME),
+    ))])
+}
+
+//Owned by one worker for its whole life, so the strategies keep their pattern history from one candle to the next
+#[derive(Default)]
+pub struct StrategyRegistry {
+    strategies: Vec<Box<dyn Strategy>>,
+    strategy_states: StrategyStates,
+    strategy_state_collection: Option<Collection<StrategyState>>, //None => the states are only kept in memory
+}
+
+impl StrategyRegistry {
+    const ORDERED_TIME_FRAMES: [TimeFrame; 7] = [
+        TimeFrame::OneMinute,
+        TimeFrame::ThreeMinutes,
+        TimeFrame::FiveMinutes,
+        TimeFrame::FifteenMinutes,
+        TimeFrame::OneDay,
+        TimeFrame::OneWeek,
+        TimeFrame::OneMonth,
+    ];
+
+    pub fn new(strategy_states: StrategyStates) -> StrategyRegistry {
+        StrategyRegistry {
+            strategies: Vec::new(),
+            strategy_states,
+            strategy_state_collection: None,
+        }
+    }
+
+    //strategy instances per tradeable algo type, built by its entry in STRATEGY_FACTORIES
+    pub fn from_algo_types(
+        tradeable_algo_types: &[AlgoTypes],
+        strategy_configs: &StrategyConfigs,
+        database_instance: &Database,
+        strategy_states: StrategyStates,
+    ) -> StrategyRegistry {
+        let mut strategy_registry = StrategyRegistry::new(strategy_states);
+        strategy_registry.strategy_state_collection = Some(database_instance.collection(StrategyStates::COLLECTION_NAME));
+        for algo_type in tradeable_algo_types.iter() {
+            let strategy_factory = match STRATEGY_FACTORIES.iter().find(|strategy_factory| strategy_factory.algo_type == *algo_type) {
+                Some(strategy_factory) => strategy_factory,
+                None => {
+                    println!("No strategy factory registered for {}", algo_type);
+                    continue;
+                }
+            };
+            match (strategy_factory.build_strategies)(strategy_configs, database_instance) {
+                Ok(strategies) => {
+                    for strategy in strategies {
+                        strategy_registry.register(strategy);
+                    }
+                }
+                Err(e) => {
+                    println!("Error while building {} from the {} config => {}", strategy_factory.algo_type, strategy_factory.config_section, e);
+                }
+            }
+        }
+        strategy_registry
+    }
+
+    pub fn register(&mut self, strategy: Box<dyn Strategy>) {
+        self.strategies.push(strategy);
+    }
+
+    pub fn get_strategy_names(&self) -> Vec<String> {
+        self.strategies.iter().map(|strategy| strategy.name()).collect()
+    }
+
+    //every time frame at least one registered strategy trades on, shortest first
+    pub fn time_frames(&self) -> Vec<TimeFrame> {
+        let subscribed_time_frames: Vec<TimeFrame> = self.strategies.iter().flat_map(|strategy| strategy.time_frames()).collect();
+        StrategyRegistry::ORDERED_TIME_FRAMES
+            .iter()
+            .filter(|time_frame| subscribed_time_frames.contains(time_frame))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_subscribed_to(&self, time_frame: &TimeFrame) -> bool {
+        self.strategies.iter().any(|strategy| strategy.time_frames().contains(time_frame))
+    }
+
+    pub fn strategies_mut(&mut self) -> &mut Vec<Box<dyn Strategy>> {
+        &mut self.strategies
+    }
+
+    pub fn strategy_states(&self) -> StrategyStates {
+        self.strategy_states.clone()
+    }
+
+    //every strategy trading on the candle's time frame sees it and persists what it identified, signals inside a strategy's cooldown are dropped
+    pub async fn on_candle(&mut self, raw_stock: &RawStock, market_context: &MarketContext) -> Vec<TradeSignal> {
+        let mut trade_signals = Vec::new();
+        for strategy in self.strategies.iter_mut() {
+            if !strategy.time_frames().contains(&raw_stock.market_time_frame) {
+                continue;
+            }
+            let strategy_trade_signals = strategy.on_candle(raw_stock, market_context);
+            strategy.persist().await;
+            trade_signals.extend(self.strategy_states.record_candle(strategy.as_ref(), raw_stock, strategy_trade_signals));
+        }
+        if let Some(strategy_state_collection) = &self.strategy_state_collection {
+            self.strategy_states.save(&self.get_strategy_names(), strategy_state_collection).await;
+        }
+        trade_signals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::TradeType;
+    use mongodb::bson::oid::ObjectId;
+
+    //signals on every candle it is fed
+    struct SignalEveryCandleStrategy {
+        name: String,
+        time_frames: Vec<TimeFrame>,
+        signal_cooldown_candles: usize,
+    }
+
+    impl Strategy for SignalEveryCandleStrategy {
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn algo_type(&self) -> AlgoTypes {
+            AlgoTypes::HammerPatternAlgo
+        }
+
+        fn time_frames(&self) -> Vec<TimeFrame> {
+            self.time_frames.clone()
+        }
+
+        fn on_candle(&mut self, raw_stock: &RawStock, _market_context: &MarketContext) -> Vec<TradeSignal> {
+            vec![TradeSignal::new(
+                raw_stock.clone(),
+                TradeType::Long,
+                AlgoTypes::HammerPatternAlgo,
+                self.name.clone(),
+                raw_stock.date.clone(),
+                100.0,
+                95.0,
+                110.0,
+                10,
+                1000.0,
+                ObjectId::new(),
+                ObjectId::new(),
+            )]
+        }
+
+        fn pattern_count(&self, _symbol: &str) -> usize {
+            0
+        }
+
+        fn signal_cooldown_candles(&self) -> usize {
+            self.signal_cooldown_candles
+        }
+
+        fn persist(&mut self) -> BoxFuture<'_, ()> {
+            Box::pin(async {})
+        }
+    }
+
+    fn strategy(name: &str, time_frames: Vec<TimeFrame>, signal_cooldown_candles: usize) -> Box<dyn Strategy> {
+        Box::new(SignalEveryCandleStrategy {
+            name: name.to_string(),
+            time_frames,
+            signal_cooldown_candles,
+        })
+    }
+
+    fn test_candle(date: &str, market_time_frame: TimeFrame) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), 100.0, 101.0, 99.0, 100.5, 1000, market_time_frame)
+    }
+
+    fn signalled_strategies(trade_signals: &[TradeSignal]) -> Vec<String> {
+        trade_signals.iter().map(|trade_signal| trade_signal.strategy_instance.clone()).collect()
+    }
+
+    //the client only connects on the first operation, building the strategies never reaches it
+    async fn database_instance() -> Database {
+        mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap().database("algo_trading_test")
+    }
+
+    #[tokio::test]
+    async fn strategies_only_see_their_own_time_frames() {
+        let mut strategy_registry = StrategyRegistry::new(StrategyStates::new());
+        strategy_registry.register(strategy("Daily", vec![TimeFrame::OneDay], 0));
+        strategy_registry.register(strategy("Intraday", vec![TimeFrame::FifteenMinutes, TimeFrame::OneMinute], 0));
+        strategy_registry.register(strategy("FiveMinutes", vec![TimeFrame::FiveMinutes, TimeFrame::OneMinute], 0));
+
+        assert_eq!(
+            strategy_registry.time_frames(),
+            vec![TimeFrame::OneMinute, TimeFrame::FiveMinutes, TimeFrame::FifteenMinutes, TimeFrame::OneDay]
+        );
+        assert!(strategy_registry.is_subscribed_to(&TimeFrame::OneDay));
+        assert!(!strategy_registry.is_subscribed_to(&TimeFrame::ThreeMinutes));
+
+        let market_context = MarketContext::default();
+        let trade_signals = strategy_registry.on_candle(&test_candle("2023-08-10 09:15:00+0530", TimeFrame::OneMinute), &market_context).await;
+        assert_eq!(signalled_strategies(&trade_signals), vec!["Intraday", "FiveMinutes"]);
+        let trade_signals = strategy_registry.on_candle(&test_candle("2023-08-10 09:15:00+0530", TimeFrame::FiveMinutes), &market_context).await;
+        assert_eq!(signalled_strategies(&trade_signals), vec!["FiveMinutes"]);
+        assert!(strategy_registry.on_candle(&test_candle("2023-08-10 09:15:00+0530", TimeFrame::ThreeMinutes), &market_context).await.is_empty());
+
+        //a strategy that never saw a candle has no state
+        let strategy_states = strategy_registry.strategy_states();
+        assert!(strategy_states.get_strategy_state("Daily").is_none());
+        assert_eq!(strategy_states.get_strategy_state("Intraday").unwrap().symbol_states["ADANIGREEN"].processed_candle_count, 1);
+        assert_eq!(strategy_states.get_strategy_state("FiveMinutes").unwrap().symbol_states["ADANIGREEN"].processed_candle_count, 2);
+    }
+
+    #[tokio::test]
+    async fn registry_drops_signals_inside_each_strategys_cooldown() {
+        let mut strategy_registry = StrategyRegistry::new(StrategyStates::new());
+        strategy_registry.register(strategy("NoCooldown", vec![TimeFrame::FiveMinutes], 0));
+        strategy_registry.register(strategy("TwoCandleCooldown", vec![TimeFrame::FiveMinutes], 2));
+
+        let mut signalled_strategies_per_candle = Vec::new();
+        for date in ["2023-08-10 09:15:00+0530", "2023-08-10 09:20:00+0530", "2023-08-10 09:25:00+0530", "2023-08-10 09:30:00+0530"] {
+            let trade_signals = strategy_registry.on_candle(&test_candle(date, TimeFrame::FiveMinutes), &MarketContext::default()).await;
+            signalled_strategies_per_candle.push(signalled_strategies(&trade_signals));
+        }
+        assert_eq!(
+            signalled_strategies_per_candle,
+            vec![
+                vec!["NoCooldown".to_string(), "TwoCandleCooldown".to_string()],
+                vec!["NoCooldown".to_string()],
+                vec!["NoCooldown".to_string()],
+                vec!["NoCooldown".to_string(), "TwoCandleCooldown".to_string()],
+            ]
+        );
+        let symbol_state = strategy_registry.strategy_states().get_strategy_state("TwoCandleCooldown").unwrap().symbol_states["ADANIGREEN"].clone();
+        assert_eq!(symbol_state.trade_signal_count, 2);
+        assert_eq!(symbol_state.skipped_trade_signal_count, 2);
+    }
+
+    #[tokio::test]
+    async fn factories_build_the_tradeable_algo_types_from_their_config_sections() {
+        let hammer_configs = vec![
+            HammerConfig {
+                instance_name: "HammerTight".to_string(),
+                ..HammerConfig::default()
+            },
+            HammerConfig {
+                instance_name: "HammerWide".to_string(),
+                sl_percentage: 7.5,
+                ..HammerConfig::default()
+            },
+        ];
+        let strategy_configs = StrategyConfigs::new().with_section(HammerConfig::COLLECTION_NAME, &hammer_configs);
+        assert_eq!(strategy_configs.section::<Vec<HammerConfig>>(HammerConfig::COLLECTION_NAME), Ok(hammer_configs));
+        //no shooting_star section => its default config
+        assert_eq!(strategy_configs.section::<ShootingStarConfig>(ShootingStarConfig::CONFIG_SECTION), Ok(ShootingStarConfig::default()));
+
+        let database_instance = database_instance().await;
+        let strategy_registry = StrategyRegistry::from_algo_types(
+            &[AlgoTypes::ShootingStarPatternAlgo, AlgoTypes::HammerPatternAlgo],
+            &strategy_configs,
+            &database_instance,
+            StrategyStates::new(),
+        );
+        assert_eq!(strategy_registry.get_strategy_names(), vec!["ShootingStarPatternAlgo", "HammerTight", "HammerWide"]);
+
+        let strategy_registry = StrategyRegistry::from_algo_types(&[AlgoTypes::ShootingStarPatternAlgo], &strategy_configs, &database_instance, StrategyStates::new());
+        assert_eq!(strategy_registry.get_strategy_names(), vec!["ShootingStarPatternAlgo"]);
+
+        //a section of the wrong shape only drops its own algo type
+        let strategy_configs = StrategyConfigs::new().with_section(HammerConfig::COLLECTION_NAME, &"not a list of configs");
+        assert!(strategy_configs.section::<Vec<HammerConfig>>(HammerConfig::COLLECTION_NAME).is_err());
+        let strategy_registry = StrategyRegistry::from_algo_types(
+            &[AlgoTypes::HammerPatternAlgo, AlgoTypes::ShootingStarPatternAlgo],
+            &strategy_configs,
+            &database_instance,
+            StrategyStates::new(),
+        );
+        assert_eq!(strategy_registry.get_strategy_names(), vec!["ShootingStarPatternAlgo"]);
+    }
+}
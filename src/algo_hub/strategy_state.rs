@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::TryStreamExt;
+use mongodb::{
+    bson::doc,
+    options::{FindOptions, ReplaceOptions},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{enums::AlgoTypes, raw_stock::RawStock},
+    order_manager::trade_signal_keeper::TradeSignal,
+};
+
+use super::strategy::Strategy;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct SymbolStrategyState {
+    pub processed_candle_count: usize,
+    pub pattern_count: usize,
+    pub trade_signal_count: usize,
+    pub skipped_trade_signal_count: usize, //dropped inside the cooldown
+    pub cooldown_candles_left: usize,
+    pub last_candle_at: String,
+    pub last_trade_signal_at: Option<String>, //date of the candle the signal came from
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrategyState {
+    pub strategy_name: String,
+    pub algo_type: AlgoTypes,
+    pub symbol_states: HashMap<String, SymbolStrategyState>,
+}
+
+//Shared handle on what every strategy instance has seen so far, keyed by strategy name.
+//The registries of all workers write into the same handle, anything holding a clone can read it while they run.
+#[derive(Debug, Clone, Default)]
+pub struct StrategyStates {
+    strategy_states: Arc<Mutex<HashMap<String, StrategyState>>>,
+}
+
+impl StrategyStates {
+    pub const COLLECTION_NAME: &'static str = "strategy_states";
+
+    pub fn new() -> StrategyStates {
+        StrategyStates::default()
+    }
+
+    //counts the candle for the strategy and the symbol, returns the signals that are outside the strategy's cooldown
+    pub fn record_candle(&self, strategy: &dyn Strategy, raw_stock: &RawStock, trade_signals: Vec<TradeSignal>) -> Vec<TradeSignal> {
+        let mut strategy_states = self.strategy_states.lock().unwrap();
+        let strategy_state = strategy_states.entry(strategy.name()).or_insert_with(|| StrategyState {
+            strategy_name: strategy.name(),
+            algo_type: strategy.algo_type(),
+            symbol_states: HashMap::new(),
+        });
+        let symbol_state = strategy_state.symbol_states.entry(raw_stock.symbol.clone()).or_default();
+        symbol_state.processed_candle_count += 1;
+        symbol_state.pattern_count = strategy.pattern_count(raw_stock.symbol.as_str());
+        symbol_state.last_candle_at = raw_stock.date.clone();
+
+        if symbol_state.cooldown_candles_left > 0 {
+            symbol_state.cooldown_candles_left -= 1;
+            symbol_state.skipped_trade_signal_count += trade_signals.len();
+            return Vec::new();
+        }
+        if let Some(trade_signal) = trade_signals.last() {
+            symbol_state.trade_signal_count += trade_signals.len();
+            symbol_state.last_trade_signal_at = Some(trade_signal.raw_stock.date.clone());
+            symbol_state.cooldown_candles_left = strategy.signal_cooldown_candles();
+        }
+        trade_signals
+    }
+
+    pub fn get_strategy_state(&self, strategy_name: &str) -> Option<StrategyState> {
+        self.strategy_states.lock().unwrap().get(strategy_name).cloned()
+    }
+
+    pub fn get_strategy_states(&self) -> Vec<StrategyState> {
+        let mut strategy_states: Vec<StrategyState> = self.strategy_states.lock().unwrap().values().cloned().collect();
+        strategy_states.sort_by(|a, b| a.strategy_name.cmp(&b.strategy_name));
+        strategy_states
+    }
+
+    //one document per strategy, replaced with the latest state => readable while the workers run
+    pub async fn save(&self, strategy_names: &[String], strategy_state_collection: &Collection<StrategyState>) {
+        for strategy_name in strategy_names.iter() {
+            let strategy_state = match self.get_strategy_state(strategy_name) {
+                Some(strategy_state) => strategy_state,
+                None => continue,
+            };
+            let filter = doc! {"strategy_name": strategy_name.clone()};
+            let options = ReplaceOptions::builder().upsert(true).build();
+            if let Err(e) = strategy_state_collection.replace_one(filter, strategy_state, options).await {
+                println!("Error while saving the strategy state of {} => {:?}", strategy_name, e);
+            }
+        }
+    }
+
+    pub async fn fetch_from_collection(strategy_state_collection: &Collection<StrategyState>) -> Vec<StrategyState> {
+        let options = FindOptions::builder().sort(doc! {"strategy_name": 1}).build();
+        let cursor = match strategy_state_collection.find(doc! {}, options).await {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                println!("Error while fetching the strategy states => {:?}", e);
+                return Vec::new();
+            }
+        };
+        match cursor.try_collect::<Vec<StrategyState>>().await {
+            Ok(strategy_states) => strategy_states,
+            Err(e) => {
+                println!("Error while fetching the strategy states => {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.strategy_states.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        algo_hub::strategy::MarketContext,
+        common::enums::{TimeFrame, TradeType},
+    };
+    use futures::future::BoxFuture;
+    use mongodb::bson::oid::ObjectId;
+
+    struct CooldownStrategy {
+        signal_cooldown_candles: usize,
+    }
+
+    impl Strategy for CooldownStrategy {
+        fn name(&self) -> String {
+            "Cooldown".to_string()
+        }
+
+        fn algo_type(&self) -> AlgoTypes {
+            AlgoTypes::ShootingStarPatternAlgo
+        }
+
+        fn on_candle(&mut self, _raw_stock: &RawStock, _market_context: &MarketContext) -> Vec<TradeSignal> {
+            Vec::new()
+        }
+
+        fn pattern_count(&self, _symbol: &str) -> usize {
+            0
+        }
+
+        fn signal_cooldown_candles(&self) -> usize {
+            self.signal_cooldown_candles
+        }
+
+        fn persist(&mut self) -> BoxFuture<'_, ()> {
+            Box::pin(async {})
+        }
+    }
+
+    fn test_candle(symbol: &str, date: &str) -> RawStock {
+        RawStock::new(symbol.to_string(), date.to_string(), 100.0, 101.0, 99.0, 100.0, 1000, TimeFrame::FiveMinutes)
+    }
+
+    fn test_trade_signal(raw_stock: &RawStock) -> TradeSignal {
+        TradeSignal::new(
+            raw_stock.clone(),
+            TradeType::Short,
+            AlgoTypes::ShootingStarPatternAlgo,
+            "Cooldown".to_string(),
+            raw_stock.date.clone(),
+            99.0,
+            104.0,
+            90.0,
+            10,
+            990.0,
+            ObjectId::new(),
+            ObjectId::new(),
+        )
+    }
+
+    #[test]
+    fn signals_inside_the_cooldown_are_dropped_per_symbol() {
+        let strategy_states = StrategyStates::new();
+        let strategy = CooldownStrategy { signal_cooldown_candles: 2 };
+        let dates = ["2023-08-10 09:20:00+0530", "2023-08-10 09:25:00+0530", "2023-08-10 09:30:00+0530", "2023-08-10 09:35:00+0530"];
+
+        let mut passed_signal_counts = Vec::new();
+        for date in dates.iter() {
+            let candle = test_candle("ADANIGREEN", date);
+            let trade_signals = vec![test_trade_signal(&candle)];
+            passed_signal_counts.push(strategy_states.record_candle(&strategy, &candle, trade_signals).len());
+        }
+        assert_eq!(passed_signal_counts, vec![1, 0, 0, 1]);
+
+        //another symbol has its own cooldown
+        let other_candle = test_candle("TCS", dates[1]);
+        assert_eq!(strategy_states.record_candle(&strategy, &other_candle, vec![test_trade_signal(&other_candle)]).len(), 1);
+
+        let symbol_state = strategy_states.get_strategy_state("Cooldown").unwrap().symbol_states["ADANIGREEN"].clone();
+        assert_eq!(symbol_state.processed_candle_count, 4);
+        assert_eq!(symbol_state.trade_signal_count, 2);
+        assert_eq!(symbol_state.skipped_trade_signal_count, 2);
+        assert_eq!(symbol_state.cooldown_candles_left, 2);
+        assert_eq!(symbol_state.last_trade_signal_at, Some(dates[3].to_string()));
+    }
+
+    #[test]
+    fn state_survives_across_clones_of_the_handle() {
+        let strategy_states = StrategyStates::new();
+        let worker_strategy_states = strategy_states.clone();
+        let strategy = CooldownStrategy { signal_cooldown_candles: 0 };
+
+        for date in ["2023-08-10 09:20:00+0530", "2023-08-10 09:25:00+0530"].iter() {
+            let candle = test_candle("ADANIGREEN", date);
+            assert_eq!(worker_strategy_states.record_candle(&strategy, &candle, vec![test_trade_signal(&candle)]).len(), 1);
+        }
+
+        let strategy_state = strategy_states.get_strategy_states().pop().unwrap();
+        assert_eq!(strategy_state.strategy_name, "Cooldown");
+        assert_eq!(strategy_state.symbol_states["ADANIGREEN"].trade_signal_count, 2);
+        assert_eq!(strategy_state.symbol_states["ADANIGREEN"].last_candle_at, "2023-08-10 09:25:00+0530");
+    }
+}
@@ -0,0 +1,328 @@
+use std::{collections::BTreeMap, error::Error, fs::File, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{common::date_parser, order_manager::order_dispatcher::Order};
+
+const TRADING_DAYS_IN_YEAR: f32 = 252.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceMetrics {
+    pub group_by: String,
+    pub group_value: String,
+    pub trade_count: i32,
+    pub winning_trade_count: i32,
+    pub losing_trade_count: i32,
+    pub total_pnl: f32,
+    pub win_rate: f32,
+    pub average_win: f32,
+    pub average_loss: f32,
+    pub profit_factor: Option<f32>, //None when there is no losing trade to divide by
+    pub expectancy: f32,
+    pub max_drawdown: f32,
+    pub sharpe_ratio: f32,
+    pub sortino_ratio: f32,
+    pub longest_losing_streak: i32,
+    pub average_holding_time_in_seconds: f32,
+}
+
+impl PerformanceMetrics {
+    pub fn calculate(group_by: &str, group_value: &str, closed_orders: &[Order], trade_capital: i64) -> PerformanceMetrics {
+        let mut orders = closed_orders.to_vec();
+        orders.sort_by_key(|order| date_parser::date_time_in_seconds(order.trade_closed_at.as_str()));
+
+        let trade_count = orders.len() as i32;
+        let winning_pnls: Vec<f32> = orders.iter().filter(|order| order.is_profitable_trade).map(|order| order.closing_profit).collect();
+        let losing_pnls: Vec<f32> = orders.iter().filter(|order| !order.is_profitable_trade).map(|order| order.closing_profit).collect();
+
+        let total_pnl: f32 = orders.iter().map(|order| order.closing_profit).sum();
+        let gross_profit: f32 = winning_pnls.iter().sum();
+        let gross_loss: f32 = losing_pnls.iter().sum();
+
+        let win_rate = PerformanceMetrics::safe_divide(winning_pnls.len() as f32, trade_count as f32);
+        let average_win = PerformanceMetrics::safe_divide(gross_profit, winning_pnls.len() as f32);
+        let average_loss = PerformanceMetrics::safe_divide(gross_loss, losing_pnls.len() as f32);
+        let profit_factor = if gross_loss < 0.0 { Some(gross_profit / gross_loss.abs()) } else { None };
+        let expectancy = win_rate * average_win + (1.0 - win_rate) * average_loss;
+
+        let (sharpe_ratio, sortino_ratio) = PerformanceMetrics::calculate_daily_ratios(&orders, trade_capital);
+
+        let total_holding_time: i64 = orders
+            .iter()
+            .map(|order| date_parser::date_time_difference_in_seconds(order.trade_closed_at.as_str(), order.trade_taken_at.as_str()))
+            .sum();
+
+        PerformanceMetrics {
+            group_by: group_by.to_owned(),
+            group_value: group_value.to_owned(),
+            trade_count,
+            winning_trade_count: winning_pnls.len() as i32,
+            losing_trade_count: losing_pnls.len() as i32,
+            total_pnl,
+            win_rate,
+            average_win,
+            average_loss,
+            profit_factor,
+            expectancy,
+            max_drawdown: PerformanceMetrics::calculate_max_drawdown(&orders),
+            sharpe_ratio,
+            sortino_ratio,
+            longest_losing_streak: PerformanceMetrics::calculate_longest_losing_streak(&orders),
+            average_holding_time_in_seconds: PerformanceMetrics::safe_divide(total_holding_time as f32, trade_count as f32),
+        }
+    }
+
+    //orders are expected in closing order, the drawdown is measured on the realised equity curve
+    fn calculate_max_drawdown(orders: &[Order]) -> f32 {
+        let mut equity = 0.0;
+        let mut peak_equity = 0.0;
+        let mut max_drawdown: f32 = 0.0;
+        for order in orders.iter() {
+            equity += order.closing_profit;
+            if equity > peak_equity {
+                peak_equity = equity;
+            }
+            max_drawdown = max_drawdown.max(peak_equity - equity);
+        }
+        max_drawdown
+    }
+
+    fn calculate_longest_losing_streak(orders: &[Order]) -> i32 {
+        let mut current_streak = 0;
+        let mut longest_streak = 0;
+        for order in orders.iter() {
+            if order.is_profitable_trade {
+                current_streak = 0;
+            } else {
+                current_streak += 1;
+                longest_streak = longest_streak.max(current_streak);
+            }
+        }
+        longest_streak
+    }
+
+    //daily return = pnl booked on the day / trade capital, annualised with 252 trading days
+    fn calculate_daily_ratios(orders: &[Order], trade_capital: i64) -> (f32, f32) {
+        if trade_capital <= 0 {
+            return (0.0, 0.0);
+        }
+
+        let mut daily_pnls: BTreeMap<String, f32> = BTreeMap::new();
+        for order in orders.iter() {
+            let trade_date_only = date_parser::return_only_date_from_datetime(order.trade_closed_at.as_str());
+            *daily_pnls.entry(trade_date_only).or_insert(0.0) += order.closing_profit;
+        }
+
+        let daily_returns: Vec<f32> = daily_pnls.values().map(|pnl| pnl / trade_capital as f32).collect();
+        if daily_returns.len() < 2 {
+            return (0.0, 0.0);
+        }
+
+        let mean_return = daily_returns.iter().sum::<f32>() / daily_returns.len() as f32;
+        let variance = daily_returns.iter().map(|daily_return| (daily_return - mean_return).powi(2)).sum::<f32>()
+            / (daily_returns.len() - 1) as f32;
+        let downside_variance = daily_returns.iter().map(|daily_return| daily_return.min(0.0).powi(2)).sum::<f32>()
+            / daily_returns.len() as f32;
+
+        let annualise = TRADING_DAYS_IN_YEAR.sqrt();
+        let sharpe_ratio = PerformanceMetrics::safe_divide(mean_return, variance.sqrt()) * annualise;
+        let sortino_ratio = PerformanceMetrics::safe_divide(mean_return, downside_variance.sqrt()) * annualise;
+        (sharpe_ratio, sortino_ratio)
+    }
+
+    fn safe_divide(numerator: f32, denominator: f32) -> f32 {
+        if denominator == 0.0 {
+            0.0
+        } else {
+            numerator / denominator
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BacktestReport {
+    pub trade_capital: i64,
+    pub overall: PerformanceMetrics,
+    pub by_algo_type: Vec<PerformanceMetrics>,
+    pub by_strategy_instance: Vec<PerformanceMetrics>,
+    pub by_symbol: Vec<PerformanceMetrics>,
+    pub by_time_frame: Vec<PerformanceMetrics>,
+}
+
+impl BacktestReport {
+    pub fn new(closed_orders: &[Order], trade_capital: i64) -> BacktestReport {
+        BacktestReport {
+            trade_capital,
+            overall: PerformanceMetrics::calculate("Overall", "All", closed_orders, trade_capital),
+            by_algo_type: BacktestReport::calculate_for_group("AlgoType", closed_orders, trade_capital, |order| order.trade_algo_type.to_string()),
+            by_strategy_instance: BacktestReport::calculate_for_group("StrategyInstance", closed_orders, trade_capital, |order| order.strategy_instance.clone()),
+            by_symbol: BacktestReport::calculate_for_group("Symbol", closed_orders, trade_capital, |order| order.symbol.clone()),
+            by_time_frame: BacktestReport::calculate_for_group("TimeFrame", closed_orders, trade_capital, |order| order.market_time_frame.to_string()),
+        }
+    }
+
+    fn calculate_for_group(group_by: &str, closed_orders: &[Order], trade_capital: i64, group_key: fn(&Order) -> String) -> Vec<PerformanceMetrics> {
+        let mut grouped_orders: BTreeMap<String, Vec<Order>> = BTreeMap::new();
+        for order in closed_orders.iter() {
+            grouped_orders.entry(group_key(order)).or_default().push(order.clone());
+        }
+
+        grouped_orders
+            .iter()
+            .map(|(group_value, orders)| PerformanceMetrics::calculate(group_by, group_value, orders, trade_capital))
+            .collect()
+    }
+
+    pub fn get_all_metrics(&self) -> Vec<PerformanceMetrics> {
+        let mut all_metrics = vec![self.overall.clone()];
+        all_metrics.extend(self.by_algo_type.iter().cloned());
+        all_metrics.extend(self.by_strategy_instance.iter().cloned());
+        all_metrics.extend(self.by_symbol.iter().cloned());
+        all_metrics.extend(self.by_time_frame.iter().cloned());
+        all_metrics
+    }
+
+    pub fn export_as_json(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = File::create(file_path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn export_as_csv(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(file_path)?;
+        for metrics in self.get_all_metrics().iter() {
+            writer.serialize(metrics)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::enums::{AlgoTypes, ExitReason, TimeFrame, TradeType},
+        order_manager::order_status::OrderStatus,
+    };
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "expected {} got {}", expected, actual);
+    }
+
+    //held for 15 minutes before trade_closed_at
+    fn closed_order(symbol: &str, trade_closed_at: &str, closing_profit: f32) -> Order {
+        let trade_closed_in_seconds = date_parser::date_time_in_seconds(trade_closed_at) - 15 * 60;
+        let trade_taken_at = chrono::DateTime::from_timestamp(trade_closed_in_seconds, 0)
+            .unwrap()
+            .with_timezone(&chrono::FixedOffset::east_opt(19_800).unwrap())
+            .format("%Y-%m-%d %H:%M:%S%z")
+            .to_string();
+        Order::new(
+            symbol.to_string(),
+            TradeType::Long,
+            AlgoTypes::HammerPatternAlgo,
+            AlgoTypes::HammerPatternAlgo.to_string(),
+            TimeFrame::FiveMinutes,
+            100.0,
+            100.0 + closing_profit / 10.0,
+            95.0,
+            110.0,
+            if closing_profit > 0.0 { OrderStatus::ExitedByTarget } else { OrderStatus::ExitedBySl },
+            10,
+            1000.0,
+            trade_taken_at,
+            trade_closed_at.to_string(),
+            format!("PAPER_{}", trade_closed_in_seconds),
+            closing_profit,
+            closing_profit > 0.0,
+            Some(if closing_profit > 0.0 { ExitReason::TargetHit } else { ExitReason::StopLossHit }),
+        )
+    }
+
+    //equity curve in closing order => 100, 50, -30, 170, 140
+    fn closed_orders() -> Vec<Order> {
+        vec![
+            closed_order("TCS", "2023-08-11 11:00:00+0530", 200.0),
+            closed_order("ADANIGREEN", "2023-08-10 10:00:00+0530", 100.0),
+            closed_order("ADANIGREEN", "2023-08-14 10:00:00+0530", -30.0),
+            closed_order("ADANIGREEN", "2023-08-10 11:00:00+0530", -50.0),
+            closed_order("TCS", "2023-08-11 10:00:00+0530", -80.0),
+        ]
+    }
+
+    #[test]
+    fn win_rate_averages_and_expectancy() {
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &closed_orders(), 10000);
+        assert_eq!(metrics.trade_count, 5);
+        assert_eq!(metrics.winning_trade_count, 2);
+        assert_eq!(metrics.losing_trade_count, 3);
+        assert_close(metrics.total_pnl, 140.0);
+        assert_close(metrics.win_rate, 0.4);
+        assert_close(metrics.average_win, 150.0);
+        assert_close(metrics.average_loss, -160.0 / 3.0);
+        assert_close(metrics.profit_factor.unwrap(), 300.0 / 160.0);
+        //expectancy is the average pnl per trade
+        assert_close(metrics.expectancy, 140.0 / 5.0);
+        assert_close(metrics.average_holding_time_in_seconds, 900.0);
+    }
+
+    #[test]
+    fn drawdown_and_losing_streak_follow_the_closing_order() {
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &closed_orders(), 10000);
+        //peak 100 down to -30
+        assert_close(metrics.max_drawdown, 130.0);
+        assert_eq!(metrics.longest_losing_streak, 2);
+
+        //an equity curve that only rises has no drawdown
+        let winning_orders = vec![closed_order("TCS", "2023-08-10 10:00:00+0530", 50.0), closed_order("TCS", "2023-08-10 11:00:00+0530", 20.0)];
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &winning_orders, 10000);
+        assert_eq!(metrics.max_drawdown, 0.0);
+        assert_eq!(metrics.longest_losing_streak, 0);
+        assert_eq!(metrics.profit_factor, None);
+
+        //a loss on the very first trade counts from the starting equity
+        let losing_orders = vec![closed_order("TCS", "2023-08-10 10:00:00+0530", -40.0)];
+        assert_close(PerformanceMetrics::calculate("Overall", "All", &losing_orders, 10000).max_drawdown, 40.0);
+    }
+
+    #[test]
+    fn daily_returns_are_annualised() {
+        //daily pnl => 50, 120, -30 on a 10000 capital
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &closed_orders(), 10000);
+        assert!((metrics.sharpe_ratio - 9.870).abs() < 1e-2, "sharpe ratio {}", metrics.sharpe_ratio);
+        assert!((metrics.sortino_ratio - 42.771).abs() < 1e-1, "sortino ratio {}", metrics.sortino_ratio);
+
+        //a single trading day or no capital has no ratio
+        let one_day_orders = vec![closed_order("TCS", "2023-08-10 10:00:00+0530", 50.0)];
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &one_day_orders, 10000);
+        assert_eq!((metrics.sharpe_ratio, metrics.sortino_ratio), (0.0, 0.0));
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &closed_orders(), 0);
+        assert_eq!((metrics.sharpe_ratio, metrics.sortino_ratio), (0.0, 0.0));
+    }
+
+    #[test]
+    fn no_orders_report_zeros() {
+        let metrics = PerformanceMetrics::calculate("Overall", "All", &[], 10000);
+        assert_eq!(metrics.trade_count, 0);
+        assert_eq!(metrics.win_rate, 0.0);
+        assert_eq!(metrics.expectancy, 0.0);
+        assert_eq!(metrics.max_drawdown, 0.0);
+        assert_eq!(metrics.profit_factor, None);
+        assert_eq!(metrics.average_holding_time_in_seconds, 0.0);
+    }
+
+    #[test]
+    fn report_groups_the_orders() {
+        let backtest_report = BacktestReport::new(&closed_orders(), 10000);
+        assert_eq!(backtest_report.overall.trade_count, 5);
+        let symbol_pnls: Vec<(String, i32, f32)> = backtest_report
+            .by_symbol
+            .iter()
+            .map(|metrics| (metrics.group_value.clone(), metrics.trade_count, metrics.total_pnl))
+            .collect();
+        assert_eq!(symbol_pnls, vec![("ADANIGREEN".to_string(), 3, 20.0), ("TCS".to_string(), 2, 120.0)]);
+        assert_eq!(backtest_report.by_algo_type.len(), 1);
+        assert_eq!(backtest_report.get_all_metrics().len(), 1 + 1 + 1 + 2 + 1);
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::Mutex;
+
+use mongodb::bson::doc;
+
+use crate::{
+    algo_hub::strategy::StrategyRegistry,
+    common::{
+        date_parser,
+        enums::{RootSystemConfig, TimeFrame},
+        raw_stock::{RawStock, RawStockLedger},
+        redis_client::RedisClient,
+        utils::{current_market_state_cache_key_formatter, current_pnl_state_cache_key_formatter, order_cache_key_formatter, time_frame_in_seconds},
+    },
+    data_consumer::data_processor,
+    order_manager::order_dispatcher::Order,
+};
+
+pub struct BacktestRunner {
+    pub root_system_config: RootSystemConfig,
+}
+
+impl BacktestRunner {
+    pub fn new(root_system_config: RootSystemConfig) -> BacktestRunner {
+        BacktestRunner { root_system_config }
+    }
+
+    pub async fn run(
+        &mut self,
+        one_minute_stocks: Vec<RawStock>,
+        five_minute_stocks: Vec<RawStock>,
+        redis_client: &Mutex<RedisClient>,
+    ) -> Vec<Order> {
+        let replay_stocks = BacktestRunner::merge_in_timestamp_order(one_minute_stocks, five_minute_stocks);
+        let mut strategy_registry = StrategyRegistry::from_algo_types(
+            &self.root_system_config.tradeable_algo_types,
+            &self.root_system_config.strategy_configs,
+            &self.root_system_config.database_instance,
+            self.root_system_config.strategy_states.clone(),
+        );
+        self.reset_cached_state(&replay_stocks, &strategy_registry.get_strategy_names(), redis_client).await;
+
+        let mut one_minute_ledger = RawStockLedger::new();
+        let mut five_minute_ledger = RawStockLedger::new();
+
+        for raw_stock in replay_stocks.iter() {
+            let raw_stock_ledger = match raw_stock.market_time_frame {
+                TimeFrame::OneMinute => &mut one_minute_ledger,
+                _ => &mut five_minute_ledger,
+            };
+            raw_stock_ledger.add_raw_stock(raw_stock.clone());
+
+            data_processor::process_raw_stock(
+                raw_stock,
+                raw_stock.market_time_frame.clone(),
+                &mut self.root_system_config,
+                &mut strategy_registry,
+                raw_stock_ledger,
+                redis_client,
+            )
+            .await;
+        }
+
+        self.root_system_config
+            .order_manager
+            .position_book
+            .closed_orders()
+            .into_iter()
+            .filter(|order| order.status.is_exited())
+            .collect()
+    }
+
+    //A candle is only known once it closes, so the replay is ordered by close time.
+    //On a tie the 1 min candle goes first so open orders are watched before new signals are taken.
+    pub fn merge_in_timestamp_order(one_minute_stocks: Vec<RawStock>, five_minute_stocks: Vec<RawStock>) -> Vec<RawStock> {
+        let mut replay_stocks: Vec<(i64, i64, RawStock)> = one_minute_stocks
+            .into_iter()
+            .chain(five_minute_stocks)
+            .map(|stock| {
+                let candle_seconds = time_frame_in_seconds(&stock.market_time_frame);
+                let closed_at = date_parser::date_time_in_seconds(stock.date.as_str()) + candle_seconds;
+                (closed_at, candle_seconds, stock)
+            })
+            .collect();
+
+        replay_stocks.sort_by_key(|(closed_at, candle_seconds, _)| (*closed_at, *candle_seconds));
+        replay_stocks.into_iter().map(|(_, _, stock)| stock).collect()
+    }
+
+    //Orders, market states and their snapshots, strategy states and indicators from a previous replay would otherwise leak into this one
+    async fn reset_cached_state(&mut self, replay_stocks: &[RawStock], strategy_names: &[String], redis_client: &Mutex<RedisClient>) {
+        let mut cache_keys: Vec<String> = Vec::new();
+        let mut pnl_cache_keys: Vec<String> = Vec::new();
+        let mut symbols: Vec<String> = Vec::new();
+        for stock in replay_stocks.iter() {
+            symbols.push(stock.symbol.clone());
+            for strategy_name in strategy_names.iter() {
+                cache_keys.push(order_cache_key_formatter(stock.symbol.as_str(), strategy_name));
+            }
+            let trade_date_only = date_parser::return_only_date_from_datetime(stock.date.as_str());
+            cache_keys.push(current_market_state_cache_key_formatter(
+                trade_date_only.as_str(),
+                stock.symbol.as_str(),
+                &stock.market_time_frame,
+            ));
+            pnl_cache_keys.push(current_pnl_state_cache_key_formatter(trade_date_only.as_str()));
+        }
+        cache_keys.sort();
+        cache_keys.dedup();
+        pnl_cache_keys.sort();
+        pnl_cache_keys.dedup();
+        symbols.sort();
+        symbols.dedup();
+
+        for cache_key in cache_keys.iter().chain(pnl_cache_keys.iter()) {
+            if let Err(e) = redis_client.lock().unwrap().delete_data(cache_key.as_str()) {
+                println!("Error while resetting the cache key {} => {:?}", cache_key, e);
+            }
+        }
+
+        let filter = doc! {"cache_key": {"$in": cache_keys.clone()}};
+        if let Err(e) = self
+            .root_system_config
+            .current_market_state_collection
+            .delete_many(filter, None)
+            .await
+        {
+            println!("Error while resetting the current market states => {:?}", e);
+        }
+
+        //an order left open by an earlier replay would block every signal of its symbol and strategy instance
+        let filter = doc! {"symbol": {"$in": symbols}, "strategy_instance": {"$in": strategy_names.to_vec()}};
+        if let Err(e) = self.root_system_config.orders_collection.delete_many(filter, None).await {
+            println!("Error while resetting the orders => {:?}", e);
+        }
+
+        let filter = doc! {"cache_key": {"$in": pnl_cache_keys}};
+        if let Err(e) = self
+            .root_system_config
+            .order_manager
+            .current_pnl_state_collection
+            .delete_many(filter, None)
+            .await
+        {
+            println!("Error while resetting the current PnL states => {:?}", e);
+        }
+
+        //the backtest database only holds replays => every replayed series starts from an empty history
+        let mut snapshot_series: Vec<(&str, &TimeFrame)> = Vec::new();
+        for stock in replay_stocks.iter() {
+            if !snapshot_series.contains(&(stock.symbol.as_str(), &stock.market_time_frame)) {
+                snapshot_series.push((stock.symbol.as_str(), &stock.market_time_frame));
+            }
+        }
+        for (symbol, time_frame) in snapshot_series {
+            self.root_system_config.market_state_history.delete_snapshots(symbol, time_frame).await;
+        }
+
+        self.root_system_config.order_manager.position_book.clear();
+        self.root_system_config.strategy_states.clear();
+        self.root_system_config.trade_keeper.clear();
+        self.root_system_config.indicator_ledger.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_candle(date: &str, market_time_frame: TimeFrame) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), 100.0, 101.0, 99.0, 100.5, 1000, market_time_frame)
+    }
+
+    fn replay_order(replay_stocks: &[RawStock]) -> Vec<(String, TimeFrame)> {
+        replay_stocks
+            .iter()
+            .map(|stock| (stock.date.clone(), stock.market_time_frame.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn candles_are_replayed_by_close_time() {
+        let one_minute_stocks = vec![
+            test_candle("2023-08-10 09:15:00+0530", TimeFrame::OneMinute),
+            test_candle("2023-08-10 09:19:00+0530", TimeFrame::OneMinute),
+            test_candle("2023-08-10 09:20:00+0530", TimeFrame::OneMinute),
+        ];
+        let five_minute_stocks = vec![
+            test_candle("2023-08-10 09:20:00+0530", TimeFrame::FiveMinutes),
+            test_candle("2023-08-10 09:15:00+0530", TimeFrame::FiveMinutes),
+        ];
+        let replay_stocks = BacktestRunner::merge_in_timestamp_order(one_minute_stocks, five_minute_stocks);
+        assert_eq!(
+            replay_order(&replay_stocks),
+            vec![
+                ("2023-08-10 09:15:00+0530".to_string(), TimeFrame::OneMinute),
+                //09:19 and the 09:15 5 min candle both close at 09:20 => the 1 min candle goes first
+                ("2023-08-10 09:19:00+0530".to_string(), TimeFrame::OneMinute),
+                ("2023-08-10 09:15:00+0530".to_string(), TimeFrame::FiveMinutes),
+                ("2023-08-10 09:20:00+0530".to_string(), TimeFrame::OneMinute),
+                ("2023-08-10 09:20:00+0530".to_string(), TimeFrame::FiveMinutes),
+            ]
+        );
+    }
+
+    #[test]
+    fn candles_closing_on_another_day_are_not_mixed() {
+        let one_minute_stocks = vec![
+            test_candle("2023-08-11 09:15:00+0530", TimeFrame::OneMinute),
+            test_candle("2023-08-10 15:29:00+0530", TimeFrame::OneMinute),
+        ];
+        let five_minute_stocks = vec![test_candle("2023-08-10 15:25:00+0530", TimeFrame::FiveMinutes)];
+        let replay_stocks = BacktestRunner::merge_in_timestamp_order(one_minute_stocks, five_minute_stocks);
+        assert_eq!(
+            replay_order(&replay_stocks),
+            vec![
+                ("2023-08-10 15:29:00+0530".to_string(), TimeFrame::OneMinute),
+                ("2023-08-10 15:25:00+0530".to_string(), TimeFrame::FiveMinutes),
+                ("2023-08-11 09:15:00+0530".to_string(), TimeFrame::OneMinute),
+            ]
+        );
+        assert!(BacktestRunner::merge_in_timestamp_order(Vec::new(), Vec::new()).is_empty());
+    }
+}
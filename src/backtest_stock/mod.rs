@@ -1 +1,7 @@
-pub mod stock_5_min;
\ No newline at end of file
+pub mod stock_1_min;
+pub mod stock_5_min;
+pub mod stock_15_min;
+pub mod stock_daily_min;
+pub mod backtest_runner;
+pub mod backtest_report;
+pub mod optimizer;
\ No newline at end of file
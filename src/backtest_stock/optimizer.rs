@@ -0,0 +1,645 @@
+use std::{cmp::Ordering, collections::{BTreeSet, HashMap}, ops::Range, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    algo_hub::{
+        hammer_config::HammerConfig,
+        hammer_pattern::HammerPatternUtil,
+        strategy::{MarketContext, Strategy},
+        strategy_state::StrategyStates,
+    },
+    common::{
+        date_parser,
+        enums::TimeFrame,
+        raw_stock::{RawStock, RawStockLedger},
+    },
+    data_consumer::{
+        current_market_state::CurrentMarketState,
+        indicators::{IndicatorConfig, IndicatorConfigs, IndicatorLedger},
+    },
+    order_manager::{order_dispatcher::Order, order_status::OrderStatus},
+    trade_watcher::{
+        monitor_trade::{self, PositionAction},
+        trade_watcher_config::TradeWatcherConfig,
+    },
+};
+
+use super::backtest_report::PerformanceMetrics;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterSet {
+    pub hammer_config: HammerConfig,
+    pub sma_window_size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterGrid {
+    pub small_body_to_candle_ratios: Vec<f32>,
+    pub min_lower_wick_to_body_ratios: Vec<f32>,
+    pub lower_to_upper_wick_ratios: Vec<f32>,
+    pub sl_percentages: Vec<f32>,
+    pub target_percentages: Vec<f32>,
+    pub sma_window_sizes: Vec<usize>,
+    pub trade_with_trend: Vec<bool>,
+}
+
+impl Default for ParameterGrid {
+    fn default() -> ParameterGrid {
+        ParameterGrid {
+            small_body_to_candle_ratios: vec![0.2, 0.25, 0.3],
+            min_lower_wick_to_body_ratios: vec![1.5, 1.75, 2.0],
+            lower_to_upper_wick_ratios: vec![2.0, 2.5],
+            sl_percentages: vec![2.0, 3.0, 5.0],
+            target_percentages: vec![4.0, 6.0, 10.0],
+            sma_window_sizes: vec![9, 20],
+            trade_with_trend: vec![false, true],
+        }
+    }
+}
+
+impl ParameterGrid {
+    //every dimension needs at least one value, an empty one would leave nothing to combine or draw from
+    pub fn validate(&self) -> Result<(), String> {
+        let dimension_lengths = [
+            ("small_body_to_candle_ratios", self.small_body_to_candle_ratios.len()),
+            ("min_lower_wick_to_body_ratios", self.min_lower_wick_to_body_ratios.len()),
+            ("lower_to_upper_wick_ratios", self.lower_to_upper_wick_ratios.len()),
+            ("sl_percentages", self.sl_percentages.len()),
+            ("target_percentages", self.target_percentages.len()),
+            ("sma_window_sizes", self.sma_window_sizes.len()),
+            ("trade_with_trend", self.trade_with_trend.len()),
+        ];
+        let empty_dimensions: Vec<&str> = dimension_lengths
+            .iter()
+            .filter(|(_, dimension_length)| *dimension_length == 0)
+            .map(|(dimension_name, _)| *dimension_name)
+            .collect();
+        if empty_dimensions.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("empty parameter grid dimensions => {}", empty_dimensions.join(", ")))
+        }
+    }
+
+    //full cartesian product of every value list on top of the base config
+    pub fn to_parameter_sets(&self, base_hammer_config: &HammerConfig) -> Result<Vec<ParameterSet>, String> {
+        self.validate()?;
+        let mut parameter_sets = Vec::new();
+        for small_body_to_candle_ratio in self.small_body_to_candle_ratios.iter() {
+            for min_lower_wick_to_body_ratio in self.min_lower_wick_to_body_ratios.iter() {
+                for lower_to_upper_wick_ratio in self.lower_to_upper_wick_ratios.iter() {
+                    for sl_percentage in self.sl_percentages.iter() {
+                        for target_percentage in self.target_percentages.iter() {
+                            for sma_window_size in self.sma_window_sizes.iter() {
+                                for trade_with_trend in self.trade_with_trend.iter() {
+                                    parameter_sets.push(ParameterGrid::build_parameter_set(
+                                        base_hammer_config,
+                                        parameter_sets.len(),
+                                        *small_body_to_candle_ratio,
+                                        *min_lower_wick_to_body_ratio,
+                                        *lower_to_upper_wick_ratio,
+                                        *sl_percentage,
+                                        *target_percentage,
+                                        *sma_window_size,
+                                        *trade_with_trend,
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(parameter_sets)
+    }
+
+    //random search => every dimension is drawn independently, the seed keeps runs reproducible
+    pub fn random_sample(&self, base_hammer_config: &HammerConfig, sample_size: usize, seed: u64) -> Result<Vec<ParameterSet>, String> {
+        self.validate()?;
+        let mut random_state = seed.max(1);
+        Ok((0..sample_size)
+            .map(|index| {
+                ParameterGrid::build_parameter_set(
+                    base_hammer_config,
+                    index,
+                    *ParameterGrid::pick(&self.small_body_to_candle_ratios, &mut random_state),
+                    *ParameterGrid::pick(&self.min_lower_wick_to_body_ratios, &mut random_state),
+                    *ParameterGrid::pick(&self.lower_to_upper_wick_ratios, &mut random_state),
+                    *ParameterGrid::pick(&self.sl_percentages, &mut random_state),
+                    *ParameterGrid::pick(&self.target_percentages, &mut random_state),
+                    *ParameterGrid::pick(&self.sma_window_sizes, &mut random_state),
+                    *ParameterGrid::pick(&self.trade_with_trend, &mut random_state),
+                )
+            })
+            .collect())
+    }
+
+    //values is never empty, validate() runs before any draw
+    fn pick<'a, T>(values: &'a [T], random_state: &mut u64) -> &'a T {
+        //xorshift64
+        *random_state ^= *random_state << 13;
+        *random_state ^= *random_state >> 7;
+        *random_state ^= *random_state << 17;
+        &values[(*random_state % values.len() as u64) as usize]
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_parameter_set(
+        base_hammer_config: &HammerConfig,
+        index: usize,
+        small_body_to_candle_ratio: f32,
+        min_lower_wick_to_body_ratio: f32,
+        lower_to_upper_wick_ratio: f32,
+        sl_percentage: f32,
+        target_percentage: f32,
+        sma_window_size: usize,
+        trade_with_trend: bool,
+    ) -> ParameterSet {
+        let mut hammer_config = base_hammer_config.clone();
+        hammer_config.instance_name = format!("{}_{}", base_hammer_config.instance_name, index);
+        hammer_config.small_body_to_candle_ratio = small_body_to_candle_ratio;
+        hammer_config.min_lower_wick_to_body_ratio = min_lower_wick_to_body_ratio;
+        hammer_config.lower_to_upper_wick_ratio = lower_to_upper_wick_ratio;
+        hammer_config.sl_percentage = sl_percentage;
+        hammer_config.target_percentage = target_percentage;
+        hammer_config.trade_with_trend = trade_with_trend;
+        ParameterSet {
+            hammer_config,
+            sma_window_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OptimizationObjective {
+    TotalPnl,
+    SharpeRatio,
+    SortinoRatio,
+    ProfitFactor,
+    Expectancy,
+    WinRate,
+}
+
+impl OptimizationObjective {
+    pub fn from_name(name: &str) -> Option<OptimizationObjective> {
+        match name {
+            "TotalPnl" => Some(OptimizationObjective::TotalPnl),
+            "SharpeRatio" => Some(OptimizationObjective::SharpeRatio),
+            "SortinoRatio" => Some(OptimizationObjective::SortinoRatio),
+            "ProfitFactor" => Some(OptimizationObjective::ProfitFactor),
+            "Expectancy" => Some(OptimizationObjective::Expectancy),
+            "WinRate" => Some(OptimizationObjective::WinRate),
+            _ => None,
+        }
+    }
+
+    pub fn score(&self, metrics: &PerformanceMetrics) -> f32 {
+        match self {
+            OptimizationObjective::TotalPnl => metrics.total_pnl,
+            OptimizationObjective::SharpeRatio => metrics.sharpe_ratio,
+            OptimizationObjective::SortinoRatio => metrics.sortino_ratio,
+            //no losing trade at all => rank by the raw profit instead of an infinite factor
+            OptimizationObjective::ProfitFactor => metrics.profit_factor.unwrap_or(metrics.total_pnl.max(0.0)),
+            OptimizationObjective::Expectancy => metrics.expectancy,
+            OptimizationObjective::WinRate => metrics.win_rate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptimizationResult {
+    pub parameter_set: ParameterSet,
+    pub metrics: PerformanceMetrics,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalkForwardResult {
+    pub in_sample_start_date: String,
+    pub in_sample_end_date: String,
+    pub out_of_sample_start_date: String,
+    pub out_of_sample_end_date: String,
+    pub in_sample_result: OptimizationResult,
+    pub out_of_sample_metrics: PerformanceMetrics,
+    pub out_of_sample_score: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestOptimizer {
+    pub objective: OptimizationObjective,
+    pub trade_capital: i64,
+    pub thread_count: usize,
+    pub trade_watcher_config: TradeWatcherConfig,
+}
+
+impl BacktestOptimizer {
+    pub fn new(objective: OptimizationObjective, trade_capital: i64, trade_watcher_config: TradeWatcherConfig) -> BacktestOptimizer {
+        let thread_count = thread::available_parallelism().map(|count| count.get()).unwrap_or(1);
+        BacktestOptimizer {
+            objective,
+            trade_capital,
+            thread_count,
+            trade_watcher_config,
+        }
+    }
+
+    //replay_stocks are expected in replay order, see BacktestRunner::merge_in_timestamp_order
+    pub fn optimize(&self, parameter_sets: &[ParameterSet], replay_stocks: &[RawStock]) -> Vec<OptimizationResult> {
+        if parameter_sets.is_empty() {
+            return Vec::new();
+        }
+
+        let chunk_size = parameter_sets.len().div_ceil(self.thread_count.max(1));
+        let mut optimization_results: Vec<OptimizationResult> = thread::scope(|scope| {
+            let handles: Vec<_> = parameter_sets
+                .chunks(chunk_size)
+                .map(|parameter_set_chunk| {
+                    scope.spawn(move || {
+                        parameter_set_chunk
+                            .iter()
+                            .map(|parameter_set| self.evaluate(parameter_set, replay_stocks))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        BacktestOptimizer::rank(&mut optimization_results);
+        optimization_results
+    }
+
+    //best score first, NaN scores are treated as equal so they keep their place
+    fn rank(optimization_results: &mut [OptimizationResult]) {
+        optimization_results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    }
+
+    //index ranges of trade_dates => (in sample, out of sample), every window rolls by out_of_sample_days
+    fn walk_forward_windows(trade_date_count: usize, in_sample_days: usize, out_of_sample_days: usize) -> Vec<(Range<usize>, Range<usize>)> {
+        let mut walk_forward_windows = Vec::new();
+        if in_sample_days == 0 || out_of_sample_days == 0 {
+            return walk_forward_windows;
+        }
+        let mut window_start = 0;
+        while window_start + in_sample_days + out_of_sample_days <= trade_date_count {
+            let out_of_sample_start = window_start + in_sample_days;
+            walk_forward_windows.push((window_start..out_of_sample_start, out_of_sample_start..out_of_sample_start + out_of_sample_days));
+            window_start += out_of_sample_days;
+        }
+        walk_forward_windows
+    }
+
+    //rolling windows => tune on in_sample_days, score the winner on the following out_of_sample_days, then roll by out_of_sample_days
+    pub fn walk_forward(
+        &self,
+        parameter_sets: &[ParameterSet],
+        replay_stocks: &[RawStock],
+        in_sample_days: usize,
+        out_of_sample_days: usize,
+    ) -> Vec<WalkForwardResult> {
+        let trade_dates: Vec<String> = replay_stocks
+            .iter()
+            .map(|stock| date_parser::return_only_date_from_datetime(stock.date.as_str()))
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+
+        let mut walk_forward_results = Vec::new();
+        for (in_sample_range, out_of_sample_range) in BacktestOptimizer::walk_forward_windows(trade_dates.len(), in_sample_days, out_of_sample_days) {
+            let in_sample_dates = &trade_dates[in_sample_range];
+            let out_of_sample_dates = &trade_dates[out_of_sample_range];
+
+            let in_sample_stocks = BacktestOptimizer::filter_by_dates(replay_stocks, in_sample_dates);
+            let out_of_sample_stocks = BacktestOptimizer::filter_by_dates(replay_stocks, out_of_sample_dates);
+
+            if let Some(in_sample_result) = self.optimize(parameter_sets, &in_sample_stocks).into_iter().next() {
+                let out_of_sample_result = self.evaluate(&in_sample_result.parameter_set, &out_of_sample_stocks);
+                walk_forward_results.push(WalkForwardResult {
+                    in_sample_start_date: in_sample_dates[0].clone(),
+                    in_sample_end_date: in_sample_dates[in_sample_dates.len() - 1].clone(),
+                    out_of_sample_start_date: out_of_sample_dates[0].clone(),
+                    out_of_sample_end_date: out_of_sample_dates[out_of_sample_dates.len() - 1].clone(),
+                    in_sample_result,
+                    out_of_sample_metrics: out_of_sample_result.metrics,
+                    out_of_sample_score: out_of_sample_result.score,
+                });
+            }
+        }
+        walk_forward_results
+    }
+
+    pub fn evaluate(&self, parameter_set: &ParameterSet, replay_stocks: &[RawStock]) -> OptimizationResult {
+        let closed_orders = self.simulate(parameter_set, replay_stocks);
+        let metrics = PerformanceMetrics::calculate(
+            "ParameterSet",
+            parameter_set.hammer_config.instance_name.as_str(),
+            &closed_orders,
+            self.trade_capital,
+        );
+        OptimizationResult {
+            parameter_set: parameter_set.clone(),
+            score: self.objective.score(&metrics),
+            metrics,
+        }
+    }
+
+    //In memory version of the live pipeline => no Mongo or Redis, so runs can go side by side.
+    //Signals place a pending entry on the 5 min close, fills, expiry and exits are checked on the 1 min candles with the helpers monitor_trade uses live.
+    fn simulate(&self, parameter_set: &ParameterSet, replay_stocks: &[RawStock]) -> Vec<Order> {
+        let mut hammer_ledger = HammerPatternUtil::new_in_memory(parameter_set.hammer_config.clone());
+        let strategy_states = StrategyStates::new(); //local to the run, so the signal cooldown applies as it does live
+        let mut five_minute_market_states: HashMap<String, CurrentMarketState> = HashMap::new();
+        let mut indicator_ledger = IndicatorLedger::new(IndicatorConfigs {
+            defaults: IndicatorConfig {
+                sma_window: parameter_set.sma_window_size,
+                ..IndicatorConfig::default()
+            },
+            ..IndicatorConfigs::default()
+        });
+        let mut one_minute_ledger = RawStockLedger::new();
+        let mut five_minute_ledger = RawStockLedger::new();
+        let mut open_orders: Vec<Order> = Vec::new();
+        let mut closed_orders: Vec<Order> = Vec::new();
+        let mut simulated_order_count = 0;
+
+        for stock in replay_stocks.iter() {
+            match stock.market_time_frame {
+                TimeFrame::OneMinute => {
+                    one_minute_ledger.add_raw_stock(stock.clone());
+                    let average_true_range = indicator_ledger.update(one_minute_ledger.get_raw_stocks(stock.symbol.as_str())).atr;
+                    let mut still_open_orders = Vec::new();
+                    for mut order in open_orders.drain(..) {
+                        if order.symbol != stock.symbol {
+                            still_open_orders.push(order);
+                            continue;
+                        }
+                        //the simulated broker fills a touched entry right away
+                        if order.status == OrderStatus::PendingEntry {
+                            if let Some(fill_price) = monitor_trade::find_entry_fill_price(&order, stock) {
+                                if let Err(e) = order.fill_entry(order.qty, fill_price, stock.date.clone()) {
+                                    println!("Error while filling the simulated order => {}", e);
+                                }
+                            }
+                        }
+                        if monitor_trade::should_expire_pending_entry(&order, stock, &self.trade_watcher_config) {
+                            continue;
+                        }
+                        if !order.status.has_position() {
+                            still_open_orders.push(order);
+                            continue;
+                        }
+
+                        match monitor_trade::check_position(&mut order, stock, &self.trade_watcher_config, average_true_range) {
+                            PositionAction::Exit(exit_price, exit_reason) => match order.exit_trade(exit_price, stock.date.clone(), exit_reason) {
+                                Ok(_) => closed_orders.push(order),
+                                Err(e) => println!("Error while exiting the simulated order => {}", e),
+                            },
+                            PositionAction::Managed | PositionAction::Unchanged => still_open_orders.push(order),
+                        }
+                    }
+                    open_orders = still_open_orders;
+                }
+                TimeFrame::FiveMinutes => {
+                    five_minute_ledger.add_raw_stock(stock.clone());
+                    let market_context = BacktestOptimizer::build_market_context(
+                        &five_minute_ledger,
+                        stock,
+                        &mut indicator_ledger,
+                        &mut five_minute_market_states,
+                    );
+
+                    let trade_signals = hammer_ledger.on_candle(stock, &market_context);
+                    for trade_signal in strategy_states.record_candle(&hammer_ledger, stock, trade_signals) {
+                        //same rule as the order cache => one open order per symbol and strategy instance
+                        let is_order_open = open_orders.iter().any(|order| {
+                            order.symbol == trade_signal.raw_stock.symbol && order.strategy_instance == trade_signal.strategy_instance
+                        });
+                        if !is_order_open {
+                            simulated_order_count += 1;
+                            let order_id = format!("SIMULATED_{}_{}_{}", trade_signal.raw_stock.symbol, trade_signal.strategy_instance, simulated_order_count);
+                            open_orders.push(Order::from_trade_signal(&trade_signal, order_id));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        closed_orders
+    }
+
+    //same roll as the live market state, kept in memory per symbol
+    fn build_market_context(
+        five_minute_ledger: &RawStockLedger,
+        stock: &RawStock,
+        indicator_ledger: &mut IndicatorLedger,
+        market_states: &mut HashMap<String, CurrentMarketState>,
+    ) -> MarketContext {
+        let raw_stocks = five_minute_ledger.get_raw_stocks(stock.symbol.as_str());
+        let indicator_values = indicator_ledger.update(raw_stocks);
+        let previous_stock = if raw_stocks.len() > 1 { raw_stocks.get(raw_stocks.len() - 2) } else { None };
+        let current_market_state = CurrentMarketState::roll_market_state(
+            stock,
+            indicator_values,
+            previous_stock,
+            market_states.get(stock.symbol.as_str()),
+            "".to_string(),
+        );
+        market_states.insert(stock.symbol.clone(), current_market_state.clone());
+        let average_true_range = current_market_state.indicators.atr;
+        MarketContext::new(Some(current_market_state), average_true_range)
+    }
+
+    fn filter_by_dates(replay_stocks: &[RawStock], trade_dates: &[String]) -> Vec<RawStock> {
+        replay_stocks
+            .iter()
+            .filter(|stock| trade_dates.contains(&date_parser::return_only_date_from_datetime(stock.date.as_str())))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_grid() -> ParameterGrid {
+        ParameterGrid {
+            small_body_to_candle_ratios: vec![0.2, 0.3],
+            min_lower_wick_to_body_ratios: vec![1.5],
+            lower_to_upper_wick_ratios: vec![2.0],
+            sl_percentages: vec![2.0, 5.0],
+            target_percentages: vec![4.0],
+            sma_window_sizes: vec![9, 20],
+            trade_with_trend: vec![false],
+        }
+    }
+
+    fn optimization_result(instance_name: &str, score: f32) -> OptimizationResult {
+        OptimizationResult {
+            parameter_set: ParameterSet {
+                hammer_config: HammerConfig {
+                    instance_name: instance_name.to_string(),
+                    ..HammerConfig::default()
+                },
+                sma_window_size: 9,
+            },
+            metrics: PerformanceMetrics::calculate("ParameterSet", instance_name, &[], 10000),
+            score,
+        }
+    }
+
+    fn test_candle(date: &str, open: f32, high: f32, low: f32, close: f32, market_time_frame: TimeFrame) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), close, high, low, open, 1000, market_time_frame)
+    }
+
+    #[test]
+    fn grid_expands_to_every_combination_on_top_of_the_base_config() {
+        assert_eq!(ParameterGrid::default().to_parameter_sets(&HammerConfig::default()).unwrap().len(), 3 * 3 * 2 * 3 * 3 * 2 * 2);
+
+        let base_hammer_config = HammerConfig {
+            instance_name: "Sweep".to_string(),
+            qty: 7,
+            ..HammerConfig::default()
+        };
+        let parameter_sets = small_grid().to_parameter_sets(&base_hammer_config).unwrap();
+        assert_eq!(parameter_sets.len(), 8);
+        //the last dimension changes fastest
+        assert_eq!(parameter_sets[0].sma_window_size, 9);
+        assert_eq!(parameter_sets[1].sma_window_size, 20);
+        assert_eq!(parameter_sets[2].hammer_config.sl_percentage, 5.0);
+        assert_eq!(parameter_sets[4].hammer_config.small_body_to_candle_ratio, 0.3);
+        for (index, parameter_set) in parameter_sets.iter().enumerate() {
+            assert_eq!(parameter_set.hammer_config.instance_name, format!("Sweep_{}", index));
+            assert_eq!(parameter_set.hammer_config.qty, 7);
+        }
+    }
+
+    #[test]
+    fn random_sample_is_reproducible_and_stays_inside_the_grid() {
+        let grid = ParameterGrid::default();
+        let sample = grid.random_sample(&HammerConfig::default(), 50, 42).unwrap();
+        assert_eq!(sample.len(), 50);
+        assert_eq!(grid.random_sample(&HammerConfig::default(), 50, 42).unwrap(), sample);
+        assert_ne!(grid.random_sample(&HammerConfig::default(), 50, 7).unwrap(), sample);
+        //seed 0 would keep xorshift at 0 forever
+        assert_eq!(grid.random_sample(&HammerConfig::default(), 5, 0).unwrap(), grid.random_sample(&HammerConfig::default(), 5, 1).unwrap());
+
+        for parameter_set in sample.iter() {
+            let hammer_config = &parameter_set.hammer_config;
+            assert!(grid.small_body_to_candle_ratios.contains(&hammer_config.small_body_to_candle_ratio));
+            assert!(grid.min_lower_wick_to_body_ratios.contains(&hammer_config.min_lower_wick_to_body_ratio));
+            assert!(grid.lower_to_upper_wick_ratios.contains(&hammer_config.lower_to_upper_wick_ratio));
+            assert!(grid.sl_percentages.contains(&hammer_config.sl_percentage));
+            assert!(grid.target_percentages.contains(&hammer_config.target_percentage));
+            assert!(grid.sma_window_sizes.contains(&parameter_set.sma_window_size));
+            assert!(grid.trade_with_trend.contains(&hammer_config.trade_with_trend));
+        }
+        //50 draws from 3 values reach all of them
+        let sl_percentages: BTreeSet<String> = sample.iter().map(|parameter_set| parameter_set.hammer_config.sl_percentage.to_string()).collect();
+        assert_eq!(sl_percentages.len(), 3);
+    }
+
+    #[test]
+    fn empty_grid_dimensions_are_rejected() {
+        let grid = ParameterGrid {
+            sl_percentages: Vec::new(),
+            trade_with_trend: Vec::new(),
+            ..small_grid()
+        };
+        let error = grid.random_sample(&HammerConfig::default(), 5, 42).unwrap_err();
+        assert_eq!(error, "empty parameter grid dimensions => sl_percentages, trade_with_trend");
+        assert!(grid.to_parameter_sets(&HammerConfig::default()).is_err());
+        assert_eq!(small_grid().validate(), Ok(()));
+    }
+
+    #[test]
+    fn results_are_ranked_best_score_first() {
+        let mut optimization_results = vec![
+            optimization_result("Low", -5.0),
+            optimization_result("High", 12.5),
+            optimization_result("Middle", 3.0),
+        ];
+        BacktestOptimizer::rank(&mut optimization_results);
+        let instance_names: Vec<&str> = optimization_results
+            .iter()
+            .map(|optimization_result| optimization_result.parameter_set.hammer_config.instance_name.as_str())
+            .collect();
+        assert_eq!(instance_names, vec!["High", "Middle", "Low"]);
+    }
+
+    #[test]
+    fn profit_factor_without_losses_ranks_by_the_profit() {
+        let mut metrics = PerformanceMetrics::calculate("ParameterSet", "All", &[], 10000);
+        metrics.total_pnl = 250.0;
+        assert_eq!(OptimizationObjective::ProfitFactor.score(&metrics), 250.0);
+        metrics.profit_factor = Some(1.8);
+        assert_eq!(OptimizationObjective::ProfitFactor.score(&metrics), 1.8);
+        assert_eq!(OptimizationObjective::from_name("Expectancy"), Some(OptimizationObjective::Expectancy));
+        assert_eq!(OptimizationObjective::from_name("Unknown"), None);
+    }
+
+    #[test]
+    fn walk_forward_windows_roll_by_the_out_of_sample_days() {
+        assert_eq!(
+            BacktestOptimizer::walk_forward_windows(12, 5, 2),
+            vec![(0..5, 5..7), (2..7, 7..9), (4..9, 9..11)]
+        );
+        //the last window ends exactly on the last date
+        assert_eq!(BacktestOptimizer::walk_forward_windows(7, 5, 2), vec![(0..5, 5..7)]);
+        assert!(BacktestOptimizer::walk_forward_windows(6, 5, 2).is_empty());
+        assert!(BacktestOptimizer::walk_forward_windows(12, 0, 2).is_empty());
+        assert!(BacktestOptimizer::walk_forward_windows(12, 5, 0).is_empty());
+    }
+
+    #[test]
+    fn walk_forward_uses_whole_trade_dates() {
+        let replay_stocks: Vec<RawStock> = (10..=13)
+            .map(|day| test_candle(format!("2023-08-{} 09:15:00+0530", day).as_str(), 100.0, 101.0, 99.0, 100.5, TimeFrame::FiveMinutes))
+            .collect();
+        let optimizer = BacktestOptimizer::new(OptimizationObjective::TotalPnl, 10000, TradeWatcherConfig::default());
+        let parameter_sets = small_grid().to_parameter_sets(&HammerConfig::default()).unwrap();
+        let walk_forward_results = optimizer.walk_forward(&parameter_sets[..1], &replay_stocks, 2, 1);
+        let windows: Vec<(&str, &str, &str, &str)> = walk_forward_results
+            .iter()
+            .map(|walk_forward_result| {
+                (
+                    walk_forward_result.in_sample_start_date.as_str(),
+                    walk_forward_result.in_sample_end_date.as_str(),
+                    walk_forward_result.out_of_sample_start_date.as_str(),
+                    walk_forward_result.out_of_sample_end_date.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            vec![("2023_08_10", "2023_08_11", "2023_08_12", "2023_08_12"), ("2023_08_11", "2023_08_12", "2023_08_13", "2023_08_13")]
+        );
+    }
+
+    #[test]
+    fn every_simulated_order_gets_its_own_id() {
+        //two hammers in a row, each entry fills on the next 1 min candle and exits at the target on the one after
+        let replay_stocks = vec![
+            test_candle("2023-08-10 09:15:00+0530", 100.0, 100.6, 95.0, 100.5, TimeFrame::FiveMinutes),
+            test_candle("2023-08-10 09:20:00+0530", 96.0, 96.5, 95.0, 96.0, TimeFrame::OneMinute),
+            test_candle("2023-08-10 09:21:00+0530", 100.0, 106.0, 99.0, 105.5, TimeFrame::OneMinute),
+            test_candle("2023-08-10 09:20:00+0530", 100.0, 100.6, 95.0, 100.5, TimeFrame::FiveMinutes),
+            test_candle("2023-08-10 09:25:00+0530", 96.0, 96.5, 95.0, 96.0, TimeFrame::OneMinute),
+            test_candle("2023-08-10 09:26:00+0530", 100.0, 106.0, 99.0, 105.5, TimeFrame::OneMinute),
+        ];
+        let optimizer = BacktestOptimizer::new(OptimizationObjective::TotalPnl, 10000, TradeWatcherConfig::default());
+        let parameter_set = ParameterSet {
+            hammer_config: HammerConfig::default(),
+            sma_window_size: 9,
+        };
+        let closed_orders = optimizer.simulate(&parameter_set, &replay_stocks);
+        assert_eq!(closed_orders.len(), 2);
+        assert_ne!(closed_orders[0].order_id, closed_orders[1].order_id);
+        assert!(closed_orders.iter().all(|order| order.status == OrderStatus::ExitedByTarget));
+    }
+}
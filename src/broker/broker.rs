@@ -0,0 +1,138 @@
+use std::{error::Error, fmt};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{enums::TradeType, raw_stock::RawStock};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BrokerOrderType {
+    Market,
+    Limit,
+}
+
+impl fmt::Display for BrokerOrderType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BrokerOrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl fmt::Display for BrokerOrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+//Long => BUY, Short => SELL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokerOrderRequest {
+    pub symbol: String,
+    pub trade_position_type: TradeType,
+    pub order_type: BrokerOrderType,
+    pub qty: i32,
+    pub limit_price: Option<f32>,
+    pub tag: String, //our order cache key, sent along so the broker side can be traced back
+}
+
+impl BrokerOrderRequest {
+    pub fn new(symbol: String, trade_position_type: TradeType, order_type: BrokerOrderType, qty: i32, limit_price: Option<f32>, tag: String) -> BrokerOrderRequest {
+        BrokerOrderRequest {
+            symbol,
+            trade_position_type,
+            order_type,
+            qty,
+            limit_price,
+            tag,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokerOrder {
+    pub broker_order_id: String,
+    pub symbol: String,
+    pub trade_position_type: TradeType,
+    pub order_type: BrokerOrderType,
+    pub qty: i32,
+    pub limit_price: Option<f32>,
+    pub tag: String,
+    pub status: BrokerOrderStatus,
+    pub filled_qty: i32,
+    pub average_fill_price: f32,
+    pub commission: f32,
+    pub placed_at: String,
+    pub updated_at: String,
+}
+
+impl BrokerOrder {
+    pub fn pending_qty(&self) -> i32 {
+        self.qty - self.filled_qty
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self.status,
+            BrokerOrderStatus::Filled | BrokerOrderStatus::Cancelled | BrokerOrderStatus::Rejected
+        )
+    }
+}
+
+//net position per symbol, positive qty => long, negative => short
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokerPosition {
+    pub symbol: String,
+    pub qty: i32,
+    pub average_price: f32,
+    pub realised_pnl: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrokerError {
+    OrderNotFound(String),
+    InvalidOrder(String),
+    OrderNotModifiable(String),
+    Rejected(String),
+    Connection(String),
+}
+
+impl fmt::Display for BrokerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BrokerError::OrderNotFound(broker_order_id) => write!(f, "order {} not found", broker_order_id),
+            BrokerError::InvalidOrder(reason) => write!(f, "invalid order => {}", reason),
+            BrokerError::OrderNotModifiable(broker_order_id) => write!(f, "order {} can not be modified any more", broker_order_id),
+            BrokerError::Rejected(reason) => write!(f, "order rejected => {}", reason),
+            BrokerError::Connection(reason) => write!(f, "broker not reachable => {}", reason),
+        }
+    }
+}
+
+impl Error for BrokerError {}
+
+//Everything OrderManager needs from a broker. The paper broker fills against the incoming candles,
+//a live broker only talks to its API and ignores the market data hook.
+pub trait Broker: Send + Sync + fmt::Debug {
+    fn name(&self) -> String;
+
+    fn place_order(&self, order_request: BrokerOrderRequest) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>>;
+
+    //None keeps the current value
+    fn modify_order(&self, broker_order_id: String, qty: Option<i32>, limit_price: Option<f32>) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>>;
+
+    fn cancel_order(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>>;
+
+    fn order_status(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>>;
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<BrokerPosition>, BrokerError>>;
+
+    fn on_market_data(&self, _raw_stock: &RawStock) {}
+}
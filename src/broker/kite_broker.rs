@@ -0,0 +1,597 @@
+use std::env;
+
+use chrono::{FixedOffset, NaiveDateTime, TimeZone};
+use futures::future::BoxFuture;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::common::{date_parser, enums::TradeType};
+
+use super::broker::{Broker, BrokerError, BrokerOrder, BrokerOrderRequest, BrokerOrderStatus, BrokerOrderType, BrokerPosition};
+
+const KITE_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const KITE_TAG_MAX_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KiteConfig {
+    pub base_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    pub access_token: String,
+    pub exchange: String,
+    pub product: String, //MIS => intraday
+    pub variety: String,
+}
+
+impl KiteConfig {
+    pub fn new(base_url: String, api_key: String, api_secret: String, access_token: String) -> KiteConfig {
+        KiteConfig {
+            base_url,
+            api_key,
+            api_secret,
+            access_token,
+            exchange: "NSE".to_string(),
+            product: "MIS".to_string(),
+            variety: "regular".to_string(),
+        }
+    }
+
+    //KITE_API_KEY, KITE_API_SECRET and KITE_ACCESS_TOKEN, None when any of them is missing
+    pub fn from_env() -> Option<KiteConfig> {
+        let api_key = env::var("KITE_API_KEY").ok()?;
+        let api_secret = env::var("KITE_API_SECRET").ok()?;
+        let access_token = env::var("KITE_ACCESS_TOKEN").ok()?;
+        let base_url = env::var("KITE_BASE_URL").unwrap_or_else(|_| "https://api.kite.trade".to_string());
+        Some(KiteConfig::new(base_url, api_key, api_secret, access_token))
+    }
+}
+
+//every Kite response is wrapped in {"status": "success", "data": ...} or {"status": "error", "message": ..., "error_type": ...}
+#[derive(Debug, Clone, Deserialize)]
+struct KiteResponse<T> {
+    status: String,
+    data: Option<T>,
+    message: Option<String>,
+    error_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct KiteOrderId {
+    order_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KiteOrder {
+    pub order_id: String,
+    pub status: String,
+    pub tradingsymbol: String,
+    pub exchange: String,
+    pub transaction_type: String,
+    pub order_type: String,
+    pub quantity: i32,
+    #[serde(default)]
+    pub filled_quantity: i32,
+    #[serde(default)]
+    pub pending_quantity: i32,
+    #[serde(default)]
+    pub price: f32,
+    #[serde(default)]
+    pub average_price: f32,
+    pub order_timestamp: Option<String>,
+    pub exchange_timestamp: Option<String>,
+    pub tag: Option<String>,
+    pub status_message: Option<String>,
+}
+
+impl KiteOrder {
+    pub fn to_broker_order(&self) -> BrokerOrder {
+        let status = match self.status.as_str() {
+            "COMPLETE" => BrokerOrderStatus::Filled,
+            "CANCELLED" => BrokerOrderStatus::Cancelled,
+            "REJECTED" => BrokerOrderStatus::Rejected,
+            _ if self.filled_quantity > 0 => BrokerOrderStatus::PartiallyFilled,
+            _ => BrokerOrderStatus::Open, //OPEN, TRIGGER PENDING and the transient *PENDING / REQ RECEIVED states
+        };
+        let placed_at = self
+            .order_timestamp
+            .as_deref()
+            .map(KiteBroker::kite_timestamp_to_stock_format)
+            .unwrap_or_else(date_parser::new_current_date_time_in_desired_stock_datetime_format);
+        let updated_at = self
+            .exchange_timestamp
+            .as_deref()
+            .map(KiteBroker::kite_timestamp_to_stock_format)
+            .unwrap_or_else(|| placed_at.clone());
+
+        BrokerOrder {
+            broker_order_id: self.order_id.clone(),
+            symbol: self.tradingsymbol.clone(),
+            trade_position_type: if self.transaction_type == "SELL" { TradeType::Short } else { TradeType::Long },
+            order_type: if self.order_type == "MARKET" { BrokerOrderType::Market } else { BrokerOrderType::Limit },
+            qty: self.quantity,
+            limit_price: if self.price > 0.0 { Some(self.price) } else { None },
+            tag: self.tag.clone().unwrap_or_default(),
+            status,
+            filled_qty: self.filled_quantity,
+            average_fill_price: self.average_price,
+            commission: 0.0, //charges only show up in the contract note
+            placed_at,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KiteTrade {
+    pub trade_id: String,
+    pub order_id: String,
+    pub exchange: String,
+    pub tradingsymbol: String,
+    pub transaction_type: String,
+    pub quantity: i32,
+    pub average_price: f32,
+    pub fill_timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct KitePosition {
+    tradingsymbol: String,
+    quantity: i32,
+    average_price: f32,
+    #[serde(default)]
+    realised: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct KitePositions {
+    net: Vec<KitePosition>,
+}
+
+//order update pushed by Kite to the postback url, checksum => SHA-256(order_id + order_timestamp + api_secret)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KitePostback {
+    #[serde(flatten)]
+    pub order: KiteOrder,
+    pub checksum: String,
+}
+
+//Kite Connect v3 REST adapter, orders go out as `variety` orders on `exchange` with the `product` of the config
+#[derive(Debug, Clone)]
+pub struct KiteBroker {
+    kite_config: KiteConfig,
+    http_client: Client,
+}
+
+impl KiteBroker {
+    pub fn new(kite_config: KiteConfig) -> KiteBroker {
+        KiteBroker {
+            kite_config,
+            http_client: Client::new(),
+        }
+    }
+
+    //Kite timestamps are IST without an offset
+    pub fn kite_timestamp_to_stock_format(kite_timestamp: &str) -> String {
+        let ist_offset = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+        match NaiveDateTime::parse_from_str(kite_timestamp, KITE_TIMESTAMP_FORMAT) {
+            Ok(naive_date_time) => match ist_offset.from_local_datetime(&naive_date_time).single() {
+                Some(date_time) => date_parser::format_date_time_in_stock_format(&date_time),
+                None => kite_timestamp.to_string(),
+            },
+            Err(_) => kite_timestamp.to_string(),
+        }
+    }
+
+    //Kite tags are alphanumeric and at most 20 characters
+    fn kite_tag(tag: &str) -> String {
+        tag.chars().filter(|c| c.is_ascii_alphanumeric()).take(KITE_TAG_MAX_LENGTH).collect()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http_client
+            .request(method, format!("{}{}", self.kite_config.base_url.trim_end_matches('/'), path))
+            .header("X-Kite-Version", "3")
+            .header("Authorization", format!("token {}:{}", self.kite_config.api_key, self.kite_config.access_token))
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request_builder: RequestBuilder, broker_order_id: Option<&str>) -> Result<T, BrokerError> {
+        let response = request_builder
+            .send()
+            .await
+            .map_err(|e| BrokerError::Connection(e.to_string()))?;
+        let status_code = response.status();
+        let body = response.text().await.map_err(|e| BrokerError::Connection(e.to_string()))?;
+
+        let kite_response = serde_json::from_str::<KiteResponse<T>>(body.as_str())
+            .map_err(|e| BrokerError::Connection(format!("unexpected response ({}) => {:?}", status_code, e)))?;
+        if kite_response.status == "success" {
+            if let Some(data) = kite_response.data {
+                return Ok(data);
+            }
+        }
+        Err(KiteBroker::map_error(status_code, kite_response.error_type, kite_response.message, broker_order_id))
+    }
+
+    fn map_error(status_code: StatusCode, error_type: Option<String>, message: Option<String>, broker_order_id: Option<&str>) -> BrokerError {
+        let message = message.unwrap_or_else(|| status_code.to_string());
+        if status_code == StatusCode::NOT_FOUND {
+            return BrokerError::OrderNotFound(broker_order_id.map(|id| id.to_string()).unwrap_or(message));
+        }
+        match error_type.as_deref() {
+            Some("InputException") => BrokerError::InvalidOrder(message),
+            Some("TokenException") | Some("PermissionException") | Some("NetworkException") | Some("DataException") => {
+                BrokerError::Connection(message)
+            }
+            _ => BrokerError::Rejected(message),
+        }
+    }
+
+    async fn fetch_order(&self, broker_order_id: &str) -> Result<BrokerOrder, BrokerError> {
+        //order history => every state the order went through, the last one is the current state
+        let order_history: Vec<KiteOrder> = self
+            .send(self.request(Method::GET, format!("/orders/{}", broker_order_id).as_str()), Some(broker_order_id))
+            .await?;
+        order_history
+            .last()
+            .map(|kite_order| kite_order.to_broker_order())
+            .ok_or_else(|| BrokerError::OrderNotFound(broker_order_id.to_string()))
+    }
+
+    //order book of the day, polled to pick up fills when no postback url is configured
+    pub async fn order_book(&self) -> Result<Vec<BrokerOrder>, BrokerError> {
+        let kite_orders: Vec<KiteOrder> = self.send(self.request(Method::GET, "/orders"), None).await?;
+        Ok(kite_orders.iter().map(|kite_order| kite_order.to_broker_order()).collect())
+    }
+
+    pub async fn trade_book(&self) -> Result<Vec<KiteTrade>, BrokerError> {
+        self.send(self.request(Method::GET, "/trades"), None).await
+    }
+
+    pub async fn order_trades(&self, broker_order_id: &str) -> Result<Vec<KiteTrade>, BrokerError> {
+        self.send(
+            self.request(Method::GET, format!("/orders/{}/trades", broker_order_id).as_str()),
+            Some(broker_order_id),
+        )
+        .await
+    }
+
+    pub fn parse_postback(&self, body: &str) -> Result<BrokerOrder, BrokerError> {
+        let kite_postback = serde_json::from_str::<KitePostback>(body)
+            .map_err(|e| BrokerError::InvalidOrder(format!("malformed postback => {:?}", e)))?;
+        let order_timestamp = kite_postback.order.order_timestamp.clone().unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}{}{}", kite_postback.order.order_id, order_timestamp, self.kite_config.api_secret).as_bytes());
+        let checksum: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+        if checksum != kite_postback.checksum {
+            return Err(BrokerError::InvalidOrder(format!("postback checksum mismatch for {}", kite_postback.order.order_id)));
+        }
+        Ok(kite_postback.order.to_broker_order())
+    }
+}
+
+impl Broker for KiteBroker {
+    fn name(&self) -> String {
+        "KiteBroker".to_string()
+    }
+
+    fn place_order(&self, order_request: BrokerOrderRequest) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            let mut form = vec![
+                ("tradingsymbol", order_request.symbol.clone()),
+                ("exchange", self.kite_config.exchange.clone()),
+                (
+                    "transaction_type",
+                    if order_request.trade_position_type == TradeType::Short { "SELL" } else { "BUY" }.to_string(),
+                ),
+                (
+                    "order_type",
+                    if order_request.order_type == BrokerOrderType::Market { "MARKET" } else { "LIMIT" }.to_string(),
+                ),
+                ("quantity", order_request.qty.to_string()),
+                ("product", self.kite_config.product.clone()),
+                ("validity", "DAY".to_string()),
+                ("tag", KiteBroker::kite_tag(order_request.tag.as_str())),
+            ];
+            if let Some(limit_price) = order_request.limit_price {
+                form.push(("price", limit_price.to_string()));
+            }
+
+            let kite_order_id: KiteOrderId = self
+                .send(
+                    self.request(Method::POST, format!("/orders/{}", self.kite_config.variety).as_str()).form(&form),
+                    None,
+                )
+                .await?;
+            self.fetch_order(kite_order_id.order_id.as_str()).await
+        })
+    }
+
+    fn modify_order(&self, broker_order_id: String, qty: Option<i32>, limit_price: Option<f32>) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            let mut form: Vec<(&str, String)> = Vec::new();
+            if let Some(qty) = qty {
+                form.push(("quantity", qty.to_string()));
+            }
+            if let Some(limit_price) = limit_price {
+                form.push(("price", limit_price.to_string()));
+            }
+            let path = format!("/orders/{}/{}", self.kite_config.variety, broker_order_id);
+            let kite_order_id: KiteOrderId = self
+                .send(self.request(Method::PUT, path.as_str()).form(&form), Some(broker_order_id.as_str()))
+                .await?;
+            self.fetch_order(kite_order_id.order_id.as_str()).await
+        })
+    }
+
+    fn cancel_order(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            let path = format!("/orders/{}/{}", self.kite_config.variety, broker_order_id);
+            let kite_order_id: KiteOrderId = self
+                .send(self.request(Method::DELETE, path.as_str()), Some(broker_order_id.as_str()))
+                .await?;
+            self.fetch_order(kite_order_id.order_id.as_str()).await
+        })
+    }
+
+    fn order_status(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move { self.fetch_order(broker_order_id.as_str()).await })
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<BrokerPosition>, BrokerError>> {
+        Box::pin(async move {
+            let kite_positions: KitePositions = self.send(self.request(Method::GET, "/portfolio/positions"), None).await?;
+            Ok(kite_positions
+                .net
+                .iter()
+                .map(|kite_position| BrokerPosition {
+                    symbol: kite_position.tradingsymbol.clone(),
+                    qty: kite_position.quantity,
+                    average_price: kite_position.average_price,
+                    realised_pnl: kite_position.realised,
+                })
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    macro_rules! kite_fixture {
+        ($file_name:expr) => {
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/kite/", $file_name))
+        };
+    }
+
+    const ORDER_ID: &str = "230810000123456";
+
+    fn kite_broker(mock_server: &MockServer) -> KiteBroker {
+        KiteBroker::new(KiteConfig::new(
+            mock_server.uri(),
+            "test_api_key".to_string(),
+            "test_api_secret".to_string(),
+            "test_access_token".to_string(),
+        ))
+    }
+
+    fn kite_response(status_code: u16, body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(status_code).set_body_raw(body, "application/json")
+    }
+
+    async fn mount_order_history(mock_server: &MockServer, body: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/orders/{}", ORDER_ID)))
+            .respond_with(kite_response(200, body))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn place_order_posts_the_kite_form_and_returns_the_order_state() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/orders/regular"))
+            .and(header("X-Kite-Version", "3"))
+            .and(header("Authorization", "token test_api_key:test_access_token"))
+            .and(body_string_contains("tradingsymbol=ADANIGREEN"))
+            .and(body_string_contains("exchange=NSE"))
+            .and(body_string_contains("transaction_type=BUY"))
+            .and(body_string_contains("order_type=LIMIT"))
+            .and(body_string_contains("quantity=20"))
+            .and(body_string_contains("product=MIS"))
+            .and(body_string_contains("price=912.5"))
+            .and(body_string_contains("tag=ORDERADANIGREENHamme"))
+            .respond_with(kite_response(200, kite_fixture!("place_order.json")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mount_order_history(&mock_server, kite_fixture!("order_history_open.json")).await;
+
+        let order_request = BrokerOrderRequest::new(
+            "ADANIGREEN".to_string(),
+            TradeType::Long,
+            BrokerOrderType::Limit,
+            20,
+            Some(912.5),
+            "ORDER_ADANIGREEN_HammerPatternAlgo".to_string(),
+        );
+        let broker_order = kite_broker(&mock_server).place_order(order_request).await.unwrap();
+
+        assert_eq!(broker_order.broker_order_id, ORDER_ID);
+        assert_eq!(broker_order.status, BrokerOrderStatus::Open);
+        assert_eq!(broker_order.order_type, BrokerOrderType::Limit);
+        assert_eq!(broker_order.limit_price, Some(912.5));
+        assert_eq!(broker_order.filled_qty, 0);
+        assert_eq!(broker_order.placed_at, "2023-08-10 09:45:00+0530");
+    }
+
+    #[tokio::test]
+    async fn order_status_maps_partial_and_complete_fills() {
+        let mock_server = MockServer::start().await;
+        mount_order_history(&mock_server, kite_fixture!("order_history_partially_filled.json")).await;
+        let broker_order = kite_broker(&mock_server).order_status(ORDER_ID.to_string()).await.unwrap();
+        assert_eq!(broker_order.status, BrokerOrderStatus::PartiallyFilled);
+        assert_eq!(broker_order.filled_qty, 12);
+        assert_eq!(broker_order.pending_qty(), 8);
+        assert_eq!(broker_order.average_fill_price, 912.35);
+
+        let mock_server = MockServer::start().await;
+        mount_order_history(&mock_server, kite_fixture!("order_history_complete.json")).await;
+        let broker_order = kite_broker(&mock_server).order_status(ORDER_ID.to_string()).await.unwrap();
+        assert_eq!(broker_order.status, BrokerOrderStatus::Filled);
+        assert_eq!(broker_order.filled_qty, 20);
+        assert_eq!(broker_order.average_fill_price, 912.4);
+        assert_eq!(broker_order.updated_at, "2023-08-10 09:53:02+0530");
+    }
+
+    #[tokio::test]
+    async fn modify_order_puts_the_new_qty_and_price() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/orders/regular/{}", ORDER_ID)))
+            .and(body_string_contains("quantity=15"))
+            .and(body_string_contains("price=910"))
+            .respond_with(kite_response(200, kite_fixture!("modify_order.json")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mount_order_history(&mock_server, kite_fixture!("order_history_modified.json")).await;
+
+        let broker_order = kite_broker(&mock_server)
+            .modify_order(ORDER_ID.to_string(), Some(15), Some(910.0))
+            .await
+            .unwrap();
+        assert_eq!(broker_order.qty, 15);
+        assert_eq!(broker_order.limit_price, Some(910.0));
+        assert_eq!(broker_order.status, BrokerOrderStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn cancel_order_keeps_the_filled_part() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("/orders/regular/{}", ORDER_ID)))
+            .respond_with(kite_response(200, kite_fixture!("cancel_order.json")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mount_order_history(&mock_server, kite_fixture!("order_history_cancelled.json")).await;
+
+        let broker_order = kite_broker(&mock_server).cancel_order(ORDER_ID.to_string()).await.unwrap();
+        assert_eq!(broker_order.status, BrokerOrderStatus::Cancelled);
+        assert_eq!(broker_order.filled_qty, 12);
+        assert_eq!(broker_order.average_fill_price, 912.35);
+    }
+
+    #[tokio::test]
+    async fn order_book_and_trade_book_are_parsed() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/orders"))
+            .respond_with(kite_response(200, kite_fixture!("orders.json")))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/trades"))
+            .respond_with(kite_response(200, kite_fixture!("trades.json")))
+            .mount(&mock_server)
+            .await;
+        let kite_broker = kite_broker(&mock_server);
+
+        let order_book = kite_broker.order_book().await.unwrap();
+        assert_eq!(order_book.len(), 2);
+        assert_eq!(order_book[0].status, BrokerOrderStatus::Filled);
+        assert_eq!(order_book[1].status, BrokerOrderStatus::Rejected);
+        assert_eq!(order_book[1].trade_position_type, TradeType::Short);
+        assert_eq!(order_book[1].order_type, BrokerOrderType::Market);
+        assert_eq!(order_book[1].limit_price, None);
+
+        let trade_book = kite_broker.trade_book().await.unwrap();
+        assert_eq!(trade_book.len(), 2);
+        assert_eq!(trade_book.iter().map(|kite_trade| kite_trade.quantity).sum::<i32>(), 20);
+        assert!(trade_book.iter().all(|kite_trade| kite_trade.order_id == ORDER_ID));
+    }
+
+    #[tokio::test]
+    async fn positions_are_read_from_the_net_book() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/portfolio/positions"))
+            .respond_with(kite_response(200, kite_fixture!("positions.json")))
+            .mount(&mock_server)
+            .await;
+
+        let positions = kite_broker(&mock_server).positions().await.unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].symbol, "ADANIGREEN");
+        assert_eq!(positions[0].qty, 20);
+        assert_eq!(positions[0].average_price, 912.4);
+    }
+
+    #[tokio::test]
+    async fn kite_errors_map_to_broker_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/orders/regular"))
+            .respond_with(kite_response(400, kite_fixture!("error_input_exception.json")))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/portfolio/positions"))
+            .respond_with(kite_response(403, kite_fixture!("error_token_exception.json")))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/orders/999"))
+            .respond_with(kite_response(404, kite_fixture!("error_order_not_found.json")))
+            .mount(&mock_server)
+            .await;
+        let kite_broker = kite_broker(&mock_server);
+
+        let order_request = BrokerOrderRequest::new(
+            "ADANIGREEN".to_string(),
+            TradeType::Long,
+            BrokerOrderType::Limit,
+            20,
+            Some(-1.0),
+            "ORDER_ADANIGREEN_HammerPatternAlgo".to_string(),
+        );
+        assert_eq!(
+            kite_broker.place_order(order_request).await,
+            Err(BrokerError::InvalidOrder("Invalid `price`.".to_string()))
+        );
+        assert_eq!(
+            kite_broker.positions().await,
+            Err(BrokerError::Connection("Incorrect `api_key` or `access_token`.".to_string()))
+        );
+        assert_eq!(
+            kite_broker.order_status("999".to_string()).await,
+            Err(BrokerError::OrderNotFound("999".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn postback_checksum_is_verified() {
+        let mock_server = MockServer::start().await;
+        let kite_broker = kite_broker(&mock_server);
+
+        let broker_order = kite_broker.parse_postback(kite_fixture!("postback.json")).unwrap();
+        assert_eq!(broker_order.broker_order_id, ORDER_ID);
+        assert_eq!(broker_order.status, BrokerOrderStatus::Filled);
+        assert_eq!(broker_order.average_fill_price, 912.4);
+
+        let tampered_postback = kite_fixture!("postback.json").replace("\"average_price\": 912.4", "\"average_price\": 900.0");
+        let tampered_postback = tampered_postback.replace(ORDER_ID, "230810000999999");
+        assert!(matches!(
+            kite_broker.parse_postback(tampered_postback.as_str()),
+            Err(BrokerError::InvalidOrder(_))
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod broker;
+pub mod paper_broker;
+pub mod kite_broker;
@@ -0,0 +1,435 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{date_parser, enums::TradeType, raw_stock::RawStock};
+
+use super::broker::{Broker, BrokerError, BrokerOrder, BrokerOrderRequest, BrokerOrderStatus, BrokerOrderType, BrokerPosition};
+
+//Every missing field falls back to Zerodha like intraday charges
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaperBrokerConfig {
+    pub slippage_percentage: f32, //market orders only, always against us
+    pub commission_percentage: f32,
+    pub max_commission_per_order: f32,
+    pub max_volume_participation: f32, //share of the candle volume one order can take, 0 => no partial fills
+}
+
+impl Default for PaperBrokerConfig {
+    fn default() -> PaperBrokerConfig {
+        PaperBrokerConfig {
+            slippage_percentage: 0.05,
+            commission_percentage: 0.03,
+            max_commission_per_order: 20.0,
+            max_volume_participation: 0.0,
+        }
+    }
+}
+
+//orders from earlier runs stay in Mongo keyed by their id => the sequence starts from the clock instead of 0,
+//every broker instance of the process draws from it so their ids never repeat either
+static ORDER_SEQUENCE: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(Utc::now().timestamp_millis() as u64 * 1000));
+
+fn next_order_id() -> String {
+    format!("PAPER_{}", ORDER_SEQUENCE.fetch_add(1, Ordering::Relaxed) + 1)
+}
+
+#[derive(Debug, Default)]
+struct PaperBook {
+    orders: Vec<BrokerOrder>,
+    positions: HashMap<String, BrokerPosition>,
+    last_candles: HashMap<String, RawStock>,
+}
+
+//Local broker for backtests and paper trading.
+//Market orders fill on the last seen candle (or the open of the next one), limit orders once a candle trades through the limit.
+#[derive(Debug)]
+pub struct PaperBroker {
+    paper_broker_config: PaperBrokerConfig,
+    paper_book: Mutex<PaperBook>,
+}
+
+impl PaperBroker {
+    pub fn new(paper_broker_config: PaperBrokerConfig) -> PaperBroker {
+        PaperBroker {
+            paper_broker_config,
+            paper_book: Mutex::new(PaperBook::default()),
+        }
+    }
+
+    fn validate_order_request(order_request: &BrokerOrderRequest) -> Result<(), BrokerError> {
+        if order_request.qty <= 0 {
+            return Err(BrokerError::InvalidOrder(format!("qty {} for {}", order_request.qty, order_request.symbol)));
+        }
+        if order_request.order_type == BrokerOrderType::Limit && order_request.limit_price.map(|price| price <= 0.0).unwrap_or(true) {
+            return Err(BrokerError::InvalidOrder(format!("limit order for {} without a limit price", order_request.symbol)));
+        }
+        Ok(())
+    }
+
+    fn slipped_price(&self, price: f32, trade_position_type: &TradeType) -> f32 {
+        let slippage = price * self.paper_broker_config.slippage_percentage / 100.0;
+        if *trade_position_type == TradeType::Long {
+            price + slippage
+        } else {
+            price - slippage
+        }
+    }
+
+    //None => the candle did not reach the order, is_placement => the candle was already closed when the order came in
+    fn find_fill_price(&self, broker_order: &BrokerOrder, stock: &RawStock, is_placement: bool) -> Option<f32> {
+        let reference_price = if is_placement { stock.close } else { stock.open };
+        match broker_order.order_type {
+            BrokerOrderType::Market => Some(self.slipped_price(reference_price, &broker_order.trade_position_type)),
+            BrokerOrderType::Limit => {
+                let limit_price = broker_order.limit_price?;
+                let (low, high) = if is_placement { (stock.close, stock.close) } else { (stock.low, stock.high) };
+                if broker_order.trade_position_type == TradeType::Long && low <= limit_price {
+                    Some(reference_price.min(limit_price))
+                } else if broker_order.trade_position_type == TradeType::Short && high >= limit_price {
+                    Some(reference_price.max(limit_price))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn fillable_qty(&self, broker_order: &BrokerOrder, stock: &RawStock) -> i32 {
+        let max_volume_participation = self.paper_broker_config.max_volume_participation;
+        if max_volume_participation <= 0.0 || stock.volume <= 0 {
+            return broker_order.pending_qty();
+        }
+        let candle_capacity = ((stock.volume as f32 * max_volume_participation) as i32).max(1);
+        broker_order.pending_qty().min(candle_capacity)
+    }
+
+    fn try_fill(&self, broker_order: &mut BrokerOrder, positions: &mut HashMap<String, BrokerPosition>, stock: &RawStock, is_placement: bool) {
+        let fill_price = match self.find_fill_price(broker_order, stock, is_placement) {
+            Some(fill_price) => fill_price,
+            None => return,
+        };
+        let fill_qty = self.fillable_qty(broker_order, stock);
+        if fill_qty <= 0 {
+            return;
+        }
+
+        let total_filled_qty = broker_order.filled_qty + fill_qty;
+        broker_order.average_fill_price =
+            (broker_order.average_fill_price * broker_order.filled_qty as f32 + fill_price * fill_qty as f32) / total_filled_qty as f32;
+        broker_order.filled_qty = total_filled_qty;
+        let turnover = fill_price * fill_qty as f32;
+        broker_order.commission += (turnover * self.paper_broker_config.commission_percentage / 100.0)
+            .min(self.paper_broker_config.max_commission_per_order);
+        broker_order.status = if broker_order.pending_qty() == 0 {
+            BrokerOrderStatus::Filled
+        } else {
+            BrokerOrderStatus::PartiallyFilled
+        };
+        broker_order.updated_at = stock.date.clone();
+
+        let signed_qty = if broker_order.trade_position_type == TradeType::Long { fill_qty } else { -fill_qty };
+        let position = positions.entry(broker_order.symbol.clone()).or_insert(BrokerPosition {
+            symbol: broker_order.symbol.clone(),
+            qty: 0,
+            average_price: 0.0,
+            realised_pnl: 0.0,
+        });
+        PaperBroker::apply_fill_to_position(position, signed_qty, fill_price);
+    }
+
+    fn apply_fill_to_position(position: &mut BrokerPosition, signed_qty: i32, fill_price: f32) {
+        //adding to the position (or opening it) => weighted average price
+        if position.qty == 0 || position.qty.signum() == signed_qty.signum() {
+            let total_qty = position.qty.abs() + signed_qty.abs();
+            position.average_price = (position.average_price * position.qty.abs() as f32 + fill_price * signed_qty.abs() as f32) / total_qty as f32;
+            position.qty += signed_qty;
+            return;
+        }
+
+        //reducing => book the pnl of the closed part, a flip opens the rest at the fill price
+        let closing_qty = position.qty.abs().min(signed_qty.abs());
+        position.realised_pnl += (fill_price - position.average_price) * closing_qty as f32 * position.qty.signum() as f32;
+        position.qty += signed_qty;
+        if position.qty == 0 {
+            position.average_price = 0.0;
+        } else if position.qty.signum() == signed_qty.signum() {
+            position.average_price = fill_price;
+        }
+    }
+}
+
+impl Broker for PaperBroker {
+    fn name(&self) -> String {
+        "PaperBroker".to_string()
+    }
+
+    fn place_order(&self, order_request: BrokerOrderRequest) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            PaperBroker::validate_order_request(&order_request)?;
+
+            let mut paper_book = self.paper_book.lock().unwrap();
+            let paper_book = &mut *paper_book;
+            let last_candle = paper_book.last_candles.get(&order_request.symbol).cloned();
+            //candle time keeps replayed orders on the market clock
+            let placed_at = last_candle
+                .as_ref()
+                .map(|stock| stock.date.clone())
+                .unwrap_or_else(date_parser::new_current_date_time_in_desired_stock_datetime_format);
+
+            let mut broker_order = BrokerOrder {
+                broker_order_id: next_order_id(),
+                symbol: order_request.symbol,
+                trade_position_type: order_request.trade_position_type,
+                order_type: order_request.order_type,
+                qty: order_request.qty,
+                limit_price: order_request.limit_price,
+                tag: order_request.tag,
+                status: BrokerOrderStatus::Open,
+                filled_qty: 0,
+                average_fill_price: 0.0,
+                commission: 0.0,
+                placed_at: placed_at.clone(),
+                updated_at: placed_at,
+            };
+            if let Some(last_candle) = last_candle {
+                self.try_fill(&mut broker_order, &mut paper_book.positions, &last_candle, true);
+            }
+            paper_book.orders.push(broker_order.clone());
+            Ok(broker_order)
+        })
+    }
+
+    fn modify_order(&self, broker_order_id: String, qty: Option<i32>, limit_price: Option<f32>) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            let mut paper_book = self.paper_book.lock().unwrap();
+            let broker_order = paper_book
+                .orders
+                .iter_mut()
+                .find(|broker_order| broker_order.broker_order_id == broker_order_id)
+                .ok_or_else(|| BrokerError::OrderNotFound(broker_order_id.clone()))?;
+            if broker_order.is_complete() {
+                return Err(BrokerError::OrderNotModifiable(broker_order_id));
+            }
+            if let Some(qty) = qty {
+                if qty <= 0 || qty < broker_order.filled_qty {
+                    return Err(BrokerError::InvalidOrder(format!("qty {} below the filled qty {}", qty, broker_order.filled_qty)));
+                }
+                broker_order.qty = qty;
+                if broker_order.pending_qty() == 0 {
+                    broker_order.status = BrokerOrderStatus::Filled;
+                }
+            }
+            if let Some(limit_price) = limit_price {
+                if broker_order.order_type != BrokerOrderType::Limit || limit_price <= 0.0 {
+                    return Err(BrokerError::InvalidOrder(format!("limit price {} for {}", limit_price, broker_order_id)));
+                }
+                broker_order.limit_price = Some(limit_price);
+            }
+            Ok(broker_order.clone())
+        })
+    }
+
+    fn cancel_order(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            let mut paper_book = self.paper_book.lock().unwrap();
+            let broker_order = paper_book
+                .orders
+                .iter_mut()
+                .find(|broker_order| broker_order.broker_order_id == broker_order_id)
+                .ok_or_else(|| BrokerError::OrderNotFound(broker_order_id.clone()))?;
+            if broker_order.is_complete() {
+                return Err(BrokerError::OrderNotModifiable(broker_order_id));
+            }
+            //the filled part stays filled, only the pending qty is cancelled
+            broker_order.status = BrokerOrderStatus::Cancelled;
+            Ok(broker_order.clone())
+        })
+    }
+
+    fn order_status(&self, broker_order_id: String) -> BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+        Box::pin(async move {
+            self.paper_book
+                .lock()
+                .unwrap()
+                .orders
+                .iter()
+                .find(|broker_order| broker_order.broker_order_id == broker_order_id)
+                .cloned()
+                .ok_or(BrokerError::OrderNotFound(broker_order_id))
+        })
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<BrokerPosition>, BrokerError>> {
+        Box::pin(async move {
+            let mut positions: Vec<BrokerPosition> = self.paper_book.lock().unwrap().positions.values().cloned().collect();
+            positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            Ok(positions)
+        })
+    }
+
+    fn on_market_data(&self, raw_stock: &RawStock) {
+        let mut paper_book = self.paper_book.lock().unwrap();
+        let paper_book = &mut *paper_book;
+        for broker_order in paper_book.orders.iter_mut() {
+            if broker_order.symbol == raw_stock.symbol && !broker_order.is_complete() {
+                self.try_fill(broker_order, &mut paper_book.positions, raw_stock, false);
+            }
+        }
+        paper_book.last_candles.insert(raw_stock.symbol.clone(), raw_stock.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::TimeFrame;
+
+    fn order_request(trade_position_type: TradeType, order_type: BrokerOrderType, qty: i32, limit_price: Option<f32>) -> BrokerOrderRequest {
+        BrokerOrderRequest::new("ADANIGREEN".to_string(), trade_position_type, order_type, qty, limit_price, "ORDER_ADANIGREEN_HammerPatternAlgo".to_string())
+    }
+
+    fn limit_order_request() -> BrokerOrderRequest {
+        order_request(TradeType::Long, BrokerOrderType::Limit, 10, Some(100.0))
+    }
+
+    fn test_candle(open: f32, high: f32, low: f32, close: f32, volume: i32) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), "2023-08-10 09:31:00+0530".to_string(), close, high, low, open, volume, TimeFrame::OneMinute)
+    }
+
+    fn test_config(slippage_percentage: f32, max_volume_participation: f32) -> PaperBrokerConfig {
+        PaperBrokerConfig {
+            slippage_percentage,
+            max_volume_participation,
+            ..PaperBrokerConfig::default()
+        }
+    }
+
+    fn assert_price(actual: f32, expected: f32, case: &str) {
+        assert!((actual - expected).abs() < 0.001, "{} => {} instead of {}", case, actual, expected);
+    }
+
+    #[tokio::test]
+    async fn order_ids_do_not_repeat_across_broker_instances() {
+        let first_broker = PaperBroker::new(PaperBrokerConfig::default());
+        let first_order = first_broker.place_order(limit_order_request()).await.unwrap();
+        let second_order = first_broker.place_order(limit_order_request()).await.unwrap();
+        assert_ne!(first_order.broker_order_id, second_order.broker_order_id);
+
+        //a restarted process gets a fresh broker
+        let restarted_broker = PaperBroker::new(PaperBrokerConfig::default());
+        let restarted_order = restarted_broker.place_order(limit_order_request()).await.unwrap();
+        assert_ne!(restarted_order.broker_order_id, first_order.broker_order_id);
+        assert_ne!(restarted_order.broker_order_id, second_order.broker_order_id);
+    }
+
+    #[tokio::test]
+    async fn resting_orders_fill_against_the_next_candle() {
+        //order type, side, limit, candle open high low close, expected fill price
+        let cases = vec![
+            ("market long at the open plus slippage", BrokerOrderType::Market, TradeType::Long, None, (100.0, 102.0, 99.0, 101.0), Some(100.1)),
+            ("market short at the open minus slippage", BrokerOrderType::Market, TradeType::Short, None, (100.0, 102.0, 99.0, 101.0), Some(99.9)),
+            ("long limit touched", BrokerOrderType::Limit, TradeType::Long, Some(100.0), (101.0, 101.5, 99.0, 100.5), Some(100.0)),
+            ("long limit gapped through", BrokerOrderType::Limit, TradeType::Long, Some(100.0), (98.0, 99.0, 97.5, 98.5), Some(98.0)),
+            ("long limit not reached", BrokerOrderType::Limit, TradeType::Long, Some(100.0), (101.0, 102.0, 100.5, 101.5), None),
+            ("short limit touched", BrokerOrderType::Limit, TradeType::Short, Some(100.0), (99.0, 101.0, 98.5, 99.5), Some(100.0)),
+            ("short limit gapped through", BrokerOrderType::Limit, TradeType::Short, Some(100.0), (102.0, 103.0, 101.5, 102.5), Some(102.0)),
+            ("short limit not reached", BrokerOrderType::Limit, TradeType::Short, Some(100.0), (99.0, 99.5, 98.0, 99.0), None),
+        ];
+        for (case, order_type, trade_position_type, limit_price, (open, high, low, close), expected_fill_price) in cases {
+            let paper_broker = PaperBroker::new(test_config(0.1, 0.0));
+            let placed_order = paper_broker.place_order(order_request(trade_position_type, order_type, 10, limit_price)).await.unwrap();
+            assert_eq!(placed_order.status, BrokerOrderStatus::Open, "{}", case);
+
+            paper_broker.on_market_data(&test_candle(open, high, low, close, 1000));
+            let broker_order = paper_broker.order_status(placed_order.broker_order_id).await.unwrap();
+            match expected_fill_price {
+                Some(expected_fill_price) => {
+                    assert_eq!(broker_order.status, BrokerOrderStatus::Filled, "{}", case);
+                    assert_eq!(broker_order.filled_qty, 10, "{}", case);
+                    assert_price(broker_order.average_fill_price, expected_fill_price, case);
+                }
+                None => {
+                    assert_eq!(broker_order.status, BrokerOrderStatus::Open, "{}", case);
+                    assert_eq!(broker_order.filled_qty, 0, "{}", case);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn orders_placed_after_a_candle_fill_on_its_close() {
+        //order type, side, limit, expected fill price against a close of 100
+        let cases = vec![
+            ("market long", BrokerOrderType::Market, TradeType::Long, None, Some(100.1)),
+            ("market short", BrokerOrderType::Market, TradeType::Short, None, Some(99.9)),
+            ("long limit above the close", BrokerOrderType::Limit, TradeType::Long, Some(100.5), Some(100.0)),
+            ("long limit below the close", BrokerOrderType::Limit, TradeType::Long, Some(99.5), None),
+            ("short limit below the close", BrokerOrderType::Limit, TradeType::Short, Some(99.5), Some(100.0)),
+            ("short limit above the close", BrokerOrderType::Limit, TradeType::Short, Some(100.5), None),
+        ];
+        for (case, order_type, trade_position_type, limit_price, expected_fill_price) in cases {
+            let paper_broker = PaperBroker::new(test_config(0.1, 0.0));
+            //the low and high of a closed candle can not fill a new order
+            paper_broker.on_market_data(&test_candle(101.0, 102.0, 98.0, 100.0, 1000));
+            let broker_order = paper_broker.place_order(order_request(trade_position_type, order_type, 10, limit_price)).await.unwrap();
+            match expected_fill_price {
+                Some(expected_fill_price) => {
+                    assert_eq!(broker_order.status, BrokerOrderStatus::Filled, "{}", case);
+                    assert_price(broker_order.average_fill_price, expected_fill_price, case);
+                }
+                None => assert_eq!(broker_order.status, BrokerOrderStatus::Open, "{}", case),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn commission_is_a_share_of_the_turnover_up_to_the_cap() {
+        //qty at a fill price of 100, expected commission with 0.03% capped at 20
+        let cases = vec![(10, 0.3), (500, 15.0), (1000, 20.0)];
+        for (qty, expected_commission) in cases {
+            let paper_broker = PaperBroker::new(test_config(0.0, 0.0));
+            paper_broker.on_market_data(&test_candle(100.0, 100.0, 100.0, 100.0, 100000));
+            let broker_order = paper_broker.place_order(order_request(TradeType::Long, BrokerOrderType::Market, qty, None)).await.unwrap();
+            assert_price(broker_order.commission, expected_commission, format!("qty {}", qty).as_str());
+        }
+    }
+
+    #[tokio::test]
+    async fn volume_participation_fills_large_orders_over_several_candles() {
+        let paper_broker = PaperBroker::new(test_config(0.0, 0.01));
+        let placed_order = paper_broker.place_order(order_request(TradeType::Long, BrokerOrderType::Limit, 10, Some(100.0))).await.unwrap();
+
+        paper_broker.on_market_data(&test_candle(100.0, 100.5, 99.5, 100.0, 600));
+        let broker_order = paper_broker.order_status(placed_order.broker_order_id.clone()).await.unwrap();
+        assert_eq!(broker_order.status, BrokerOrderStatus::PartiallyFilled);
+        assert_eq!(broker_order.filled_qty, 6);
+
+        paper_broker.on_market_data(&test_candle(99.0, 99.5, 98.5, 99.0, 600));
+        let broker_order = paper_broker.order_status(placed_order.broker_order_id.clone()).await.unwrap();
+        assert_eq!(broker_order.status, BrokerOrderStatus::Filled);
+        assert_eq!(broker_order.filled_qty, 10);
+        assert_price(broker_order.average_fill_price, (100.0 * 6.0 + 99.0 * 4.0) / 10.0, "weighted average");
+
+        //the cancel of a partial fill keeps the filled part as a position
+        let paper_broker = PaperBroker::new(test_config(0.0, 0.01));
+        let placed_order = paper_broker.place_order(order_request(TradeType::Short, BrokerOrderType::Market, 10, None)).await.unwrap();
+        paper_broker.on_market_data(&test_candle(100.0, 100.5, 99.5, 100.0, 300));
+        let cancelled_order = paper_broker.cancel_order(placed_order.broker_order_id).await.unwrap();
+        assert_eq!(cancelled_order.status, BrokerOrderStatus::Cancelled);
+        assert_eq!(cancelled_order.filled_qty, 3);
+        let positions = paper_broker.positions().await.unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].qty, -3);
+    }
+}
@@ -0,0 +1,27 @@
+use std::{error::Error, fs};
+
+use serde::de::DeserializeOwned;
+
+//.toml files hold the config under [table_name] ([[table_name]] for lists), .json files hold it as the plain value
+pub fn load_from_file<T: DeserializeOwned>(file_path: &str, table_name: &str) -> Result<T, Box<dyn Error>> {
+    let file_content = fs::read_to_string(file_path)?;
+    if file_path.ends_with(".toml") {
+        let mut config_file: toml::Table = toml::from_str(file_content.as_str())?;
+        match config_file.remove(table_name) {
+            Some(config) => Ok(config.try_into::<T>()?),
+            None => Err(format!("missing [{}] table", table_name).into()),
+        }
+    } else {
+        Ok(serde_json::from_str::<T>(file_content.as_str())?)
+    }
+}
+
+pub fn load_config<T: DeserializeOwned + Default>(file_path: &str, table_name: &str) -> T {
+    match load_from_file::<T>(file_path, table_name) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Not able to read {} from {} => {:?}", table_name, file_path, e);
+            T::default()
+        }
+    }
+}
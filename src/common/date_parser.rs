@@ -41,4 +41,20 @@ pub fn return_only_date_from_datetime(date: &str) -> String {
     date_time.format("%Y_%m_%d").to_string()
 }
 
+#[allow(dead_code, unused_variables)]
+pub fn date_time_in_seconds(date: &str) -> i64 {
+    let date_time = DateTime::parse_from_str(date, FILE_STOCK_DATETIME_FORMAT).unwrap();
+    date_time.timestamp()
+}
+
+#[allow(dead_code, unused_variables)]
+pub fn parse_date_time_in_stock_format(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(date, FILE_STOCK_DATETIME_FORMAT).ok()
+}
+
+#[allow(dead_code, unused_variables)]
+pub fn format_date_time_in_stock_format(date_time: &DateTime<FixedOffset>) -> String {
+    date_time.format(FILE_STOCK_DATETIME_FORMAT).to_string()
+}
+
 // }
@@ -1,8 +1,9 @@
 use mongodb::{Database, Collection};
 use serde::{Deserialize, Serialize};
-use std::{fmt, sync::{Arc, Mutex}};
+use std::fmt;
+use tokio::sync::broadcast;
 
-use crate::{algo_hub::hammer_pattern::{HammerCandle, HammerPatternUtil}, data_consumer::current_market_state::CurrentMarketState, order_manager::{order_dispatcher::Order, trade_signal_keeper::{TradeSignal, TradeSignalsKeeper}, self}};
+use crate::{algo_hub::{strategy::StrategyConfigs, strategy_state::StrategyStates}, data_consumer::{candle_aggregator::AggregatedCandle, current_market_state::CurrentMarketState, data_consumer::ReconnectConfig, market_state_history::MarketStateHistory, indicators::IndicatorLedger}, order_manager::{order_dispatcher::Order, position_sizer::PositionSizer, risk_gate::RiskGate, trade_signal_keeper::{TradeSignal, TradeSignalsKeeper}, self}, trade_watcher::trade_watcher_config::TradeWatcherConfig};
 
 
 #[allow(dead_code)]
@@ -52,6 +53,20 @@ impl fmt::Display for MarketTrend {
 
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExitReason{
+    TargetHit,
+    StopLossHit,
+    TimeExit,
+    SquareOff, //intraday cutoff, every open position is closed
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TimeFrame{
     OneMinute,
     ThreeMinutes,
@@ -75,6 +90,7 @@ impl fmt::Display for TimeFrame {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ThreadJobType{
     DataConsumerViaSocket,
+    DataConsumerViaCandleAggregator, //higher time frame bars built from the 1 min socket
     TradeWatcherCron
 }
 
@@ -83,6 +99,9 @@ pub struct ThreadWorkerConfig {
     pub thread_job_type: ThreadJobType,
     pub root_system_config: RootSystemConfig,
     pub time_frame: TimeFrame,
+    pub stream_symbol: Option<String>, //Some => the stream carries a single symbol, None => every message carries its own symbol column
+    pub reconnect_config: ReconnectConfig,
+    pub candle_broadcaster: broadcast::Sender<AggregatedCandle>,
 }
 
 #[derive(Debug, Clone)]
@@ -95,14 +114,19 @@ pub struct TradeableAlgoConfig<GenericCollectionStruct, GenericCollectionLedger>
 #[derive(Debug, Clone)]
 pub struct RootSystemConfig {
     pub database_instance : Database,
-    pub hammer_candle_collection: Collection<HammerCandle>,
-    pub hammer_ledger: HammerPatternUtil,
     pub current_market_state_collection: Collection<CurrentMarketState>,
+    pub market_state_history: MarketStateHistory, //built once, every worker appends its snapshots through it
     pub orders_collection: Collection<Order>,
     pub trade_signal_collection: Collection<TradeSignal>,
     pub server_url: String,
     pub tradeable_algo_types: Vec<AlgoTypes>,
+    pub tradeable_symbols: Vec<String>,
+    pub strategy_configs: StrategyConfigs, //config sections the strategies are built from
+    pub strategy_states: StrategyStates,
     pub trade_keeper: TradeSignalsKeeper, 
     pub order_manager: order_manager::order_dispatcher::OrderManager,
-    pub shared_order_ledger: Arc<Mutex<Vec<Order>>>
+    pub risk_gate: RiskGate,
+    pub position_sizer: PositionSizer,
+    pub trade_watcher_config: TradeWatcherConfig,
+    pub indicator_ledger: IndicatorLedger, //not shared => every worker keeps the indicators of its own candles
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::common::enums::TimeFrame;
@@ -38,22 +40,26 @@ impl RawStock{
     }
 }
 
+//candles are kept per symbol so the history of one instrument never leaks into another
 pub struct RawStockLedger {
-    pub raw_stocks: Vec<RawStock>
+    pub raw_stocks: HashMap<String, Vec<RawStock>>
 }
 
 impl RawStockLedger {
     pub fn new() -> RawStockLedger {
         RawStockLedger {
-            raw_stocks: Vec::new(),
+            raw_stocks: HashMap::new(),
         }
     }
 
-    pub fn get_raw_stocks(&self) -> &Vec<RawStock> {
-        &self.raw_stocks
+    pub fn get_raw_stocks(&self, symbol: &str) -> &[RawStock] {
+        match self.raw_stocks.get(symbol) {
+            Some(raw_stocks) => raw_stocks,
+            None => &[],
+        }
     }
 
     pub fn add_raw_stock(&mut self, raw_stock: RawStock) {
-        self.raw_stocks.push(raw_stock);
+        self.raw_stocks.entry(raw_stock.symbol.clone()).or_default().push(raw_stock);
     }
 }
\ No newline at end of file
@@ -5,6 +5,9 @@ use redis::{Client, Commands, Connection, RedisResult};
 
 static mut REDIS_CLIENT: Option<Mutex<RedisClient>> = None;
 static INIT: Once = Once::new();
+//backtests replay into their own Redis database so resetting them never touches the live keys
+static mut BACKTEST_REDIS_CLIENT: Option<Mutex<RedisClient>> = None;
+static BACKTEST_INIT: Once = Once::new();
 
 pub struct RedisClient {
     connection: Connection,
@@ -18,8 +21,11 @@ pub struct RedisClient {
 // }
 
 impl RedisClient {
-    fn new() -> RedisResult<Self> {
-        let client = Client::open("redis://127.0.0.1/")?;
+    pub const REDIS_URL: &'static str = "redis://127.0.0.1/";
+    pub const BACKTEST_REDIS_URL: &'static str = "redis://127.0.0.1/1";
+
+    fn new(redis_url: &str) -> RedisResult<Self> {
+        let client = Client::open(redis_url)?;
         let connection = client.get_connection()?;
         Ok(Self { connection })
     }
@@ -27,7 +33,7 @@ impl RedisClient {
     pub fn get_instance() -> &'static Mutex<RedisClient> {
         unsafe {
             INIT.call_once(|| {
-                REDIS_CLIENT = Some(Mutex::new(RedisClient::new().unwrap()));
+                REDIS_CLIENT = Some(Mutex::new(RedisClient::new(RedisClient::REDIS_URL).unwrap()));
             });
 
             REDIS_CLIENT
@@ -36,8 +42,20 @@ impl RedisClient {
         }
     }
 
+    pub fn get_backtest_instance() -> &'static Mutex<RedisClient> {
+        unsafe {
+            BACKTEST_INIT.call_once(|| {
+                BACKTEST_REDIS_CLIENT = Some(Mutex::new(RedisClient::new(RedisClient::BACKTEST_REDIS_URL).unwrap()));
+            });
+
+            BACKTEST_REDIS_CLIENT
+                .as_ref()
+                .expect("Failed to initialize the backtest Redis client")
+        }
+    }
+
     pub fn set_data(&mut self, key: &str, value: &str) -> RedisResult<()> {
-        self.connection.set(key, value)?;
+        self.connection.set::<_, _, ()>(key, value)?;
         Ok(())
     }
 
@@ -47,7 +65,7 @@ impl RedisClient {
     }
 
     pub fn delete_data(&mut self, key: &str) -> RedisResult<()> {
-        self.connection.del(key)?;
+        self.connection.del::<_, ()>(key)?;
         Ok(())
     }
 }
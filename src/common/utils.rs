@@ -1,10 +1,28 @@
-use crate::common::enums::{TimeFrame, AlgoTypes};
+use crate::common::enums::TimeFrame;
 
-//ORDER_TradeAlgoType_Symbol
-pub fn order_cache_key_formatter(symbol: &str, algo_type: &AlgoTypes) -> String {
-    format!("ORDER_{}_{}", symbol, algo_type.to_string())
+//ORDER_Symbol_StrategyInstance => the default instance of every strategy is named after its AlgoTypes
+pub fn order_cache_key_formatter(symbol: &str, strategy_instance: &str) -> String {
+    format!("ORDER_{}_{}", symbol, strategy_instance)
 }
 
 pub fn current_market_state_cache_key_formatter(trade_date_only: &str, symbol: &str, market_time_frame: &TimeFrame) -> String {
     format!("{}_{}_{}_{}","CMS" , trade_date_only, symbol, market_time_frame)
-}
\ No newline at end of file
+}
+//PNL_TradeDate => one PnL state per trading day
+pub fn current_pnl_state_cache_key_formatter(trade_date_only: &str) -> String {
+    format!("{}_{}", "PNL", trade_date_only)
+}
+
+pub fn time_frame_in_seconds(market_time_frame: &TimeFrame) -> i64 {
+    match market_time_frame {
+        TimeFrame::OneMinute => 60,
+        TimeFrame::ThreeMinutes => 3 * 60,
+        TimeFrame::FiveMinutes => 5 * 60,
+        TimeFrame::FifteenMinutes => 15 * 60,
+        TimeFrame::OneDay => 24 * 60 * 60,
+        TimeFrame::OneWeek => 7 * 24 * 60 * 60,
+        TimeFrame::OneMonth => 30 * 24 * 60 * 60,
+        TimeFrame::OneYear => 365 * 24 * 60 * 60,
+        TimeFrame::Infinity => 0,
+    }
+}
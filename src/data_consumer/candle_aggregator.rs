@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+use crate::common::{
+    date_parser,
+    enums::TimeFrame,
+    raw_stock::RawStock,
+    utils::time_frame_in_seconds,
+};
+
+//what the 1 min worker publishes to the higher time frame workers
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregatedCandle {
+    pub raw_stock: RawStock,
+    pub is_backfilled: bool, //completed by a backfilled 1 min candle => only goes into the ledger
+}
+
+#[derive(Debug, Clone)]
+struct BuildingCandle {
+    bucket_end: DateTime<FixedOffset>,
+    raw_stock: RawStock,
+}
+
+//Builds higher time frame bars out of the 1 min candles.
+//Buckets are aligned to the session open (NSE 09:15 - 15:30), so 3/5/15 min bars line up with the exchange bars
+//and OneDay covers the whole session. OneWeek runs from the Monday open to the Friday close, OneMonth from the open
+//of the 1st to the close of the last weekday. Candle dates are the bar start, same as the CSV and socket feeds.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    time_frames: Vec<TimeFrame>,
+    session_open_time: NaiveTime,
+    session_close_time: NaiveTime,
+    building_candles: HashMap<(String, TimeFrame), BuildingCandle>,
+    last_candle_starts: HashMap<String, DateTime<FixedOffset>>, //of the last 1 min candle taken per symbol
+}
+
+impl CandleAggregator {
+    pub fn new(time_frames: Vec<TimeFrame>) -> CandleAggregator {
+        CandleAggregator::new_with_session(
+            time_frames,
+            NaiveTime::from_hms_opt(9, 15, 0).unwrap(),
+            NaiveTime::from_hms_opt(15, 30, 0).unwrap(),
+        )
+    }
+
+    pub fn new_with_session(time_frames: Vec<TimeFrame>, session_open_time: NaiveTime, session_close_time: NaiveTime) -> CandleAggregator {
+        CandleAggregator {
+            time_frames,
+            session_open_time,
+            session_close_time,
+            building_candles: HashMap::new(),
+            last_candle_starts: HashMap::new(),
+        }
+    }
+
+    //returns the bars completed by this candle, a bar is also emitted early when a later bucket starts before it was closed (missed minutes)
+    pub fn add_one_minute_candle(&mut self, stock: &RawStock) -> Vec<RawStock> {
+        let mut completed_candles = Vec::new();
+        if stock.market_time_frame != TimeFrame::OneMinute {
+            return completed_candles;
+        }
+
+        let candle_start = match date_parser::parse_date_time_in_stock_format(stock.date.as_str()) {
+            Some(candle_start) => candle_start,
+            None => return completed_candles,
+        };
+        let candle_end = candle_start + Duration::minutes(1);
+        let (session_open, session_close) = match self.session_bounds(&candle_start) {
+            Some(session_bounds) => session_bounds,
+            None => return completed_candles,
+        };
+        //pre-open and post-close ticks are not part of any exchange bar
+        if candle_start < session_open || candle_end > session_close {
+            return completed_candles;
+        }
+        //a late or repeated candle would reopen a bar that was already emitted
+        if let Some(last_candle_start) = self.last_candle_starts.get(&stock.symbol) {
+            if candle_start <= *last_candle_start {
+                return completed_candles;
+            }
+        }
+        self.last_candle_starts.insert(stock.symbol.clone(), candle_start);
+
+        for time_frame in self.time_frames.clone().iter() {
+            let (bucket_start, bucket_end) = match self.bucket_bounds(time_frame, &candle_start, &session_open, &session_close) {
+                Some(bucket_bounds) => bucket_bounds,
+                None => continue,
+            };
+            let key = (stock.symbol.clone(), time_frame.clone());
+
+            let is_new_bucket = self
+                .building_candles
+                .get(&key)
+                .map(|building_candle| building_candle.bucket_end != bucket_end)
+                .unwrap_or(false);
+            if is_new_bucket {
+                if let Some(building_candle) = self.building_candles.remove(&key) {
+                    completed_candles.push(building_candle.raw_stock);
+                }
+            }
+
+            match self.building_candles.get_mut(&key) {
+                Some(building_candle) => {
+                    building_candle.raw_stock.high = building_candle.raw_stock.high.max(stock.high);
+                    building_candle.raw_stock.low = building_candle.raw_stock.low.min(stock.low);
+                    building_candle.raw_stock.close = stock.close;
+                    building_candle.raw_stock.volume += stock.volume;
+                }
+                None => {
+                    self.building_candles.insert(
+                        key.clone(),
+                        BuildingCandle {
+                            bucket_end,
+                            raw_stock: RawStock::new(
+                                stock.symbol.clone(),
+                                date_parser::format_date_time_in_stock_format(&bucket_start),
+                                stock.close,
+                                stock.high,
+                                stock.low,
+                                stock.open,
+                                stock.volume,
+                                time_frame.clone(),
+                            ),
+                        },
+                    );
+                }
+            }
+
+            if candle_end >= bucket_end {
+                if let Some(building_candle) = self.building_candles.remove(&key) {
+                    completed_candles.push(building_candle.raw_stock);
+                }
+            }
+        }
+        completed_candles
+    }
+
+    fn session_bounds(&self, candle_start: &DateTime<FixedOffset>) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        self.session_bounds_on(&candle_start.date_naive(), candle_start.offset())
+    }
+
+    fn session_bounds_on(&self, trade_date: &NaiveDate, offset: &FixedOffset) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        let session_open = offset.from_local_datetime(&trade_date.and_time(self.session_open_time)).single()?;
+        let session_close = offset.from_local_datetime(&trade_date.and_time(self.session_close_time)).single()?;
+        Some((session_open, session_close))
+    }
+
+    fn bucket_bounds(
+        &self,
+        time_frame: &TimeFrame,
+        candle_start: &DateTime<FixedOffset>,
+        session_open: &DateTime<FixedOffset>,
+        session_close: &DateTime<FixedOffset>,
+    ) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        let trade_date = candle_start.date_naive();
+        match time_frame {
+            TimeFrame::OneDay => Some((*session_open, *session_close)),
+            TimeFrame::OneWeek => {
+                let week_start_date = trade_date - Duration::days(trade_date.weekday().num_days_from_monday() as i64);
+                let week_end_date = week_start_date + Duration::days(4);
+                let (bucket_start, _) = self.session_bounds_on(&week_start_date, candle_start.offset())?;
+                let (_, bucket_end) = self.session_bounds_on(&week_end_date, candle_start.offset())?;
+                Some((bucket_start, bucket_end))
+            }
+            TimeFrame::OneMonth => {
+                let month_start_date = trade_date.with_day(1)?;
+                let next_month_start_date = if trade_date.month() == 12 {
+                    NaiveDate::from_ymd_opt(trade_date.year() + 1, 1, 1)?
+                } else {
+                    NaiveDate::from_ymd_opt(trade_date.year(), trade_date.month() + 1, 1)?
+                };
+                let mut month_end_date = next_month_start_date - Duration::days(1);
+                while matches!(month_end_date.weekday(), Weekday::Sat | Weekday::Sun) {
+                    month_end_date -= Duration::days(1);
+                }
+                let (bucket_start, _) = self.session_bounds_on(&month_start_date, candle_start.offset())?;
+                let (_, bucket_end) = self.session_bounds_on(&month_end_date, candle_start.offset())?;
+                Some((bucket_start, bucket_end))
+            }
+            _ => {
+                let bucket_size_in_seconds = time_frame_in_seconds(time_frame).max(60);
+                let seconds_since_open = (*candle_start - *session_open).num_seconds();
+                let bucket_start = *session_open + Duration::seconds(seconds_since_open / bucket_size_in_seconds * bucket_size_in_seconds);
+                let bucket_end = (bucket_start + Duration::seconds(bucket_size_in_seconds)).min(*session_close);
+                Some((bucket_start, bucket_end))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_minute_candle(date: &str, open: f32, high: f32, low: f32, close: f32) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), close, high, low, open, 100, TimeFrame::OneMinute)
+    }
+
+    fn session_aggregator() -> CandleAggregator {
+        CandleAggregator::new(vec![TimeFrame::ThreeMinutes, TimeFrame::FiveMinutes, TimeFrame::FifteenMinutes])
+    }
+
+    #[test]
+    fn buckets_are_aligned_to_the_session_open() {
+        let mut candle_aggregator = session_aggregator();
+        let mut completed_candles = Vec::new();
+        for minute in 15..30 {
+            let date = format!("2023-08-10 09:{}:00+0530", minute);
+            completed_candles.extend(candle_aggregator.add_one_minute_candle(&one_minute_candle(date.as_str(), 100.0, 101.0, 99.0, 100.0)));
+        }
+        let bar_starts: Vec<(TimeFrame, String)> = completed_candles
+            .iter()
+            .map(|candle| (candle.market_time_frame.clone(), candle.date.clone()))
+            .collect();
+        assert_eq!(
+            bar_starts,
+            vec![
+                (TimeFrame::ThreeMinutes, "2023-08-10 09:15:00+0530".to_string()),
+                (TimeFrame::FiveMinutes, "2023-08-10 09:15:00+0530".to_string()),
+                (TimeFrame::ThreeMinutes, "2023-08-10 09:18:00+0530".to_string()),
+                (TimeFrame::ThreeMinutes, "2023-08-10 09:21:00+0530".to_string()),
+                (TimeFrame::FiveMinutes, "2023-08-10 09:20:00+0530".to_string()),
+                (TimeFrame::ThreeMinutes, "2023-08-10 09:24:00+0530".to_string()),
+                (TimeFrame::ThreeMinutes, "2023-08-10 09:27:00+0530".to_string()),
+                (TimeFrame::FiveMinutes, "2023-08-10 09:25:00+0530".to_string()),
+                (TimeFrame::FifteenMinutes, "2023-08-10 09:15:00+0530".to_string()),
+            ]
+        );
+        assert!(completed_candles.iter().all(|candle| candle.volume == time_frame_in_seconds(&candle.market_time_frame) as i32 / 60 * 100));
+    }
+
+    #[test]
+    fn bar_takes_the_first_open_last_close_and_the_extremes() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::FiveMinutes]);
+        let one_minute_candles = [
+            one_minute_candle("2023-08-10 09:15:00+0530", 100.0, 101.0, 99.5, 100.5),
+            one_minute_candle("2023-08-10 09:16:00+0530", 100.5, 103.0, 100.0, 102.0),
+            one_minute_candle("2023-08-10 09:17:00+0530", 102.0, 102.5, 98.0, 99.0),
+            one_minute_candle("2023-08-10 09:18:00+0530", 99.0, 100.0, 98.5, 99.5),
+        ];
+        for one_minute_candle in one_minute_candles.iter() {
+            assert!(candle_aggregator.add_one_minute_candle(one_minute_candle).is_empty());
+        }
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:19:00+0530", 99.5, 101.0, 99.0, 100.8));
+        assert_eq!(completed_candles.len(), 1);
+        let five_minute_candle = &completed_candles[0];
+        assert_eq!(
+            (five_minute_candle.open, five_minute_candle.high, five_minute_candle.low, five_minute_candle.close),
+            (100.0, 103.0, 98.0, 100.8)
+        );
+        assert_eq!(five_minute_candle.volume, 500);
+    }
+
+    #[test]
+    fn last_bucket_of_the_session_is_cut_at_the_close() {
+        //the 15:15 bar would run to 15:30 but the session closes at 15:20 => it is emitted partial by the 15:19 candle
+        let mut candle_aggregator = CandleAggregator::new_with_session(
+            vec![TimeFrame::FifteenMinutes],
+            NaiveTime::from_hms_opt(9, 15, 0).unwrap(),
+            NaiveTime::from_hms_opt(15, 20, 0).unwrap(),
+        );
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:19:00+0530", 100.5, 102.0, 100.0, 101.5));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].date, "2023-08-10 15:15:00+0530");
+        assert_eq!(completed_candles[0].volume, 200);
+
+        //nothing after the close is aggregated
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:20:00+0530", 101.5, 102.0, 101.0, 101.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:14:00+0530", 101.5, 102.0, 101.0, 101.5)).is_empty());
+    }
+
+    #[test]
+    fn whole_day_closes_on_the_15_29_candle() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::FifteenMinutes, TimeFrame::OneDay]);
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:28:00+0530", 100.5, 102.0, 100.0, 101.0)).is_empty());
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 15:29:00+0530", 101.0, 101.5, 100.5, 101.2));
+        let bar_starts: Vec<(TimeFrame, String)> = completed_candles
+            .iter()
+            .map(|candle| (candle.market_time_frame.clone(), candle.date.clone()))
+            .collect();
+        assert_eq!(
+            bar_starts,
+            vec![
+                (TimeFrame::FifteenMinutes, "2023-08-10 15:15:00+0530".to_string()),
+                (TimeFrame::OneDay, "2023-08-10 09:15:00+0530".to_string()),
+            ]
+        );
+        assert_eq!(completed_candles[1].close, 101.2);
+        assert_eq!(completed_candles[1].volume, 300);
+    }
+
+    #[test]
+    fn gap_emits_the_open_bar_when_a_later_bucket_starts() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::FiveMinutes]);
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:16:00+0530", 100.5, 101.5, 100.0, 101.0)).is_empty());
+
+        //09:17 - 09:26 never arrived
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:27:00+0530", 102.0, 102.5, 101.5, 102.0));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].date, "2023-08-10 09:15:00+0530");
+        assert_eq!(completed_candles[0].close, 101.0);
+        assert_eq!(completed_candles[0].volume, 200);
+
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:29:00+0530", 102.0, 103.0, 101.0, 102.5));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].date, "2023-08-10 09:25:00+0530");
+        assert_eq!(completed_candles[0].open, 102.0);
+    }
+
+    #[test]
+    fn late_and_repeated_candles_are_ignored() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::FiveMinutes]);
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:17:00+0530", 100.5, 101.5, 100.0, 101.0)).is_empty());
+        //arrives after 09:17 => would overwrite the close with an older price
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:16:00+0530", 90.0, 90.0, 80.0, 85.0)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:17:00+0530", 90.0, 90.0, 80.0, 85.0)).is_empty());
+
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:19:00+0530", 101.0, 101.5, 100.5, 101.2));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!((completed_candles[0].low, completed_candles[0].close), (99.0, 101.2));
+        assert_eq!(completed_candles[0].volume, 300);
+
+        //a candle of the bar already emitted does not reopen it
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:18:00+0530", 100.0, 100.0, 100.0, 100.0)).is_empty());
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:24:00+0530", 101.0, 101.5, 100.5, 101.0));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].date, "2023-08-10 09:20:00+0530");
+    }
+
+    #[test]
+    fn symbols_are_aggregated_separately() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::ThreeMinutes]);
+        let mut other_symbol_candle = one_minute_candle("2023-08-10 09:15:00+0530", 500.0, 501.0, 499.0, 500.0);
+        other_symbol_candle.symbol = "TCS".to_string();
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&other_symbol_candle).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:16:00+0530", 100.5, 101.0, 100.0, 100.8)).is_empty());
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-10 09:17:00+0530", 100.8, 101.0, 100.0, 100.9));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].symbol, "ADANIGREEN");
+        assert_eq!(completed_candles[0].high, 101.0);
+    }
+
+    #[test]
+    fn week_runs_from_the_monday_open_to_the_friday_close() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::OneWeek]);
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-07 09:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-09 12:00:00+0530", 100.5, 104.0, 98.0, 103.0)).is_empty());
+
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-11 15:29:00+0530", 103.0, 103.5, 102.0, 102.5));
+        assert_eq!(completed_candles.len(), 1);
+        let weekly_candle = &completed_candles[0];
+        assert_eq!(weekly_candle.date, "2023-08-07 09:15:00+0530");
+        assert_eq!(weekly_candle.market_time_frame, TimeFrame::OneWeek);
+        assert_eq!((weekly_candle.open, weekly_candle.high, weekly_candle.low, weekly_candle.close), (100.0, 104.0, 98.0, 102.5));
+        assert_eq!(weekly_candle.volume, 300);
+    }
+
+    #[test]
+    fn week_closed_by_a_friday_holiday_is_emitted_on_the_next_week() {
+        let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::OneWeek]);
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-14 09:15:00+0530", 100.0, 101.0, 99.0, 100.5)).is_empty());
+        //Friday 18 Aug is a holiday => the Thursday close is not the end of the bucket
+        assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-17 15:29:00+0530", 100.5, 102.0, 100.0, 101.0)).is_empty());
+
+        let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle("2023-08-21 09:15:00+0530", 101.0, 101.5, 100.5, 101.0));
+        assert_eq!(completed_candles.len(), 1);
+        assert_eq!(completed_candles[0].date, "2023-08-14 09:15:00+0530");
+        assert_eq!(completed_candles[0].close, 101.0);
+    }
+
+    #[test]
+    fn month_closes_on_its_last_weekday() {
+        //31 Aug 2023 is a Thursday, 30 Sep 2023 a Saturday
+        let cases = vec![
+            ("2023-08-01 09:15:00+0530", "2023-08-31 15:29:00+0530", "2023-08-01 09:15:00+0530"),
+            ("2023-09-01 09:15:00+0530", "2023-09-29 15:29:00+0530", "2023-09-01 09:15:00+0530"),
+            ("2023-12-01 09:15:00+0530", "2023-12-29 15:29:00+0530", "2023-12-01 09:15:00+0530"),
+        ];
+        for (first_date, last_date, expected_date) in cases {
+            let mut candle_aggregator = CandleAggregator::new(vec![TimeFrame::OneMonth]);
+            assert!(candle_aggregator.add_one_minute_candle(&one_minute_candle(first_date, 100.0, 101.0, 99.0, 100.5)).is_empty());
+            let completed_candles = candle_aggregator.add_one_minute_candle(&one_minute_candle(last_date, 100.5, 102.0, 100.0, 101.5));
+            assert_eq!(completed_candles.len(), 1, "{}", last_date);
+            assert_eq!(completed_candles[0].date, expected_date);
+            assert_eq!(completed_candles[0].market_time_frame, TimeFrame::OneMonth);
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use std::sync::Mutex;
 
 use crate::common::{enums::{TimeFrame, MarketTrend}, raw_stock::{RawStock, RawStockLedger}, date_parser, redis_client::RedisClient, utils::current_market_state_cache_key_formatter};
-use mongodb::{Collection, Database, options::{UpdateOptions, FindOneOptions}, bson::{doc, Document}};
+use mongodb::{Collection, options::{UpdateOptions, FindOneOptions}, bson::{doc, to_bson, Bson, Document}};
 use serde::{Deserialize, Serialize};
+
+use super::{indicators::IndicatorValues, market_state_history::MarketStateHistory};
+//Rolling state of one symbol on one time frame, saved per trade date under CMS_<date>_<symbol>_<timeframe>.
+//Prices that were not seen yet (first candle ever, first day ever) are 0.0
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CurrentMarketState {
 
@@ -10,6 +14,8 @@ pub struct CurrentMarketState {
     pub previous_candle_market_trend: MarketTrend,
     pub current_candle_market_trend: MarketTrend,
     pub current_sma: f32,  // Simple Moving Average
+    #[serde(default)]
+    pub indicators: IndicatorValues,
 
     pub previous_candle_open: f32,
     pub previous_candle_high: f32,
@@ -22,6 +28,30 @@ pub struct CurrentMarketState {
     pub current_candle_low: f32,
     pub current_candle_close: f32,
     pub current_candle_volume: i32,
+    #[serde(default)]
+    pub current_candle_at: String, //date of the candle the state was rolled on
+
+    //trade date of the current candle, up to and including it
+    #[serde(default)]
+    pub day_open: f32,
+    #[serde(default)]
+    pub day_high: f32,
+    #[serde(default)]
+    pub day_low: f32,
+    #[serde(default)]
+    pub day_close: f32,
+    #[serde(default)]
+    pub day_volume: i64,
+
+    //last trade date before the current one with a candle on this time frame
+    #[serde(default)]
+    pub previous_day_open: f32,
+    #[serde(default)]
+    pub previous_day_high: f32,
+    #[serde(default)]
+    pub previous_day_low: f32,
+    #[serde(default)]
+    pub previous_day_close: f32,
 
     pub last_consecutive_green_candle_count: i32,
     pub last_consecutive_red_candle_count: i32,
@@ -92,6 +122,7 @@ impl CurrentMarketState {
         previous_candle_market_trend: MarketTrend,
         current_candle_market_trend: MarketTrend,
         current_sma: f32,
+        indicators: IndicatorValues,
         previous_candle_open: f32,
         previous_candle_high: f32,
         previous_candle_low: f32,
@@ -102,6 +133,16 @@ impl CurrentMarketState {
         current_candle_low: f32,
         current_candle_close: f32,
         current_candle_volume: i32,
+        current_candle_at: String,
+        day_open: f32,
+        day_high: f32,
+        day_low: f32,
+        day_close: f32,
+        day_volume: i64,
+        previous_day_open: f32,
+        previous_day_high: f32,
+        previous_day_low: f32,
+        previous_day_close: f32,
         last_consecutive_green_candle_count: i32,
         last_consecutive_red_candle_count: i32,
         symbol: String,
@@ -114,6 +155,7 @@ impl CurrentMarketState {
             previous_candle_market_trend,
             current_candle_market_trend,
             current_sma,
+            indicators,
             previous_candle_open,
             previous_candle_high,
             previous_candle_low,
@@ -124,6 +166,16 @@ impl CurrentMarketState {
             current_candle_low,
             current_candle_close,
             current_candle_volume,
+            current_candle_at,
+            day_open,
+            day_high,
+            day_low,
+            day_close,
+            day_volume,
+            previous_day_open,
+            previous_day_high,
+            previous_day_low,
+            previous_day_close,
             last_consecutive_green_candle_count,
             last_consecutive_red_candle_count,
             symbol,
@@ -139,6 +191,7 @@ impl CurrentMarketState {
             previous_candle_market_trend: current_state.previous_candle_market_trend,
             current_candle_market_trend: current_state.current_candle_market_trend,
             current_sma: current_state.current_sma,
+            indicators: current_state.indicators,
             previous_candle_open: current_state.previous_candle_open,
             previous_candle_high: current_state.previous_candle_high,
             previous_candle_low: current_state.previous_candle_low,
@@ -149,6 +202,16 @@ impl CurrentMarketState {
             current_candle_low: current_state.current_candle_low,
             current_candle_close: current_state.current_candle_close,
             current_candle_volume: current_state.current_candle_volume,
+            current_candle_at: current_state.current_candle_at,
+            day_open: current_state.day_open,
+            day_high: current_state.day_high,
+            day_low: current_state.day_low,
+            day_close: current_state.day_close,
+            day_volume: current_state.day_volume,
+            previous_day_open: current_state.previous_day_open,
+            previous_day_high: current_state.previous_day_high,
+            previous_day_low: current_state.previous_day_low,
+            previous_day_close: current_state.previous_day_close,
             last_consecutive_green_candle_count: current_state.last_consecutive_green_candle_count,
             last_consecutive_red_candle_count: current_state.last_consecutive_red_candle_count,
             symbol: current_state.symbol,
@@ -164,6 +227,7 @@ impl CurrentMarketState {
             "previous_candle_market_trend": self.previous_candle_market_trend.to_string(),
             "current_candle_market_trend": self.current_candle_market_trend.to_string(),
             "current_sma": self.current_sma,
+            "indicators": to_bson(&self.indicators).unwrap_or(Bson::Null),
             "previous_candle_open": self.previous_candle_open,
             "previous_candle_high": self.previous_candle_high,
             "previous_candle_low": self.previous_candle_low,
@@ -174,6 +238,16 @@ impl CurrentMarketState {
             "current_candle_low": self.current_candle_low,
             "current_candle_close": self.current_candle_close,
             "current_candle_volume": self.current_candle_volume,
+            "current_candle_at": self.current_candle_at.to_string(),
+            "day_open": self.day_open,
+            "day_high": self.day_high,
+            "day_low": self.day_low,
+            "day_close": self.day_close,
+            "day_volume": self.day_volume,
+            "previous_day_open": self.previous_day_open,
+            "previous_day_high": self.previous_day_high,
+            "previous_day_low": self.previous_day_low,
+            "previous_day_close": self.previous_day_close,
             "last_consecutive_green_candle_count": self.last_consecutive_green_candle_count,
             "last_consecutive_red_candle_count": self.last_consecutive_red_candle_count,
             "symbol": self.symbol.to_string(),
@@ -183,294 +257,276 @@ impl CurrentMarketState {
         }
     }
 
-    pub async fn calculate_market_state(stock: &RawStock, time_frame: TimeFrame, current_market_state_collection: &Collection<CurrentMarketState>, redis_client: &Mutex<RedisClient>, raw_stock_ledger: &RawStockLedger, database_instance: Database) {
+    //Rolls the state of the candle's symbol and time frame forward by one candle, saves it to Redis and Mongo and appends it to the history.
+    //A candle that is not newer than the stored state (replayed or backfilled twice) leaves the stored state as it is.
+    pub async fn calculate_market_state(stock: &RawStock, time_frame: TimeFrame, current_market_state_collection: &Collection<CurrentMarketState>, redis_client: &Mutex<RedisClient>, raw_stock_ledger: &RawStockLedger, indicator_values: IndicatorValues, market_state_history: &MarketStateHistory) -> Option<CurrentMarketState> {
+        if time_frame == TimeFrame::Infinity {
+            return None;
+        }
 
         let trade_date_only = date_parser::return_only_date_from_datetime(stock.date.as_str());
         let current_market_state_cache_key = current_market_state_cache_key_formatter(trade_date_only.as_str(), stock.symbol.as_str(), &stock.market_time_frame);
-        let filter = doc! {"cache_key": current_market_state_cache_key.clone() };
-        let options = FindOneOptions::builder().build();
-        let previous_market_state = match current_market_state_collection.find_one(filter, options).await {
-            Ok(Some(data)) => {
-                // println!("Data fetched from current_market_stats for key => {}", current_market_state_cache_key);
-                Some(data)
-            },
-            Ok(None) => None,
-            Err(e) => {
-                println!("Error while fetching the data from MongoDB => {:?}", e);
-                None
+        let previous_market_state = Self::fetch_previous_market_state(stock, current_market_state_cache_key.as_str(), current_market_state_collection, redis_client).await;
+
+        if let Some(previous_market_state) = previous_market_state.as_ref() {
+            if !previous_market_state.current_candle_at.is_empty()
+                && date_parser::date_time_in_seconds(previous_market_state.current_candle_at.as_str()) >= date_parser::date_time_in_seconds(stock.date.as_str())
+            {
+                return Some(previous_market_state.clone());
             }
+        }
+
+        //the ledger already holds the current candle, the one before it is the previous candle
+        let stock_date_in_seconds = date_parser::date_time_in_seconds(stock.date.as_str());
+        let previous_stock = raw_stock_ledger
+            .get_raw_stocks(stock.symbol.as_str())
+            .iter()
+            .rev()
+            .find(|raw_stock| date_parser::date_time_in_seconds(raw_stock.date.as_str()) < stock_date_in_seconds);
+        let current_market_state = Self::roll_market_state(
+            stock,
+            indicator_values,
+            previous_stock,
+            previous_market_state.as_ref(),
+            current_market_state_cache_key.clone(),
+        );
+
+        if let Err(e) = redis_client.lock().unwrap().set_data(current_market_state_cache_key.as_str(), serde_json::to_string(&current_market_state).unwrap().as_str()) {
+            println!("Error while setting the data in Redis => {:?}", e);
+        }
+        let filter = doc! {"cache_key": current_market_state_cache_key };
+        let options = UpdateOptions::builder().upsert(true).build();
+        let document = doc!{"$set":current_market_state.to_document()};
+        if let Err(e) = current_market_state_collection.update_one(filter, document, options).await {
+            println!("Error while updating a current_market_state into the collection: {:?} error {:?}", current_market_state, e);
+        }
+        market_state_history.record_snapshot(&current_market_state).await;
+
+        Some(current_market_state)
+    }
+
+    //same trade date => Redis, then Mongo. First candle of the day => the latest state of an earlier candle, so the previous day carries over
+    async fn fetch_previous_market_state(stock: &RawStock, current_market_state_cache_key: &str, current_market_state_collection: &Collection<CurrentMarketState>, redis_client: &Mutex<RedisClient>) -> Option<CurrentMarketState> {
+        let cached_market_state = match redis_client.lock().unwrap().get_data(current_market_state_cache_key) {
+            Ok(data) => serde_json::from_str::<CurrentMarketState>(data.as_str()).ok(),
+            Err(_) => None,
         };
+        if cached_market_state.is_some() {
+            return cached_market_state;
+        }
 
-         let current_market_state = match time_frame {
-            TimeFrame::OneMinute => {
-                None
-                // Self::calculate_market_state_for_oneminute(stock)
-            },
-            TimeFrame::ThreeMinutes => {
-                None
-                //  Self::calculate_market_state_for_threeminutes(stock)
-            },
-            TimeFrame::FiveMinutes => {
-                Self::calculate_market_state_for_fiveminutes(stock, redis_client, raw_stock_ledger, current_market_state_cache_key, previous_market_state) 
-            },
-            TimeFrame::FifteenMinutes => {
-                None
-                // Self::calculate_market_state_for_fifteenminutes(stock)
-            },
-            TimeFrame::OneDay => {
-                None
-                // Self::calculate_market_state_for_oneday(stock)
-            },
-            TimeFrame::OneWeek => {
-                None
-                // Self::calculate_market_state_for_oneweek(stock)
-            },
-            TimeFrame::OneMonth => {
-                None
-                // Self::calculate_market_state_for_onemonth(stock)
-            },
-            TimeFrame::OneYear => {
-                None
-                // Self::calculate_market_state_for_oneyear(stock)
-            },
-            _ => {
+        let filter = doc! {"cache_key": current_market_state_cache_key };
+        match current_market_state_collection.find_one(filter, FindOneOptions::builder().build()).await {
+            Ok(Some(data)) => return Some(data),
+            Ok(None) => (),
+            Err(e) => println!("Error while fetching the data from MongoDB => {:?}", e),
+        }
+
+        let filter = doc! {
+            "symbol": stock.symbol.clone(),
+            "market_time_frame": stock.market_time_frame.to_string(),
+            "current_candle_at": {"$lt": stock.date.clone()},
+        };
+        let options = FindOneOptions::builder().sort(doc! {"current_candle_at": -1}).build();
+        match current_market_state_collection.find_one(filter, options).await {
+            Ok(data) => data,
+            Err(e) => {
+                println!("Error while fetching the latest market state from MongoDB => {:?}", e);
                 None
             }
+        }
+    }
+
+    //previous_stock => the candle before this one on the same time frame, falls back to the candle of the previous state
+    pub fn roll_market_state(stock: &RawStock, indicator_values: IndicatorValues, previous_stock: Option<&RawStock>, previous_market_state: Option<&CurrentMarketState>, cache_key: String) -> CurrentMarketState {
+        let trade_date = date_parser::return_only_date_from_datetime(stock.date.as_str());
+        let (previous_candle_open, previous_candle_high, previous_candle_low, previous_candle_close, previous_candle_volume) = match (previous_stock, previous_market_state) {
+            (Some(previous_stock), _) => (previous_stock.open, previous_stock.high, previous_stock.low, previous_stock.close, previous_stock.volume),
+            (None, Some(previous_market_state)) => (
+                previous_market_state.current_candle_open,
+                previous_market_state.current_candle_high,
+                previous_market_state.current_candle_low,
+                previous_market_state.current_candle_close,
+                previous_market_state.current_candle_volume,
+            ),
+            (None, None) => (0.0, 0.0, 0.0, 0.0, 0),
         };
 
-        match current_market_state {
-            Some(current_market_state) => {
-                let filter = doc! {"cache_key": current_market_state.cache_key.clone() };
-                let options = UpdateOptions::builder().upsert(true).build();
-                let document = doc!{"$set":current_market_state.to_document()};
-                match current_market_state_collection.update_one(filter,  document, options).await {
-                    Ok(_) => {
-                        // println!("Successfully inserted a current_market_state into the collection");
-                    },
-                    Err(e) => {
-                        println!("Error while updating a current_market_state into the collection: {:?} error {:?}", current_market_state,e);
-                    }
-                }
-            },
-            None => {
-                println!("No market state found for the stock: {}", stock.symbol);
-            }
+        let same_day_market_state = previous_market_state.filter(|previous_market_state| previous_market_state.trade_date == trade_date);
+        let (day_open, day_high, day_low, day_volume) = match same_day_market_state {
+            Some(previous_market_state) => (
+                previous_market_state.day_open,
+                previous_market_state.day_high.max(stock.high),
+                previous_market_state.day_low.min(stock.low),
+                previous_market_state.day_volume + stock.volume as i64,
+            ),
+            None => (stock.open, stock.high, stock.low, stock.volume as i64),
+        };
+        let (previous_day_open, previous_day_high, previous_day_low, previous_day_close) = match (same_day_market_state, previous_market_state) {
+            (Some(previous_market_state), _) => (
+                previous_market_state.previous_day_open,
+                previous_market_state.previous_day_high,
+                previous_market_state.previous_day_low,
+                previous_market_state.previous_day_close,
+            ),
+            //the state before the first candle of the day is the last one of the previous day
+            (None, Some(previous_market_state)) => (
+                previous_market_state.day_open,
+                previous_market_state.day_high,
+                previous_market_state.day_low,
+                previous_market_state.day_close,
+            ),
+            (None, None) => (0.0, 0.0, 0.0, 0.0),
+        };
+
+        let previous_green_candle_count = previous_market_state.map_or(0, |previous_market_state| previous_market_state.last_consecutive_green_candle_count);
+        let previous_red_candle_count = previous_market_state.map_or(0, |previous_market_state| previous_market_state.last_consecutive_red_candle_count);
+
+        CurrentMarketState {
+            market_time_frame: stock.market_time_frame.clone(),
+            previous_candle_market_trend: previous_market_state.map_or(MarketTrend::Sideways, |previous_market_state| previous_market_state.current_candle_market_trend.clone()),
+            current_candle_market_trend: Self::identify_market_trend(stock.close, indicator_values.sma),
+            current_sma: indicator_values.sma.unwrap_or_default(),
+            indicators: indicator_values,
+            previous_candle_open,
+            previous_candle_high,
+            previous_candle_low,
+            previous_candle_close,
+            previous_candle_volume,
+            current_candle_open: stock.open,
+            current_candle_high: stock.high,
+            current_candle_low: stock.low,
+            current_candle_close: stock.close,
+            current_candle_volume: stock.volume,
+            current_candle_at: stock.date.clone(),
+            day_open,
+            day_high,
+            day_low,
+            day_close: stock.close,
+            day_volume,
+            previous_day_open,
+            previous_day_high,
+            previous_day_low,
+            previous_day_close,
+            last_consecutive_green_candle_count: if stock.close > stock.open { previous_green_candle_count + 1 } else { 0 },
+            last_consecutive_red_candle_count: if stock.close < stock.open { previous_red_candle_count + 1 } else { 0 },
+            symbol: stock.symbol.to_owned(),
+            trade_date,
+            last_updated_at: date_parser::new_current_date_time_in_desired_stock_datetime_format(),
+            cache_key,
         }
     }
 
-    fn calculate_market_state_for_oneminute(stock: &RawStock) -> Option<CurrentMarketState>{
-        Some(CurrentMarketState::new (
-            TimeFrame::OneMinute,
-            MarketTrend::Bearish,
-            MarketTrend::Bearish,
-            0.0,
-            100.0,
-            200.0,
-            50.0,
-            150.0,
-            2000,
-            200.0,
-            300.0,
-            150.0,
-            250.0,
-            3000,
-            2,
-            2,
-            "ADANIGREEN".to_owned(),
-            date_parser::new_current_date_time_in_desired_stock_datetime_format(),
-            date_parser::new_current_date_time_in_desired_stock_datetime_format(),
-            "cache_key".to_owned() //TODO: generate cache key
-        ))
-    }
-    fn calculate_market_state_for_threeminutes(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+    //close above the SMA => bullish, below => bearish, no SMA yet => sideways
+    pub fn identify_market_trend(close: f32, sma: Option<f32>) -> MarketTrend {
+        match sma {
+            Some(sma) if close > sma => MarketTrend::Bullish,
+            Some(sma) if close < sma => MarketTrend::Bearish,
+            _ => MarketTrend::Sideways,
+        }
     }
-    fn calculate_market_state_for_fiveminutes(stock: &RawStock, redis_client: &Mutex<RedisClient>, raw_stock_ledger: &RawStockLedger, current_market_state_cache_key: String, previous_market_state_db: Option<CurrentMarketState>)-> Option<CurrentMarketState>{
-        
-        
-
-        let previous_market_state =  match previous_market_state_db {
-            Some(previous_market_state) => Some(previous_market_state),
-            None => {
-                match redis_client.lock().unwrap().get_data(current_market_state_cache_key.as_str()) {
-                    Ok(data) => {
-                        // println!("Data fetched from Redis for key => {}", current_market_state_cache_key);
-                        let deserialised_data = serde_json::from_str::<CurrentMarketState>(data.as_str()).unwrap();
-                        Some(deserialised_data)
-                    }
-                    Err(e) => {
-                        println!("Error while fetching the data from Redis => {:?}", e);
-                        //fetch from the mongodb
-                        None
-                    }
-                }
-            }
-        };
+}
 
-        //TODO: find the logic for calculating the current day market trend based on SMA and EMA or other indicators
-        let sma_window_size = 9; //TimeFrame::FiveMinutes as i32;
-        let (current_sma, current_candle_market_trend) = Self::identify_market_trend_SMA(&raw_stock_ledger.raw_stocks, stock, sma_window_size);
-
-        let updated_market_state = match previous_market_state {
-            Some(previous_market_state) => {
-                let mut update_required = false;
-                let new_stock_low = if stock.low < previous_market_state.current_candle_low {
-                    update_required = true;
-                    stock.low
-                } else {
-                    previous_market_state.current_candle_low
-                };
-                let new_stock_high = if stock.high > previous_market_state.current_candle_high {
-                    update_required = true;
-                    stock.high
-                } else {
-                    previous_market_state.current_candle_high
-                };
-
-                let new_stock_close = if stock.close < previous_market_state.current_candle_close {
-                    update_required = true;
-                    stock.close
-                } else {
-                    previous_market_state.current_candle_close
-                };
-
-                let new_stock_volume = previous_market_state.current_candle_volume + stock.volume;
-                
-                let last_consecutive_green_candle_count = if stock.close > stock.open {
-                    update_required = true;
-                    previous_market_state.last_consecutive_green_candle_count + 1
-                } else {
-                    0
-                };
-
-                let last_consecutive_red_candle_count = if stock.close < stock.open {
-                    update_required = true;
-                    previous_market_state.last_consecutive_red_candle_count + 1
-                } else {
-                    0
-                };
-
-                
-                // println!("last_consecutive_green_candle_count => {}", last_consecutive_green_candle_count);
-                // println!("last_consecutive_red_candle_count => {}", last_consecutive_red_candle_count);
-                // println!("current_candle_market_trend => {:?}", current_candle_market_trend);
-                // println!("current_SMA => {}", current_sma);
-                // println!("current_candle_close => {}", stock.close.clone());
-                // println!("update_required => {}", update_required);
-                // println!("previous_market_state => {:?}", previous_market_state);
-
-                if update_required {
-                    let current_market_state = CurrentMarketState::new(
-                        stock.market_time_frame.clone(),
-                        MarketTrend::Bearish,
-                        current_candle_market_trend,
-                        current_sma,
-                        previous_market_state.current_candle_open,
-                        previous_market_state.current_candle_high,
-                        previous_market_state.current_candle_low,
-                        previous_market_state.current_candle_close,
-                        previous_market_state.current_candle_volume,
-                        previous_market_state.current_candle_open,
-                        new_stock_high,
-                        new_stock_low,
-                        new_stock_close,
-                        new_stock_volume,
-                        last_consecutive_green_candle_count,
-                        last_consecutive_red_candle_count,
-                        stock.symbol.to_owned(),
-                        previous_market_state.trade_date,
-                        date_parser::new_current_date_time_in_desired_stock_datetime_format(),
-                        current_market_state_cache_key.clone(),
-                    );
-                    Some(current_market_state)
-                }else{
-                    None
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            }
-            None => {
-                Some(CurrentMarketState::new(
-                    stock.market_time_frame.clone(),
-                    MarketTrend::Bearish, //TODO:calculate it => adding dummy data now
-                    current_candle_market_trend,
-                    current_sma,
-                    100.0,
-                    200.0,
-                    50.0,
-                    150.0,
-                    2000,
-                    stock.open,
-                    stock.high,
-                    stock.low,
-                    stock.close,
-                    stock.volume,
-                    if stock.close > stock.open {1} else {0}, //TODO:calculate it => adding dummy data now
-                    if stock.open > stock.close {1} else {0}, //TODO: calculate it => adding dummy data now
-                    stock.symbol.to_owned(),
-                    date_parser::new_current_date_time_in_desired_stock_datetime_format(),
-                    date_parser::new_current_date_time_in_desired_stock_datetime_format(),
-                    current_market_state_cache_key.clone()
-                ))
-            }
-        };
+    fn test_candle(date: &str, open: f32, high: f32, low: f32, close: f32, volume: i32) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), close, high, low, open, volume, TimeFrame::FiveMinutes)
+    }
 
-        match updated_market_state {
-            Some(updated_market_state) => {
-                // println!("Updated market state => {:?}", updated_market_state);
-                match redis_client.lock().unwrap().set_data(current_market_state_cache_key.as_str(), serde_json::to_string(&updated_market_state.clone()).unwrap().as_str()) {
-                    Ok(_) => {
-                        // println!("Data set in Redis for key => {}", current_market_state_cache_key);
-                        Some(updated_market_state)
-                    }
-                    Err(e) => {
-                        println!("Error while setting the data in Redis => {:?}", e);
-                        None
-                    }
-                }
-            }
-            None => None
-            
+    fn with_sma(sma: Option<f32>) -> IndicatorValues {
+        IndicatorValues {
+            sma,
+            ..IndicatorValues::default()
         }
     }
-    fn calculate_market_state_for_fifteenminutes(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+
+    fn roll(stock: &RawStock, sma: Option<f32>, previous_stock: Option<&RawStock>, previous_market_state: Option<&CurrentMarketState>) -> CurrentMarketState {
+        CurrentMarketState::roll_market_state(stock, with_sma(sma), previous_stock, previous_market_state, "CMS_TEST".to_string())
     }
-    fn calculate_market_state_for_oneday(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+
+    #[test]
+    fn trend_compares_the_close_with_the_sma() {
+        assert_eq!(CurrentMarketState::identify_market_trend(101.0, Some(100.0)), MarketTrend::Bullish);
+        assert_eq!(CurrentMarketState::identify_market_trend(99.0, Some(100.0)), MarketTrend::Bearish);
+        assert_eq!(CurrentMarketState::identify_market_trend(100.0, Some(100.0)), MarketTrend::Sideways);
+        assert_eq!(CurrentMarketState::identify_market_trend(100.0, None), MarketTrend::Sideways);
     }
-    fn calculate_market_state_for_oneweek(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+
+    #[test]
+    fn first_candle_ever_starts_from_zero() {
+        let market_state = roll(&test_candle("2023-08-10 09:15:00+0530", 100.0, 102.0, 99.0, 101.0, 1000), Some(100.5), None, None);
+        assert_eq!(market_state.previous_candle_market_trend, MarketTrend::Sideways);
+        assert_eq!(market_state.current_candle_market_trend, MarketTrend::Bullish);
+        assert_eq!(market_state.current_sma, 100.5);
+        assert_eq!(market_state.previous_candle_close, 0.0);
+        assert_eq!((market_state.day_open, market_state.day_high, market_state.day_low, market_state.day_close), (100.0, 102.0, 99.0, 101.0));
+        assert_eq!(market_state.day_volume, 1000);
+        assert_eq!(market_state.previous_day_close, 0.0);
+        assert_eq!((market_state.last_consecutive_green_candle_count, market_state.last_consecutive_red_candle_count), (1, 0));
+        assert_eq!(market_state.trade_date, "2023_08_10");
+        assert_eq!(market_state.cache_key, "CMS_TEST");
     }
-    fn calculate_market_state_for_onemonth(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+
+    #[test]
+    fn same_day_candle_rolls_the_candle_and_extends_the_day() {
+        let first_state = roll(&test_candle("2023-08-10 09:15:00+0530", 100.0, 102.0, 99.0, 101.0, 1000), Some(100.5), None, None);
+        let second_candle = test_candle("2023-08-10 09:20:00+0530", 101.0, 103.0, 98.0, 98.5, 500);
+        let second_state = roll(&second_candle, Some(100.0), None, Some(&first_state));
+
+        //the previous candle comes from the previous state when the ledger has none
+        assert_eq!(
+            (second_state.previous_candle_open, second_state.previous_candle_high, second_state.previous_candle_low, second_state.previous_candle_close, second_state.previous_candle_volume),
+            (100.0, 102.0, 99.0, 101.0, 1000)
+        );
+        assert_eq!(second_state.previous_candle_market_trend, MarketTrend::Bullish);
+        assert_eq!(second_state.current_candle_market_trend, MarketTrend::Bearish);
+        assert_eq!((second_state.day_open, second_state.day_high, second_state.day_low, second_state.day_close), (100.0, 103.0, 98.0, 98.5));
+        assert_eq!(second_state.day_volume, 1500);
+        //the red candle ends the green streak
+        assert_eq!((second_state.last_consecutive_green_candle_count, second_state.last_consecutive_red_candle_count), (0, 1));
+
+        let third_state = roll(&test_candle("2023-08-10 09:25:00+0530", 98.5, 99.0, 97.0, 97.5, 200), Some(99.5), None, Some(&second_state));
+        assert_eq!((third_state.last_consecutive_green_candle_count, third_state.last_consecutive_red_candle_count), (0, 2));
+        //a doji ends both streaks
+        let fourth_state = roll(&test_candle("2023-08-10 09:30:00+0530", 97.5, 98.0, 97.0, 97.5, 200), None, None, Some(&third_state));
+        assert_eq!((fourth_state.last_consecutive_green_candle_count, fourth_state.last_consecutive_red_candle_count), (0, 0));
+        assert_eq!(fourth_state.previous_candle_market_trend, MarketTrend::Bearish);
+        assert_eq!(fourth_state.current_candle_market_trend, MarketTrend::Sideways);
     }
-    fn calculate_market_state_for_oneyear(stock: &RawStock)->Option<CurrentMarketState>{
-        None
+
+    #[test]
+    fn previous_stock_wins_over_the_previous_state() {
+        let first_state = roll(&test_candle("2023-08-10 09:15:00+0530", 100.0, 102.0, 99.0, 101.0, 1000), None, None, None);
+        let previous_stock = test_candle("2023-08-10 09:20:00+0530", 101.0, 101.5, 100.5, 101.2, 300);
+        let market_state = roll(&test_candle("2023-08-10 09:25:00+0530", 101.2, 101.8, 101.0, 101.6, 400), None, Some(&previous_stock), Some(&first_state));
+        assert_eq!(market_state.previous_candle_open, 101.0);
+        assert_eq!(market_state.previous_candle_close, 101.2);
+        assert_eq!(market_state.previous_candle_volume, 300);
     }
 
-    //simple moving average based market trend identification
-    fn identify_market_trend_SMA(raw_stocks: &Vec<RawStock>, stock: &RawStock, sma_window_size: usize)-> (f32, MarketTrend){
+    #[test]
+    fn first_candle_of_a_new_day_rolls_the_day_over() {
+        let first_state = roll(&test_candle("2023-08-10 09:15:00+0530", 100.0, 102.0, 99.0, 101.0, 1000), None, None, None);
+        let last_state = roll(&test_candle("2023-08-10 15:25:00+0530", 101.0, 104.0, 100.0, 103.0, 800), None, None, Some(&first_state));
 
-        if raw_stocks.len() <= sma_window_size {
-            (0.0, MarketTrend::Sideways) // Not enough data points for analysis
-        }else{
-            let mut prices: Vec<f32> = Vec::new();
-            for raw_stock in raw_stocks {
-                prices.push(raw_stock.close);
-            }
-            let sma = prices.windows(sma_window_size).map(|price| price.iter().sum::<f32>() / sma_window_size as f32);
-        
-            let current_close_price = stock.close;
-            let current_sma = sma.last().unwrap_or_default();
-        
-            if current_close_price > current_sma {
-                (current_sma, MarketTrend::Bullish)
-            } else if current_close_price < current_sma {
-                (current_sma, MarketTrend::Bearish)
-            } else {
-                (current_sma, MarketTrend::Sideways)
-            }
-        }
+        let next_day_state = roll(&test_candle("2023-08-11 09:15:00+0530", 103.5, 105.0, 103.0, 104.0, 600), None, None, Some(&last_state));
+        assert_eq!(next_day_state.trade_date, "2023_08_11");
+        assert_eq!(
+            (next_day_state.previous_day_open, next_day_state.previous_day_high, next_day_state.previous_day_low, next_day_state.previous_day_close),
+            (100.0, 104.0, 99.0, 103.0)
+        );
+        assert_eq!((next_day_state.day_open, next_day_state.day_high, next_day_state.day_low), (103.5, 105.0, 103.0));
+        assert_eq!(next_day_state.day_volume, 600);
+        //the previous candle and the streaks carry over the day boundary
+        assert_eq!(next_day_state.previous_candle_close, 103.0);
+        assert_eq!(next_day_state.last_consecutive_green_candle_count, 3);
+
+        //later candles of the new day keep the previous day
+        let later_state = roll(&test_candle("2023-08-11 09:20:00+0530", 104.0, 104.5, 102.5, 103.0, 400), None, None, Some(&next_day_state));
+        assert_eq!(later_state.previous_day_close, 103.0);
+        assert_eq!(later_state.previous_day_high, 104.0);
+        assert_eq!((later_state.day_open, later_state.day_high, later_state.day_low), (103.5, 105.0, 102.5));
     }
 }
-
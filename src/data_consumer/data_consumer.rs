@@ -0,0 +1,437 @@
+use std::{
+    error::Error,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpStream, sync::broadcast::error::RecvError};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use url::Url;
+
+use crate::{
+    algo_hub::strategy::StrategyRegistry,
+    common::{
+        enums::{RootSystemConfig, ThreadJobType, ThreadWorkerConfig, TimeFrame},
+        raw_stock::{RawStock, RawStockLedger},
+        redis_client::RedisClient,
+    },
+};
+
+use super::{
+    candle_aggregator::{AggregatedCandle, CandleAggregator},
+    data_processor,
+    tick_parser::{TickParseError, TickParser},
+};
+
+//read from the [ingestion.reconnect] table of ingestion.toml, every missing field keeps its default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub initial_backoff_in_millis: u64,
+    pub max_backoff_in_millis: u64,
+    pub max_retry_count: Option<u32>, //None => keep retrying for the whole trading day
+    pub ping_interval_in_seconds: u64,
+    pub heartbeat_timeout_in_seconds: u64, //no message at all (pong included) for this long => the connection is treated as dead
+    pub backfill_file_path: Option<String>, //local CSV stand-in for the broker's historical candle API
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> ReconnectConfig {
+        ReconnectConfig {
+            initial_backoff_in_millis: 500,
+            max_backoff_in_millis: 30_000,
+            max_retry_count: None,
+            ping_interval_in_seconds: 15,
+            heartbeat_timeout_in_seconds: 45,
+            backfill_file_path: None,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    //exponential backoff with equal jitter => half of the delay is fixed, the other half random
+    pub fn backoff_delay(&self, retry_count: u32) -> Duration {
+        let exponential_delay = self
+            .initial_backoff_in_millis
+            .saturating_mul(2u64.saturating_pow(retry_count))
+            .min(self.max_backoff_in_millis);
+        let jitter_range = exponential_delay / 2 + 1;
+        let jitter = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos() as u64)
+            .unwrap_or(0)
+            % jitter_range;
+        Duration::from_millis(exponential_delay / 2 + jitter)
+    }
+}
+
+pub async fn ingest_data_via_stream(
+    thread_worker_config: ThreadWorkerConfig,
+    redis_client: &Mutex<RedisClient>,
+) -> Result<(), Box<dyn Error>> {
+    if thread_worker_config.thread_job_type == ThreadJobType::TradeWatcherCron {
+        return Ok(());
+    }
+
+    let mut root_system_config = thread_worker_config.root_system_config.clone();
+    let server_url = root_system_config.server_url.clone();
+    let reconnect_config = thread_worker_config.reconnect_config.clone();
+
+    let mut raw_stock_ledger = RawStockLedger::new();
+    let mut tick_parser = TickParser::new(
+        thread_worker_config.time_frame.clone(),
+        thread_worker_config.stream_symbol.clone(),
+        root_system_config.tradeable_symbols.clone(),
+    );
+    let mut strategy_registry = StrategyRegistry::from_algo_types(
+        &root_system_config.tradeable_algo_types,
+        &root_system_config.strategy_configs,
+        &root_system_config.database_instance,
+        root_system_config.strategy_states.clone(),
+    );
+
+    //only the 1 min stream feeds the aggregator, it builds every higher time frame a strategy trades on
+    let mut candle_aggregator = CandleAggregator::new(
+        strategy_registry
+            .time_frames()
+            .into_iter()
+            .filter(|time_frame| *time_frame != TimeFrame::OneMinute)
+            .collect(),
+    );
+
+    let mut retry_count: u32 = 0;
+    let mut was_connected = false;
+    loop {
+        match connect_async(Url::parse(&server_url)?).await {
+            Ok((ws_stream, _)) => {
+                println!("Connected to WebSocket server: {}", server_url);
+                retry_count = 0;
+                if was_connected {
+                    let backfilled_stocks = backfill_missed_candles(&reconnect_config, &raw_stock_ledger, &mut tick_parser);
+                    for backfilled_stock in backfilled_stocks.iter() {
+                        raw_stock_ledger.add_raw_stock(backfilled_stock.clone());
+                        data_processor::process_backfilled_raw_stock(
+                            backfilled_stock,
+                            thread_worker_config.time_frame.clone(),
+                            &mut root_system_config,
+                            &raw_stock_ledger,
+                            redis_client,
+                        )
+                        .await;
+                        publish_aggregated_candles(&thread_worker_config, &mut candle_aggregator, backfilled_stock, true);
+                    }
+                }
+                was_connected = true;
+
+                consume_stream(
+                    ws_stream,
+                    &thread_worker_config,
+                    &reconnect_config,
+                    &mut root_system_config,
+                    &mut strategy_registry,
+                    &mut raw_stock_ledger,
+                    &mut tick_parser,
+                    &mut candle_aggregator,
+                    redis_client,
+                )
+                .await;
+                println!("Connection lost to WebSocket server: {}", server_url);
+            }
+            Err(e) => {
+                println!("Error while connecting to {} => {:?}", server_url, e);
+            }
+        }
+
+        if let Some(max_retry_count) = reconnect_config.max_retry_count {
+            if retry_count >= max_retry_count {
+                return Err(format!("Giving up on {} after {} retries", server_url, retry_count).into());
+            }
+        }
+        let backoff_delay = reconnect_config.backoff_delay(retry_count);
+        println!("Reconnecting to {} in {:?} (retry {})", server_url, backoff_delay, retry_count + 1);
+        tokio::time::sleep(backoff_delay).await;
+        retry_count += 1;
+    }
+}
+
+//returns when the server closes the stream, the stream errors out or the heartbeat times out
+#[allow(clippy::too_many_arguments)]
+async fn consume_stream(
+    mut ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    thread_worker_config: &ThreadWorkerConfig,
+    reconnect_config: &ReconnectConfig,
+    root_system_config: &mut RootSystemConfig,
+    strategy_registry: &mut StrategyRegistry,
+    raw_stock_ledger: &mut RawStockLedger,
+    tick_parser: &mut TickParser,
+    candle_aggregator: &mut CandleAggregator,
+    redis_client: &Mutex<RedisClient>,
+) {
+    let ping_interval = Duration::from_secs(reconnect_config.ping_interval_in_seconds);
+    let heartbeat_timeout = Duration::from_secs(reconnect_config.heartbeat_timeout_in_seconds);
+    let mut last_message_at = Instant::now();
+
+    loop {
+        let msg = match tokio::time::timeout(ping_interval, ws_stream.next()).await {
+            Ok(Some(msg)) => msg,
+            Ok(None) => return,
+            Err(_) => {
+                if last_message_at.elapsed() >= heartbeat_timeout {
+                    println!("No message received for {:?}, dropping the connection", last_message_at.elapsed());
+                    return;
+                }
+                if ws_stream.send(Message::Ping(Vec::new())).await.is_err() {
+                    println!("Error while sending ping, dropping the connection");
+                    return;
+                }
+                continue;
+            }
+        };
+
+        match msg {
+            Ok(message) => {
+                last_message_at = Instant::now();
+                if message.is_text() {
+                    let text = message.to_text().unwrap();
+
+                    let raw_stock = match tick_parser.parse(text) {
+                        Ok(raw_stock) => raw_stock,
+                        Err(TickParseError::HeaderRow) => continue,
+                        Err(e) => {
+                            println!(
+                                "Rejected tick on {} stream => {} | {:?}",
+                                thread_worker_config.time_frame, e, tick_parser.tick_parse_stats
+                            );
+                            continue;
+                        }
+                    };
+
+                    // println!("Received on {} tick: {:?}",thread_worker_config.time_frame, text);
+
+                    raw_stock_ledger.add_raw_stock(raw_stock.clone());
+
+                    data_processor::process_raw_stock(
+                        &raw_stock,
+                        thread_worker_config.time_frame.clone(),
+                        root_system_config,
+                        strategy_registry,
+                        raw_stock_ledger,
+                        redis_client,
+                    )
+                    .await;
+
+                    publish_aggregated_candles(thread_worker_config, candle_aggregator, &raw_stock, false);
+                } else if message.is_close() {
+                    return;
+                }
+            }
+            Err(e) => {
+                eprintln!("Error while receiving message: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn publish_aggregated_candles(
+    thread_worker_config: &ThreadWorkerConfig,
+    candle_aggregator: &mut CandleAggregator,
+    raw_stock: &RawStock,
+    is_backfilled: bool,
+) {
+    if thread_worker_config.time_frame != TimeFrame::OneMinute {
+        return;
+    }
+    for aggregated_stock in candle_aggregator.add_one_minute_candle(raw_stock) {
+        //an error only means no worker is subscribed right now
+        let _ = thread_worker_config.candle_broadcaster.send(AggregatedCandle {
+            raw_stock: aggregated_stock,
+            is_backfilled,
+        });
+    }
+}
+
+//Higher time frame worker => consumes the bars the 1 min worker builds instead of its own socket
+pub async fn ingest_data_via_candle_aggregator(
+    thread_worker_config: ThreadWorkerConfig,
+    redis_client: &Mutex<RedisClient>,
+) -> Result<(), Box<dyn Error>> {
+    let mut root_system_config = thread_worker_config.root_system_config.clone();
+    let mut candle_receiver = thread_worker_config.candle_broadcaster.subscribe();
+
+    let mut raw_stock_ledger = RawStockLedger::new();
+    let mut strategy_registry = StrategyRegistry::from_algo_types(
+        &root_system_config.tradeable_algo_types,
+        &root_system_config.strategy_configs,
+        &root_system_config.database_instance,
+        root_system_config.strategy_states.clone(),
+    );
+
+    println!("Subscribed to the {} candle aggregator", thread_worker_config.time_frame);
+    loop {
+        let aggregated_candle = match candle_receiver.recv().await {
+            Ok(aggregated_candle) => aggregated_candle,
+            Err(RecvError::Lagged(skipped_count)) => {
+                println!("{} worker lagged behind, {} aggregated candles skipped", thread_worker_config.time_frame, skipped_count);
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if aggregated_candle.raw_stock.market_time_frame != thread_worker_config.time_frame {
+            continue;
+        }
+
+        raw_stock_ledger.add_raw_stock(aggregated_candle.raw_stock.clone());
+        if aggregated_candle.is_backfilled {
+            data_processor::process_backfilled_raw_stock(
+                &aggregated_candle.raw_stock,
+                thread_worker_config.time_frame.clone(),
+                &mut root_system_config,
+                &raw_stock_ledger,
+                redis_client,
+            )
+            .await;
+            continue;
+        }
+
+        data_processor::process_raw_stock(
+            &aggregated_candle.raw_stock,
+            thread_worker_config.time_frame.clone(),
+            &mut root_system_config,
+            &mut strategy_registry,
+            &raw_stock_ledger,
+            redis_client,
+        )
+        .await;
+    }
+}
+
+//Reads the candles missed while the socket was down so the ledger (and the SMA built on it) has no holes.
+//Rows are parsed like socket messages => every candle keeps the symbol of its own row (or the stream's symbol).
+//The caller only watches the open orders on them, no signals are generated on stale prices.
+fn backfill_missed_candles(reconnect_config: &ReconnectConfig, raw_stock_ledger: &RawStockLedger, tick_parser: &mut TickParser) -> Vec<RawStock> {
+    let mut backfilled_stocks = Vec::new();
+    let backfill_file_path = match &reconnect_config.backfill_file_path {
+        Some(backfill_file_path) => backfill_file_path,
+        None => return backfilled_stocks,
+    };
+
+    let archived_stocks = match fetch_archived_candles(backfill_file_path, &mut tick_parser.archive_parser()) {
+        Ok(archived_stocks) => archived_stocks,
+        Err(e) => {
+            println!("Error while reading backfill candles from {} => {:?}", backfill_file_path, e);
+            return backfilled_stocks;
+        }
+    };
+
+    for archived_stock in archived_stocks {
+        //only symbols with live history => without it there is no gap to close
+        if raw_stock_ledger.get_raw_stocks(archived_stock.symbol.as_str()).is_empty() {
+            continue;
+        }
+        //the live parser rejects everything up to the last candle it saw of the symbol
+        if tick_parser.validate(&archived_stock).is_ok() {
+            backfilled_stocks.push(archived_stock);
+        }
+    }
+    println!("Backfilled {} candles from {}", backfilled_stocks.len(), backfill_file_path);
+    backfilled_stocks
+}
+
+fn fetch_archived_candles(backfill_file_path: &str, archive_parser: &mut TickParser) -> Result<Vec<RawStock>, Box<dyn Error>> {
+    let archived_rows = std::fs::read_to_string(backfill_file_path)?;
+    let archived_stocks: Vec<RawStock> = archived_rows
+        .lines()
+        .filter(|archived_row| !archived_row.trim().is_empty())
+        .filter_map(|archived_row| archive_parser.parse(archived_row).ok())
+        .collect();
+    if archive_parser.tick_parse_stats.rejected_count() > 0 {
+        println!("Rejected backfill rows in {} => {:?}", backfill_file_path, archive_parser.tick_parse_stats);
+    }
+    Ok(archived_stocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delay_doubles_up_to_the_cap_with_equal_jitter() {
+        let reconnect_config = ReconnectConfig {
+            initial_backoff_in_millis: 100,
+            max_backoff_in_millis: 1_000,
+            ..ReconnectConfig::default()
+        };
+        //retry 4 => 1600 ms capped to 1000, retry 64 => the multiplication saturates instead of overflowing
+        for (retry_count, expected_delay_in_millis) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1_000), (10, 1_000), (64, 1_000)] {
+            for _ in 0..100 {
+                let backoff_delay = reconnect_config.backoff_delay(retry_count).as_millis() as u64;
+                assert!(
+                    (expected_delay_in_millis / 2..=expected_delay_in_millis).contains(&backoff_delay),
+                    "retry {} waited {} ms",
+                    retry_count,
+                    backoff_delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_backoff_retries_immediately() {
+        let reconnect_config = ReconnectConfig {
+            initial_backoff_in_millis: 0,
+            ..ReconnectConfig::default()
+        };
+        assert_eq!(reconnect_config.backoff_delay(5), Duration::ZERO);
+    }
+
+    #[test]
+    fn backfilled_candles_keep_the_symbol_of_their_row() {
+        //the file name prefix is not a symbol => every row names its own
+        let backfill_file_path = std::env::temp_dir().join(format!("backfill_{}_1_min.csv", std::process::id()));
+        std::fs::write(
+            &backfill_file_path,
+            [
+                ",date,close,high,low,open,volume,symbol",
+                "0,2023-08-10 09:15:00+0530,101,102,100,100.5,1000,ADANIGREEN",
+                "1,2023-08-10 09:16:00+0530,101.5,102,100.5,101,900,ADANIGREEN",
+                "2,2023-08-10 09:16:00+0530,3301,3302,3299,3300,700,TCS",
+                "3,2023-08-10 09:17:00+0530,102,102.5,101,101.5,800,ADANIGREEN",
+                "4,2023-08-10 09:17:00+0530,99,99,99,99,100,INFY",
+                "",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        let reconnect_config = ReconnectConfig {
+            backfill_file_path: Some(backfill_file_path.to_string_lossy().to_string()),
+            ..ReconnectConfig::default()
+        };
+
+        //live history of ADANIGREEN up to 09:15, none of TCS
+        let mut tick_parser = TickParser::new(TimeFrame::OneMinute, None, vec!["ADANIGREEN".to_string(), "TCS".to_string()]);
+        let live_stock = tick_parser.parse("0,2023-08-10 09:15:00+0530,101,102,100,100.5,1000,ADANIGREEN").unwrap();
+        let mut raw_stock_ledger = RawStockLedger::new();
+        raw_stock_ledger.add_raw_stock(live_stock);
+
+        let backfilled_stocks = backfill_missed_candles(&reconnect_config, &raw_stock_ledger, &mut tick_parser);
+        let backfilled_candles: Vec<(&str, &str)> = backfilled_stocks
+            .iter()
+            .map(|backfilled_stock| (backfilled_stock.symbol.as_str(), backfilled_stock.date.as_str()))
+            .collect();
+        assert_eq!(
+            backfilled_candles,
+            vec![("ADANIGREEN", "2023-08-10 09:16:00+0530"), ("ADANIGREEN", "2023-08-10 09:17:00+0530")]
+        );
+        assert!(backfilled_stocks.iter().all(|backfilled_stock| backfilled_stock.market_time_frame == TimeFrame::OneMinute));
+        //the header row of the file did not remap the live columns and the live stream can not replay the backfilled candles
+        assert!(tick_parser.parse("5,2023-08-10 09:17:00+0530,102,102.5,101,101.5,800,ADANIGREEN").is_err());
+        assert!(tick_parser.parse("6,2023-08-10 09:18:00+0530,102,102.5,101,101.5,800,ADANIGREEN").is_ok());
+
+        std::fs::remove_file(&backfill_file_path).unwrap();
+        assert!(backfill_missed_candles(&reconnect_config, &raw_stock_ledger, &mut tick_parser).is_empty());
+        assert!(backfill_missed_candles(&ReconnectConfig::default(), &raw_stock_ledger, &mut tick_parser).is_empty());
+    }
+}
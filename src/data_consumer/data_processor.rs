@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+
+use crate::{
+    algo_hub::{algo_dispatcher, strategy::{MarketContext, StrategyRegistry}},
+    common::{
+        enums::{RootSystemConfig, TimeFrame},
+        raw_stock::{RawStock, RawStockLedger},
+        redis_client::RedisClient,
+    },
+    data_consumer::current_market_state::CurrentMarketState,
+    trade_watcher::monitor_trade,
+};
+
+//Single entry point for every candle, shared by the socket workers and the backtest runner
+pub async fn process_raw_stock(
+    raw_stock: &RawStock,
+    time_frame: TimeFrame,
+    root_system_config: &mut RootSystemConfig,
+    strategy_registry: &mut StrategyRegistry,
+    raw_stock_ledger: &RawStockLedger,
+    redis_client: &Mutex<RedisClient>,
+) {
+    let (current_market_state, average_true_range) =
+        roll_and_watch_open_trades(raw_stock, time_frame.clone(), root_system_config, raw_stock_ledger, redis_client).await;
+    if strategy_registry.is_subscribed_to(&time_frame) {
+        let market_context = MarketContext::new(current_market_state, average_true_range);
+
+        algo_dispatcher::ingest_raw_stock_data(
+            raw_stock,
+            &market_context,
+            strategy_registry,
+            &mut root_system_config.trade_keeper,
+            root_system_config.trade_signal_collection.clone(),
+            &mut root_system_config.order_manager,
+            root_system_config.orders_collection.clone(),
+            &root_system_config.risk_gate,
+            &root_system_config.position_sizer,
+            redis_client,
+            root_system_config.database_instance.clone(),
+        )
+        .await;
+    }
+}
+
+//Candles missed while the socket was down => the open orders still hit their SL or target on them,
+//but no new signal is taken on prices that are already stale
+pub async fn process_backfilled_raw_stock(
+    raw_stock: &RawStock,
+    time_frame: TimeFrame,
+    root_system_config: &mut RootSystemConfig,
+    raw_stock_ledger: &RawStockLedger,
+    redis_client: &Mutex<RedisClient>,
+) {
+    roll_and_watch_open_trades(raw_stock, time_frame, root_system_config, raw_stock_ledger, redis_client).await;
+}
+
+async fn roll_and_watch_open_trades(
+    raw_stock: &RawStock,
+    time_frame: TimeFrame,
+    root_system_config: &mut RootSystemConfig,
+    raw_stock_ledger: &RawStockLedger,
+    redis_client: &Mutex<RedisClient>,
+) -> (Option<CurrentMarketState>, Option<f32>) {
+    //indicators and market state are rolled on every time frame, the strategies only trade on the time frames they subscribe to
+    let indicator_values = root_system_config
+        .indicator_ledger
+        .update(raw_stock_ledger.get_raw_stocks(raw_stock.symbol.as_str()));
+    let average_true_range = indicator_values.atr; //of the candle's own time frame
+    let current_market_state = CurrentMarketState::calculate_market_state(
+        raw_stock,
+        time_frame.clone(),
+        &root_system_config.current_market_state_collection,
+        redis_client,
+        raw_stock_ledger,
+        indicator_values,
+        &root_system_config.market_state_history,
+    )
+    .await;
+    if time_frame == TimeFrame::OneMinute {
+        //resting broker orders are filled before the exits are checked
+        root_system_config.order_manager.broker.on_market_data(raw_stock);
+        monitor_trade::check_for_exit_opportunity(
+            &mut root_system_config.order_manager,
+            raw_stock.clone(),
+            &root_system_config.trade_watcher_config,
+            average_true_range,
+            redis_client,
+            root_system_config.orders_collection.clone(),
+        )
+        .await;
+    }
+    (current_market_state, average_true_range)
+}
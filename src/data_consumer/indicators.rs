@@ -0,0 +1,742 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    config_file,
+    date_parser,
+    enums::{MarketTrend, TimeFrame},
+    raw_stock::RawStock,
+};
+
+//Windows of every indicator, missing fields fall back to the defaults below
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndicatorConfig {
+    pub sma_window: usize,
+    pub ema_window: usize,
+    pub rsi_window: usize,
+    pub atr_window: usize,
+    pub bollinger_window: usize,
+    pub bollinger_std_multiplier: f32,
+    pub macd_fast_window: usize,
+    pub macd_slow_window: usize,
+    pub macd_signal_window: usize,
+    pub supertrend_atr_window: usize,
+    pub supertrend_multiplier: f32,
+    pub volume_average_window: usize,
+}
+
+impl Default for IndicatorConfig {
+    fn default() -> IndicatorConfig {
+        IndicatorConfig {
+            sma_window: 9,
+            ema_window: 21,
+            rsi_window: 14,
+            atr_window: 14,
+            bollinger_window: 20,
+            bollinger_std_multiplier: 2.0,
+            macd_fast_window: 12,
+            macd_slow_window: 26,
+            macd_signal_window: 9,
+            supertrend_atr_window: 10,
+            supertrend_multiplier: 3.0,
+            volume_average_window: 20,
+        }
+    }
+}
+
+//defaults for every time frame, a time frame entry replaces them as a whole (its own missing fields use the IndicatorConfig defaults)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct IndicatorConfigs {
+    pub defaults: IndicatorConfig,
+    pub time_frames: HashMap<String, IndicatorConfig>, //keyed by TimeFrame name
+}
+
+impl IndicatorConfigs {
+    //.toml files hold an [indicators] table, .json files a plain object
+    pub fn load_config(file_path: &str) -> IndicatorConfigs {
+        config_file::load_config(file_path, "indicators")
+    }
+
+    pub fn indicator_config(&self, time_frame: &TimeFrame) -> &IndicatorConfig {
+        self.time_frames.get(&time_frame.to_string()).unwrap_or(&self.defaults)
+    }
+}
+
+//latest value of every indicator, None => not enough candles yet for its window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct IndicatorValues {
+    pub sma: Option<f32>,
+    pub ema: Option<f32>,
+    pub rsi: Option<f32>,
+    pub atr: Option<f32>,
+    pub vwap: Option<f32>, //resets with every trade date
+    pub bollinger_upper: Option<f32>,
+    pub bollinger_middle: Option<f32>,
+    pub bollinger_lower: Option<f32>,
+    pub macd: Option<f32>,
+    pub macd_signal: Option<f32>,
+    pub macd_histogram: Option<f32>,
+    pub supertrend: Option<f32>,
+    pub supertrend_trend: Option<MarketTrend>,
+    pub volume_average: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimpleMovingAverage {
+    window: usize,
+    values: VecDeque<f32>,
+    sum: f64,
+}
+
+impl SimpleMovingAverage {
+    pub fn new(window: usize) -> SimpleMovingAverage {
+        SimpleMovingAverage {
+            window,
+            values: VecDeque::with_capacity(window + 1),
+            sum: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, value: f32) -> Option<f32> {
+        if self.window == 0 {
+            return None;
+        }
+        self.values.push_back(value);
+        self.sum += value as f64;
+        if self.values.len() > self.window {
+            self.sum -= self.values.pop_front().unwrap_or_default() as f64;
+        }
+        (self.values.len() == self.window).then(|| (self.sum / self.window as f64) as f32)
+    }
+}
+
+//seeded with the SMA of the first window values
+#[derive(Debug, Clone)]
+pub struct ExponentialMovingAverage {
+    multiplier: f32,
+    seed: SimpleMovingAverage,
+    value: Option<f32>,
+}
+
+impl ExponentialMovingAverage {
+    pub fn new(window: usize) -> ExponentialMovingAverage {
+        ExponentialMovingAverage {
+            multiplier: 2.0 / (window as f32 + 1.0),
+            seed: SimpleMovingAverage::new(window),
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, value: f32) -> Option<f32> {
+        self.value = match self.value {
+            Some(previous_value) => Some(previous_value + (value - previous_value) * self.multiplier),
+            None => self.seed.update(value),
+        };
+        self.value
+    }
+}
+
+//Wilder's smoothing => the first value is the plain average of window inputs, every later one (previous * (window - 1) + input) / window
+#[derive(Debug, Clone)]
+struct WilderAverage {
+    window: usize,
+    seed_count: usize,
+    seed_sum: f32,
+    value: Option<f32>,
+}
+
+impl WilderAverage {
+    fn new(window: usize) -> WilderAverage {
+        WilderAverage {
+            window,
+            seed_count: 0,
+            seed_sum: 0.0,
+            value: None,
+        }
+    }
+
+    fn update(&mut self, input: f32) -> Option<f32> {
+        if self.window == 0 {
+            return None;
+        }
+        self.value = match self.value {
+            Some(previous_value) => Some((previous_value * (self.window - 1) as f32 + input) / self.window as f32),
+            None => {
+                self.seed_count += 1;
+                self.seed_sum += input;
+                (self.seed_count == self.window).then(|| self.seed_sum / self.window as f32)
+            }
+        };
+        self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RelativeStrengthIndex {
+    previous_close: Option<f32>,
+    average_gain: WilderAverage,
+    average_loss: WilderAverage,
+}
+
+impl RelativeStrengthIndex {
+    pub fn new(window: usize) -> RelativeStrengthIndex {
+        RelativeStrengthIndex {
+            previous_close: None,
+            average_gain: WilderAverage::new(window),
+            average_loss: WilderAverage::new(window),
+        }
+    }
+
+    pub fn update(&mut self, close: f32) -> Option<f32> {
+        let previous_close = self.previous_close.replace(close)?;
+        let change = close - previous_close;
+        let average_gain = self.average_gain.update(change.max(0.0));
+        let average_loss = self.average_loss.update((-change).max(0.0));
+        let (average_gain, average_loss) = (average_gain?, average_loss?);
+        if average_loss == 0.0 {
+            return Some(100.0);
+        }
+        Some(100.0 - 100.0 / (1.0 + average_gain / average_loss))
+    }
+}
+
+//the first candle has no previous close, its true range is its high - low
+#[derive(Debug, Clone)]
+pub struct AverageTrueRange {
+    previous_close: Option<f32>,
+    average_true_range: WilderAverage,
+}
+
+impl AverageTrueRange {
+    pub fn new(window: usize) -> AverageTrueRange {
+        AverageTrueRange {
+            previous_close: None,
+            average_true_range: WilderAverage::new(window),
+        }
+    }
+
+    pub fn update(&mut self, stock: &RawStock) -> Option<f32> {
+        let true_range = match self.previous_close.replace(stock.close) {
+            Some(previous_close) => (stock.high - stock.low)
+                .max((stock.high - previous_close).abs())
+                .max((stock.low - previous_close).abs()),
+            None => stock.high - stock.low,
+        };
+        self.average_true_range.update(true_range)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VolumeWeightedAveragePrice {
+    trade_date: String,
+    cumulative_price_volume: f64,
+    cumulative_volume: f64,
+}
+
+impl VolumeWeightedAveragePrice {
+    pub fn new() -> VolumeWeightedAveragePrice {
+        VolumeWeightedAveragePrice::default()
+    }
+
+    pub fn update(&mut self, stock: &RawStock) -> Option<f32> {
+        let trade_date = date_parser::return_only_date_from_datetime(stock.date.as_str());
+        if trade_date != self.trade_date {
+            self.trade_date = trade_date;
+            self.cumulative_price_volume = 0.0;
+            self.cumulative_volume = 0.0;
+        }
+        let typical_price = (stock.high + stock.low + stock.close) as f64 / 3.0;
+        self.cumulative_price_volume += typical_price * stock.volume as f64;
+        self.cumulative_volume += stock.volume as f64;
+        (self.cumulative_volume > 0.0).then(|| (self.cumulative_price_volume / self.cumulative_volume) as f32)
+    }
+}
+
+//middle => SMA of the closes, the bands are std_multiplier population standard deviations away
+#[derive(Debug, Clone)]
+pub struct BollingerBands {
+    window: usize,
+    std_multiplier: f32,
+    closes: VecDeque<f32>,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl BollingerBands {
+    pub fn new(window: usize, std_multiplier: f32) -> BollingerBands {
+        BollingerBands {
+            window,
+            std_multiplier,
+            closes: VecDeque::with_capacity(window + 1),
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        }
+    }
+
+    //(upper, middle, lower)
+    pub fn update(&mut self, close: f32) -> Option<(f32, f32, f32)> {
+        if self.window == 0 {
+            return None;
+        }
+        self.closes.push_back(close);
+        self.sum += close as f64;
+        self.sum_of_squares += (close as f64).powi(2);
+        if self.closes.len() > self.window {
+            let dropped_close = self.closes.pop_front().unwrap_or_default() as f64;
+            self.sum -= dropped_close;
+            self.sum_of_squares -= dropped_close.powi(2);
+        }
+        if self.closes.len() < self.window {
+            return None;
+        }
+        let middle = self.sum / self.window as f64;
+        let standard_deviation = (self.sum_of_squares / self.window as f64 - middle.powi(2)).max(0.0).sqrt();
+        let band_width = standard_deviation * self.std_multiplier as f64;
+        Some(((middle + band_width) as f32, middle as f32, (middle - band_width) as f32))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MovingAverageConvergenceDivergence {
+    fast_ema: ExponentialMovingAverage,
+    slow_ema: ExponentialMovingAverage,
+    signal_ema: ExponentialMovingAverage,
+}
+
+impl MovingAverageConvergenceDivergence {
+    pub fn new(fast_window: usize, slow_window: usize, signal_window: usize) -> MovingAverageConvergenceDivergence {
+        MovingAverageConvergenceDivergence {
+            fast_ema: ExponentialMovingAverage::new(fast_window),
+            slow_ema: ExponentialMovingAverage::new(slow_window),
+            signal_ema: ExponentialMovingAverage::new(signal_window),
+        }
+    }
+
+    //(macd, signal, histogram), the signal line starts once signal_window MACD values exist
+    pub fn update(&mut self, close: f32) -> Option<(f32, Option<f32>, Option<f32>)> {
+        let fast_ema = self.fast_ema.update(close);
+        let slow_ema = self.slow_ema.update(close);
+        let macd = fast_ema? - slow_ema?;
+        let signal = self.signal_ema.update(macd);
+        Some((macd, signal, signal.map(|signal| macd - signal)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SuperTrend {
+    multiplier: f32,
+    average_true_range: AverageTrueRange,
+    final_upper_band: Option<f32>,
+    final_lower_band: Option<f32>,
+    previous_close: Option<f32>,
+    is_uptrend: bool,
+}
+
+impl SuperTrend {
+    pub fn new(atr_window: usize, multiplier: f32) -> SuperTrend {
+        SuperTrend {
+            multiplier,
+            average_true_range: AverageTrueRange::new(atr_window),
+            final_upper_band: None,
+            final_lower_band: None,
+            previous_close: None,
+            is_uptrend: true,
+        }
+    }
+
+    //the bands only tighten while price stays inside them, the trend flips when the close crosses the opposite band
+    pub fn update(&mut self, stock: &RawStock) -> Option<(f32, MarketTrend)> {
+        let average_true_range = self.average_true_range.update(stock);
+        let previous_close = self.previous_close.replace(stock.close);
+        let average_true_range = average_true_range?;
+
+        let middle = (stock.high + stock.low) / 2.0;
+        let basic_upper_band = middle + self.multiplier * average_true_range;
+        let basic_lower_band = middle - self.multiplier * average_true_range;
+        let final_upper_band = match (self.final_upper_band, previous_close) {
+            (Some(final_upper_band), Some(previous_close)) if basic_upper_band > final_upper_band && previous_close <= final_upper_band => final_upper_band,
+            _ => basic_upper_band,
+        };
+        let final_lower_band = match (self.final_lower_band, previous_close) {
+            (Some(final_lower_band), Some(previous_close)) if basic_lower_band < final_lower_band && previous_close >= final_lower_band => final_lower_band,
+            _ => basic_lower_band,
+        };
+
+        if self.final_upper_band.is_none() {
+            self.is_uptrend = stock.close >= middle;
+        } else if self.is_uptrend && stock.close < final_lower_band {
+            self.is_uptrend = false;
+        } else if !self.is_uptrend && stock.close > final_upper_band {
+            self.is_uptrend = true;
+        }
+        self.final_upper_band = Some(final_upper_band);
+        self.final_lower_band = Some(final_lower_band);
+
+        if self.is_uptrend {
+            Some((final_lower_band, MarketTrend::Bullish))
+        } else {
+            Some((final_upper_band, MarketTrend::Bearish))
+        }
+    }
+}
+
+//every indicator of one symbol on one time frame
+#[derive(Debug, Clone)]
+pub struct IndicatorSet {
+    sma: SimpleMovingAverage,
+    ema: ExponentialMovingAverage,
+    rsi: RelativeStrengthIndex,
+    atr: AverageTrueRange,
+    vwap: VolumeWeightedAveragePrice,
+    bollinger_bands: BollingerBands,
+    macd: MovingAverageConvergenceDivergence,
+    supertrend: SuperTrend,
+    volume_average: SimpleMovingAverage,
+    last_candle_at: Option<i64>, //start of the latest candle fed, epoch seconds
+    scanned_candle_count: usize,  //ledger candles already looked at, the ledger only appends
+    indicator_values: IndicatorValues,
+}
+
+impl IndicatorSet {
+    pub fn new(indicator_config: &IndicatorConfig) -> IndicatorSet {
+        IndicatorSet {
+            sma: SimpleMovingAverage::new(indicator_config.sma_window),
+            ema: ExponentialMovingAverage::new(indicator_config.ema_window),
+            rsi: RelativeStrengthIndex::new(indicator_config.rsi_window),
+            atr: AverageTrueRange::new(indicator_config.atr_window),
+            vwap: VolumeWeightedAveragePrice::new(),
+            bollinger_bands: BollingerBands::new(indicator_config.bollinger_window, indicator_config.bollinger_std_multiplier),
+            macd: MovingAverageConvergenceDivergence::new(
+                indicator_config.macd_fast_window,
+                indicator_config.macd_slow_window,
+                indicator_config.macd_signal_window,
+            ),
+            supertrend: SuperTrend::new(indicator_config.supertrend_atr_window, indicator_config.supertrend_multiplier),
+            volume_average: SimpleMovingAverage::new(indicator_config.volume_average_window),
+            last_candle_at: None,
+            scanned_candle_count: 0,
+            indicator_values: IndicatorValues::default(),
+        }
+    }
+
+    pub fn update(&mut self, stock: &RawStock) -> &IndicatorValues {
+        let bollinger_bands = self.bollinger_bands.update(stock.close);
+        let macd = self.macd.update(stock.close);
+        let supertrend = self.supertrend.update(stock);
+        self.indicator_values = IndicatorValues {
+            sma: self.sma.update(stock.close),
+            ema: self.ema.update(stock.close),
+            rsi: self.rsi.update(stock.close),
+            atr: self.atr.update(stock),
+            vwap: self.vwap.update(stock),
+            bollinger_upper: bollinger_bands.map(|(upper, _, _)| upper),
+            bollinger_middle: bollinger_bands.map(|(_, middle, _)| middle),
+            bollinger_lower: bollinger_bands.map(|(_, _, lower)| lower),
+            macd: macd.map(|(macd, _, _)| macd),
+            macd_signal: macd.and_then(|(_, signal, _)| signal),
+            macd_histogram: macd.and_then(|(_, _, histogram)| histogram),
+            supertrend: supertrend.as_ref().map(|(supertrend, _)| *supertrend),
+            supertrend_trend: supertrend.map(|(_, supertrend_trend)| supertrend_trend),
+            volume_average: self.volume_average.update(stock.volume as f32),
+        };
+        &self.indicator_values
+    }
+}
+
+//One IndicatorSet per symbol and time frame, every candle costs O(1) whatever the length of the history
+#[derive(Debug, Clone, Default)]
+pub struct IndicatorLedger {
+    indicator_configs: IndicatorConfigs,
+    indicator_sets: HashMap<String, IndicatorSet>,
+}
+
+impl IndicatorLedger {
+    pub fn new(indicator_configs: IndicatorConfigs) -> IndicatorLedger {
+        IndicatorLedger {
+            indicator_configs,
+            indicator_sets: HashMap::new(),
+        }
+    }
+
+    //Feeds the candles of the ledger slice that start after the last one this set has seen, in time order.
+    //A backfilled candle older than that can not be folded into running averages and is skipped.
+    //Returns the values after the latest candle fed.
+    pub fn update(&mut self, raw_stocks: &[RawStock]) -> IndicatorValues {
+        let last_stock = match raw_stocks.last() {
+            Some(last_stock) => last_stock,
+            None => return IndicatorValues::default(),
+        };
+        let indicator_config = self.indicator_configs.indicator_config(&last_stock.market_time_frame);
+        let indicator_set = self
+            .indicator_sets
+            .entry(IndicatorLedger::indicator_set_key(last_stock.symbol.as_str(), &last_stock.market_time_frame))
+            .or_insert_with(|| IndicatorSet::new(indicator_config));
+        //a shorter slice => the ledger was rebuilt, every candle is looked at again
+        if raw_stocks.len() < indicator_set.scanned_candle_count {
+            indicator_set.scanned_candle_count = 0;
+        }
+        let mut new_stocks: Vec<(i64, &RawStock)> = raw_stocks[indicator_set.scanned_candle_count..]
+            .iter()
+            .filter_map(|stock| {
+                date_parser::parse_date_time_in_stock_format(stock.date.as_str()).map(|candle_at| (candle_at.timestamp(), stock))
+            })
+            .filter(|(candle_at, _)| indicator_set.last_candle_at.is_none_or(|last_candle_at| *candle_at > last_candle_at))
+            .collect();
+        new_stocks.sort_by_key(|(candle_at, _)| *candle_at);
+        indicator_set.scanned_candle_count = raw_stocks.len();
+        for (candle_at, stock) in new_stocks {
+            indicator_set.update(stock);
+            indicator_set.last_candle_at = Some(candle_at);
+        }
+        indicator_set.indicator_values.clone()
+    }
+
+    pub fn get_indicator_values(&self, symbol: &str, time_frame: &TimeFrame) -> Option<IndicatorValues> {
+        self.indicator_sets
+            .get(&IndicatorLedger::indicator_set_key(symbol, time_frame))
+            .map(|indicator_set| indicator_set.indicator_values.clone())
+    }
+
+    pub fn clear(&mut self) {
+        self.indicator_sets.clear();
+    }
+
+    fn indicator_set_key(symbol: &str, time_frame: &TimeFrame) -> String {
+        format!("{}_{}", symbol, time_frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        match actual {
+            Some(actual) => assert!((actual - expected).abs() < 1e-3, "expected {} got {}", expected, actual),
+            None => panic!("expected {} got None", expected),
+        }
+    }
+
+    fn test_candle(date: &str, high: f32, low: f32, close: f32, volume: i32) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), close, high, low, close, volume, TimeFrame::FiveMinutes)
+    }
+
+    #[test]
+    fn sma_starts_once_the_window_is_full_and_slides() {
+        let mut sma = SimpleMovingAverage::new(3);
+        assert_eq!(sma.update(1.0), None);
+        assert_eq!(sma.update(2.0), None);
+        assert_close(sma.update(3.0), 2.0);
+        assert_close(sma.update(4.0), 3.0);
+        assert_close(sma.update(8.0), 5.0);
+        assert_eq!(SimpleMovingAverage::new(0).update(1.0), None);
+    }
+
+    #[test]
+    fn ema_is_seeded_with_the_sma() {
+        let mut ema = ExponentialMovingAverage::new(3);
+        assert_eq!(ema.update(2.0), None);
+        assert_eq!(ema.update(4.0), None);
+        assert_close(ema.update(6.0), 4.0);
+        assert_close(ema.update(8.0), 6.0);
+        assert_close(ema.update(12.0), 9.0);
+    }
+
+    #[test]
+    fn wilder_average_smooths_after_the_seed() {
+        let mut wilder_average = WilderAverage::new(3);
+        assert_eq!(wilder_average.update(3.0), None);
+        assert_eq!(wilder_average.update(6.0), None);
+        assert_close(wilder_average.update(9.0), 6.0);
+        assert_close(wilder_average.update(12.0), 8.0);
+        assert_eq!(WilderAverage::new(0).update(1.0), None);
+    }
+
+    #[test]
+    fn rsi_uses_wilder_averages_of_gains_and_losses() {
+        let mut rsi = RelativeStrengthIndex::new(2);
+        assert_eq!(rsi.update(10.0), None);
+        assert_eq!(rsi.update(11.0), None);
+        assert_close(rsi.update(10.5), 66.667);
+        assert_close(rsi.update(11.5), 85.714);
+
+        let mut rising_rsi = RelativeStrengthIndex::new(2);
+        for close in [1.0, 2.0, 3.0] {
+            rising_rsi.update(close);
+        }
+        assert_close(rising_rsi.update(4.0), 100.0);
+    }
+
+    #[test]
+    fn atr_uses_the_true_range_against_the_previous_close() {
+        let mut atr = AverageTrueRange::new(2);
+        assert_eq!(atr.update(&test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 100)), None);
+        assert_close(atr.update(&test_candle("2023-08-10 09:20:00+0530", 11.0, 9.0, 10.5, 100)), 2.0);
+        //the gap from 10.5 to the high of 13 is the true range
+        assert_close(atr.update(&test_candle("2023-08-10 09:25:00+0530", 13.0, 11.0, 12.0, 100)), 2.25);
+    }
+
+    #[test]
+    fn vwap_weights_the_typical_price_and_resets_every_day() {
+        let mut vwap = VolumeWeightedAveragePrice::new();
+        assert_eq!(vwap.update(&test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 0)), None);
+        assert_close(vwap.update(&test_candle("2023-08-10 09:20:00+0530", 10.0, 8.0, 9.0, 100)), 9.0);
+        assert_close(vwap.update(&test_candle("2023-08-10 09:25:00+0530", 12.0, 10.0, 11.0, 300)), 10.5);
+        assert_close(vwap.update(&test_candle("2023-08-11 09:15:00+0530", 20.0, 18.0, 19.0, 50)), 19.0);
+    }
+
+    #[test]
+    fn bollinger_bands_use_the_population_standard_deviation() {
+        let mut bollinger_bands = BollingerBands::new(3, 2.0);
+        assert_eq!(bollinger_bands.update(2.0), None);
+        assert_eq!(bollinger_bands.update(4.0), None);
+        let (upper, middle, lower) = bollinger_bands.update(6.0).unwrap();
+        assert_close(Some(upper), 7.266);
+        assert_close(Some(middle), 4.0);
+        assert_close(Some(lower), 0.734);
+        //a flat window has no width
+        let mut flat_bollinger_bands = BollingerBands::new(2, 2.0);
+        flat_bollinger_bands.update(5.0);
+        assert_eq!(flat_bollinger_bands.update(5.0), Some((5.0, 5.0, 5.0)));
+    }
+
+    #[test]
+    fn macd_signal_starts_after_signal_window_macd_values() {
+        let mut macd = MovingAverageConvergenceDivergence::new(2, 3, 2);
+        assert_eq!(macd.update(1.0), None);
+        assert_eq!(macd.update(2.0), None);
+        let (macd_value, signal, histogram) = macd.update(3.0).unwrap();
+        assert_close(Some(macd_value), 0.5);
+        assert_eq!((signal, histogram), (None, None));
+
+        let (macd_value, signal, histogram) = macd.update(4.0).unwrap();
+        assert_close(Some(macd_value), 0.5);
+        assert_close(signal, 0.5);
+        assert_close(histogram, 0.0);
+
+        let (macd_value, signal, histogram) = macd.update(8.0).unwrap();
+        assert_close(Some(macd_value), 1.0);
+        assert_close(signal, 0.8333);
+        assert_close(histogram, 0.1667);
+    }
+
+    #[test]
+    fn supertrend_flips_when_the_close_crosses_the_held_band() {
+        let mut supertrend = SuperTrend::new(2, 1.0);
+        assert_eq!(supertrend.update(&test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 100)), None);
+
+        let (band, trend) = supertrend.update(&test_candle("2023-08-10 09:20:00+0530", 11.0, 9.0, 10.5, 100)).unwrap();
+        assert_close(Some(band), 8.0);
+        assert_eq!(trend, MarketTrend::Bullish);
+
+        //the lower band is held at 8 and the close falls through it
+        let (band, trend) = supertrend.update(&test_candle("2023-08-10 09:25:00+0530", 9.0, 6.0, 6.5, 100)).unwrap();
+        assert_close(Some(band), 10.75);
+        assert_eq!(trend, MarketTrend::Bearish);
+    }
+
+    #[test]
+    fn indicator_set_fills_every_value_once_warmed_up() {
+        let indicator_config = IndicatorConfig {
+            sma_window: 2,
+            ema_window: 2,
+            rsi_window: 2,
+            atr_window: 2,
+            bollinger_window: 2,
+            bollinger_std_multiplier: 2.0,
+            macd_fast_window: 2,
+            macd_slow_window: 3,
+            macd_signal_window: 2,
+            supertrend_atr_window: 2,
+            supertrend_multiplier: 1.0,
+            volume_average_window: 2,
+        };
+        let mut indicator_set = IndicatorSet::new(&indicator_config);
+        let indicator_values = indicator_set.update(&test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 100)).clone();
+        assert_eq!(indicator_values.sma, None);
+        assert_eq!(indicator_values.volume_average, None);
+        assert_close(indicator_values.vwap, 9.0);
+
+        indicator_set.update(&test_candle("2023-08-10 09:20:00+0530", 11.0, 9.0, 10.0, 300));
+        indicator_set.update(&test_candle("2023-08-10 09:25:00+0530", 12.0, 10.0, 11.0, 200));
+        let indicator_values = indicator_set.update(&test_candle("2023-08-10 09:30:00+0530", 13.0, 11.0, 12.0, 400)).clone();
+        assert_close(indicator_values.sma, 11.5);
+        assert_close(indicator_values.volume_average, 300.0);
+        assert_close(indicator_values.rsi, 100.0);
+        assert_close(indicator_values.atr, 2.0);
+        assert!(indicator_values.ema.is_some() && indicator_values.bollinger_middle.is_some());
+        assert!(indicator_values.macd_histogram.is_some() && indicator_values.supertrend.is_some());
+        assert_eq!(indicator_values.supertrend_trend, Some(MarketTrend::Bullish));
+    }
+
+    fn ledger_with_sma_window(sma_window: usize) -> IndicatorLedger {
+        IndicatorLedger::new(IndicatorConfigs {
+            defaults: IndicatorConfig { sma_window, ..IndicatorConfig::default() },
+            ..IndicatorConfigs::default()
+        })
+    }
+
+    #[test]
+    fn ledger_feeds_only_candles_newer_than_the_last_one() {
+        let mut indicator_ledger = ledger_with_sma_window(2);
+        let mut raw_stocks = vec![
+            test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 100),
+            test_candle("2023-08-10 09:20:00+0530", 11.0, 9.0, 11.0, 100),
+        ];
+        assert_close(indicator_ledger.update(&raw_stocks).sma, 10.0);
+        //the same slice again changes nothing
+        assert_close(indicator_ledger.update(&raw_stocks).sma, 10.0);
+
+        //a backfilled candle older than the last one fed is skipped
+        raw_stocks.push(test_candle("2023-08-10 09:10:00+0530", 30.0, 20.0, 25.0, 100));
+        assert_close(indicator_ledger.update(&raw_stocks).sma, 10.0);
+
+        //newer candles appended out of order are fed in time order
+        raw_stocks.push(test_candle("2023-08-10 09:30:00+0530", 16.0, 14.0, 15.0, 100));
+        raw_stocks.push(test_candle("2023-08-10 09:25:00+0530", 14.0, 12.0, 13.0, 100));
+        assert_close(indicator_ledger.update(&raw_stocks).sma, 14.0);
+        assert_close(
+            indicator_ledger.get_indicator_values("ADANIGREEN", &TimeFrame::FiveMinutes).and_then(|indicator_values| indicator_values.sma),
+            14.0,
+        );
+    }
+
+    #[test]
+    fn ledger_catches_up_the_same_as_candle_by_candle() {
+        let raw_stocks: Vec<RawStock> = (0..40)
+            .map(|index| {
+                let close = 100.0 + (index as f32 * 0.7).sin() * 5.0;
+                test_candle(format!("2023-08-10 {:02}:{:02}:00+0530", 9 + index / 12, (index % 12) * 5).as_str(), close + 1.0, close - 1.0, close, 100 + index)
+            })
+            .collect();
+
+        let mut catch_up_ledger = IndicatorLedger::new(IndicatorConfigs::default());
+        let caught_up_values = catch_up_ledger.update(&raw_stocks);
+
+        let mut candle_by_candle_ledger = IndicatorLedger::new(IndicatorConfigs::default());
+        let mut candle_by_candle_values = IndicatorValues::default();
+        for candle_count in 1..=raw_stocks.len() {
+            candle_by_candle_values = candle_by_candle_ledger.update(&raw_stocks[..candle_count]);
+        }
+        assert_eq!(caught_up_values, candle_by_candle_values);
+        assert!(caught_up_values.rsi.is_some() && caught_up_values.macd_signal.is_some());
+    }
+
+    #[test]
+    fn cleared_ledger_starts_over() {
+        let mut indicator_ledger = ledger_with_sma_window(2);
+        let raw_stocks = vec![
+            test_candle("2023-08-10 09:15:00+0530", 10.0, 8.0, 9.0, 100),
+            test_candle("2023-08-10 09:20:00+0530", 11.0, 9.0, 11.0, 100),
+        ];
+        indicator_ledger.update(&raw_stocks);
+        indicator_ledger.clear();
+        assert_eq!(indicator_ledger.get_indicator_values("ADANIGREEN", &TimeFrame::FiveMinutes), None);
+        assert_eq!(indicator_ledger.update(&raw_stocks[..1]).sma, None);
+        assert_close(indicator_ledger.update(&raw_stocks).sma, 10.0);
+    }
+}
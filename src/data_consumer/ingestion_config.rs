@@ -0,0 +1,216 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::config_file;
+
+use super::data_consumer::ReconnectConfig;
+
+//one socket feeding 1 min candles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamConfig {
+    pub server_url: String,
+    pub stream_symbol: Option<String>, //Some => the stream carries a single symbol, None => every message carries its own symbol column
+}
+
+//Symbols and sockets of the live workers. Nothing is assumed => without symbols the process refuses to start
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IngestionConfig {
+    pub tradeable_symbols: Vec<String>,
+    pub streams: Vec<StreamConfig>,
+    pub reconnect: ReconnectConfig, //shared by every stream
+}
+
+impl IngestionConfig {
+    //.toml files hold an [ingestion] table, .json files a plain object
+    pub fn load_config(file_path: &str) -> IngestionConfig {
+        config_file::load_config(file_path, "ingestion")
+    }
+
+    //--symbols ADANIGREEN,TCS replaces the symbols, every --stream <url>[=<symbol>] replaces the streams of the file
+    //and --backfill-file <csv path> sets the reconnect backfill
+    pub fn apply_cli_args(mut self, args: &[String]) -> Result<IngestionConfig, String> {
+        let mut cli_streams: Vec<StreamConfig> = Vec::new();
+        let mut arg_iter = args.iter();
+        while let Some(arg) = arg_iter.next() {
+            match arg.as_str() {
+                "--symbols" => {
+                    let symbols = arg_iter.next().ok_or("--symbols needs a comma separated list")?;
+                    self.tradeable_symbols = symbols
+                        .split(',')
+                        .map(|symbol| symbol.trim().to_string())
+                        .filter(|symbol| !symbol.is_empty())
+                        .collect();
+                }
+                "--backfill-file" => {
+                    let backfill_file_path = arg_iter.next().ok_or("--backfill-file needs a CSV path")?;
+                    self.reconnect.backfill_file_path = Some(backfill_file_path.clone());
+                }
+                "--stream" => {
+                    let stream = arg_iter.next().ok_or("--stream needs <url>[=<symbol>]")?;
+                    cli_streams.push(match stream.split_once('=') {
+                        Some((server_url, stream_symbol)) => StreamConfig {
+                            server_url: server_url.to_string(),
+                            stream_symbol: Some(stream_symbol.to_string()),
+                        },
+                        None => StreamConfig {
+                            server_url: stream.to_string(),
+                            stream_symbol: None,
+                        },
+                    });
+                }
+                _ => (),
+            }
+        }
+        if !cli_streams.is_empty() {
+            self.streams = cli_streams;
+        }
+        Ok(self)
+    }
+
+    //the positional arguments (mode and CSV paths) without the ingestion flags and their values
+    pub fn positional_args(args: &[String]) -> Vec<String> {
+        let mut positional_args = Vec::new();
+        let mut arg_iter = args.iter();
+        while let Some(arg) = arg_iter.next() {
+            match arg.as_str() {
+                "--symbols" | "--stream" | "--backfill-file" => {
+                    arg_iter.next();
+                }
+                _ => positional_args.push(arg.clone()),
+            }
+        }
+        positional_args
+    }
+
+    //is_live => every stream is checked too, a backtest reads its candles from CSV
+    pub fn validate(&self, is_live: bool) -> Result<(), String> {
+        if self.tradeable_symbols.is_empty() {
+            return Err("no tradeable symbols, set tradeable_symbols in ingestion.toml or pass --symbols".to_string());
+        }
+        if !is_live {
+            return Ok(());
+        }
+        if let Some(backfill_file_path) = &self.reconnect.backfill_file_path {
+            if !Path::new(backfill_file_path).exists() {
+                return Err(format!("backfill file {} does not exist", backfill_file_path));
+            }
+        }
+        if self.streams.is_empty() {
+            return Err("no streams, add [[ingestion.streams]] to ingestion.toml or pass --stream".to_string());
+        }
+        for stream in self.streams.iter() {
+            if let Some(stream_symbol) = &stream.stream_symbol {
+                if !self.tradeable_symbols.contains(stream_symbol) {
+                    return Err(format!("stream {} carries {} which is not a tradeable symbol", stream.server_url, stream_symbol));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn file_config() -> IngestionConfig {
+        IngestionConfig {
+            tradeable_symbols: vec!["ADANIGREEN".to_string()],
+            streams: vec![StreamConfig {
+                server_url: "ws://localhost:5554".to_string(),
+                stream_symbol: Some("ADANIGREEN".to_string()),
+            }],
+            reconnect: ReconnectConfig::default(),
+        }
+    }
+
+    #[test]
+    fn cli_args_replace_the_file_values() {
+        let ingestion_config = file_config()
+            .apply_cli_args(&args(&["algo-trading", "--symbols", "TCS, INFY", "--stream", "ws://localhost:6000=TCS", "--stream", "ws://localhost:6001"]))
+            .unwrap();
+        assert_eq!(ingestion_config.tradeable_symbols, vec!["TCS".to_string(), "INFY".to_string()]);
+        assert_eq!(
+            ingestion_config.streams,
+            vec![
+                StreamConfig {
+                    server_url: "ws://localhost:6000".to_string(),
+                    stream_symbol: Some("TCS".to_string()),
+                },
+                StreamConfig {
+                    server_url: "ws://localhost:6001".to_string(),
+                    stream_symbol: None,
+                },
+            ]
+        );
+        assert_eq!(ingestion_config.validate(true), Ok(()));
+        assert_eq!(ingestion_config.reconnect, ReconnectConfig::default());
+
+        //without flags the file values stay
+        assert_eq!(file_config().apply_cli_args(&args(&["algo-trading"])).unwrap(), file_config());
+        assert!(file_config().apply_cli_args(&args(&["algo-trading", "--symbols"])).is_err());
+    }
+
+    #[test]
+    fn flags_are_not_positional_args() {
+        assert_eq!(
+            IngestionConfig::positional_args(&args(&["algo-trading", "backtest", "--symbols", "TCS", "1m.csv", "--backfill-file", "backfill.csv", "5m.csv", "--stream", "ws://localhost:6000", "20000"])),
+            args(&["algo-trading", "backtest", "1m.csv", "5m.csv", "20000"])
+        );
+    }
+
+    #[test]
+    fn missing_symbols_or_streams_are_refused() {
+        assert!(IngestionConfig::default().validate(false).is_err());
+        assert!(IngestionConfig::default().validate(true).is_err());
+
+        let without_streams = IngestionConfig {
+            streams: Vec::new(),
+            ..file_config()
+        };
+        assert_eq!(without_streams.validate(false), Ok(()));
+        assert!(without_streams.validate(true).is_err());
+
+        let missing_backfill_file = file_config().apply_cli_args(&args(&["algo-trading", "--backfill-file", "does_not_exist.csv"])).unwrap();
+        assert_eq!(missing_backfill_file.reconnect.backfill_file_path, Some("does_not_exist.csv".to_string()));
+        assert!(missing_backfill_file.validate(true).is_err());
+
+        let untradeable_stream = file_config().apply_cli_args(&args(&["algo-trading", "--stream", "ws://localhost:6000=TCS"])).unwrap();
+        assert!(untradeable_stream.validate(true).is_err());
+    }
+
+    #[test]
+    fn toml_table_is_read() {
+        let ingestion_table: toml::Table = toml::from_str(
+            r#"
+            [ingestion]
+            tradeable_symbols = ["ADANIGREEN", "TCS"]
+
+            [[ingestion.streams]]
+            server_url = "ws://localhost:5554"
+            stream_symbol = "ADANIGREEN"
+
+            [[ingestion.streams]]
+            server_url = "ws://localhost:5555"
+
+            [ingestion.reconnect]
+            max_backoff_in_millis = 10000
+            max_retry_count = 20
+            "#,
+        )
+        .unwrap();
+        let ingestion_config: IngestionConfig = ingestion_table["ingestion"].clone().try_into().unwrap();
+        assert_eq!(ingestion_config.tradeable_symbols.len(), 2);
+        assert_eq!(ingestion_config.streams[1].stream_symbol, None);
+        assert_eq!(ingestion_config.reconnect.max_backoff_in_millis, 10_000);
+        assert_eq!(ingestion_config.reconnect.max_retry_count, Some(20));
+        assert_eq!(ingestion_config.reconnect.initial_backoff_in_millis, ReconnectConfig::default().initial_backoff_in_millis);
+        assert_eq!(ingestion_config.validate(true), Ok(()));
+    }
+}
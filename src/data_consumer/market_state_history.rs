@@ -0,0 +1,202 @@
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, DateTime as BsonDateTime, Document},
+    options::{CreateCollectionOptions, FindOneOptions, FindOptions, TimeseriesGranularity, TimeseriesOptions},
+    Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{date_parser, enums::TimeFrame},
+    order_manager::trade_signal_keeper::TradeSignal,
+};
+
+use super::current_market_state::CurrentMarketState;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketStateSnapshotMeta {
+    pub symbol: String,
+    pub market_time_frame: TimeFrame,
+}
+
+//one per candle the market state was rolled on, never updated afterwards
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketStateSnapshot {
+    pub candle_at: BsonDateTime, //time field of the time-series collection
+    pub meta: MarketStateSnapshotMeta,
+    pub market_state: CurrentMarketState,
+}
+
+//Append-only history of the market states, next to the latest state per day kept in current_market_states
+#[derive(Debug, Clone)]
+pub struct MarketStateHistory {
+    market_state_snapshot_collection: Collection<MarketStateSnapshot>,
+}
+
+impl MarketStateHistory {
+    pub const COLLECTION_NAME: &'static str = "market_state_snapshots";
+
+    pub fn new(database_instance: &Database) -> MarketStateHistory {
+        MarketStateHistory {
+            market_state_snapshot_collection: database_instance.collection::<MarketStateSnapshot>(MarketStateHistory::COLLECTION_NAME),
+        }
+    }
+
+    //creates the time-series collection on the first start, an existing collection is left as it is
+    pub async fn create_collection(database_instance: &Database) {
+        match database_instance.list_collection_names(doc! {"name": MarketStateHistory::COLLECTION_NAME}).await {
+            Ok(collection_names) if !collection_names.is_empty() => return,
+            Ok(_) => (),
+            Err(e) => {
+                println!("Error while listing the collections => {:?}", e);
+                return;
+            }
+        }
+        let timeseries_options = TimeseriesOptions::builder()
+            .time_field("candle_at".to_string())
+            .meta_field(Some("meta".to_string()))
+            .granularity(Some(TimeseriesGranularity::Minutes))
+            .build();
+        let options = CreateCollectionOptions::builder().timeseries(timeseries_options).build();
+        if let Err(e) = database_instance.create_collection(MarketStateHistory::COLLECTION_NAME, options).await {
+            println!("Error while creating the {} time-series collection => {:?}", MarketStateHistory::COLLECTION_NAME, e);
+        }
+    }
+
+    pub async fn record_snapshot(&self, market_state: &CurrentMarketState) {
+        let candle_at = match MarketStateHistory::to_bson_date_time(market_state.current_candle_at.as_str()) {
+            Some(candle_at) => candle_at,
+            None => {
+                println!("Market state snapshot skipped, invalid candle date {}", market_state.current_candle_at);
+                return;
+            }
+        };
+        let market_state_snapshot = MarketStateSnapshot {
+            candle_at,
+            meta: MarketStateSnapshotMeta {
+                symbol: market_state.symbol.clone(),
+                market_time_frame: market_state.market_time_frame.clone(),
+            },
+            market_state: market_state.clone(),
+        };
+        if let Err(e) = self.market_state_snapshot_collection.insert_one(market_state_snapshot, None).await {
+            println!("Error while inserting a market state snapshot => {:?}", e);
+        }
+    }
+
+    //from and to are candle dates in the stock format, both included
+    pub async fn fetch_snapshots(&self, symbol: &str, time_frame: &TimeFrame, from: &str, to: &str) -> Vec<MarketStateSnapshot> {
+        let (from, to) = match (MarketStateHistory::to_bson_date_time(from), MarketStateHistory::to_bson_date_time(to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => {
+                println!("Invalid market state snapshot range {} - {}", from, to);
+                return Vec::new();
+            }
+        };
+        let filter = MarketStateHistory::series_filter(symbol, time_frame, doc! {"$gte": from, "$lte": to});
+        let options = FindOptions::builder().sort(doc! {"candle_at": 1}).build();
+        let market_state_snapshots = match self.market_state_snapshot_collection.find(filter, options).await {
+            Ok(cursor) => cursor.try_collect().await,
+            Err(e) => Err(e),
+        };
+        market_state_snapshots.unwrap_or_else(|e| {
+            println!("Error while fetching the market state snapshots => {:?}", e);
+            Vec::new()
+        })
+    }
+
+    //latest snapshot at or before the given candle date
+    pub async fn fetch_snapshot_at(&self, symbol: &str, time_frame: &TimeFrame, at: &str) -> Option<MarketStateSnapshot> {
+        let at = MarketStateHistory::to_bson_date_time(at)?;
+        let filter = MarketStateHistory::series_filter(symbol, time_frame, doc! {"$lte": at});
+        let options = FindOneOptions::builder().sort(doc! {"candle_at": -1}).build();
+        match self.market_state_snapshot_collection.find_one(filter, options).await {
+            Ok(market_state_snapshot) => market_state_snapshot,
+            Err(e) => {
+                println!("Error while fetching the market state snapshot => {:?}", e);
+                None
+            }
+        }
+    }
+
+    //the market context the strategy saw when the signal fired => the state rolled on the signal candle
+    pub async fn fetch_snapshot_for_trade_signal(&self, trade_signal: &TradeSignal) -> Option<MarketStateSnapshot> {
+        let raw_stock = &trade_signal.raw_stock;
+        self.fetch_snapshot_at(raw_stock.symbol.as_str(), &raw_stock.market_time_frame, raw_stock.date.as_str()).await
+    }
+
+    //a rerun of the same candles would otherwise append the same snapshots again.
+    //MongoDB before 7.0 only deletes from a time-series collection by its meta fields => the whole series goes, not a candle range
+    pub async fn delete_snapshots(&self, symbol: &str, time_frame: &TimeFrame) {
+        if let Err(e) = self.market_state_snapshot_collection.delete_many(MarketStateHistory::meta_filter(symbol, time_frame), None).await {
+            println!("Error while deleting the market state snapshots => {:?}", e);
+        }
+    }
+
+    fn meta_filter(symbol: &str, time_frame: &TimeFrame) -> Document {
+        doc! {
+            "meta.symbol": symbol,
+            "meta.market_time_frame": time_frame.to_string(),
+        }
+    }
+
+    fn series_filter(symbol: &str, time_frame: &TimeFrame, candle_at_filter: Document) -> Document {
+        let mut series_filter = MarketStateHistory::meta_filter(symbol, time_frame);
+        series_filter.insert("candle_at", candle_at_filter);
+        series_filter
+    }
+
+    fn to_bson_date_time(date: &str) -> Option<BsonDateTime> {
+        date_parser::parse_date_time_in_stock_format(date).map(|date_time| BsonDateTime::from_millis(date_time.timestamp_millis()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candle_dates_convert_to_utc_millis() {
+        //09:15 IST is 03:45 UTC
+        assert_eq!(
+            MarketStateHistory::to_bson_date_time("2023-08-10 09:15:00+0530"),
+            Some(BsonDateTime::parse_rfc3339_str("2023-08-10T03:45:00Z").unwrap())
+        );
+        let one_minute_later = MarketStateHistory::to_bson_date_time("2023-08-10 09:16:00+0530").unwrap();
+        assert_eq!(one_minute_later.timestamp_millis() - MarketStateHistory::to_bson_date_time("2023-08-10 09:15:00+0530").unwrap().timestamp_millis(), 60_000);
+        assert_eq!(MarketStateHistory::to_bson_date_time("2023-08-10"), None);
+        assert_eq!(MarketStateHistory::to_bson_date_time(""), None);
+    }
+
+    #[test]
+    fn series_filter_matches_the_meta_fields_and_the_candle_range() {
+        let from = MarketStateHistory::to_bson_date_time("2023-08-10 09:15:00+0530").unwrap();
+        let to = MarketStateHistory::to_bson_date_time("2023-08-10 15:25:00+0530").unwrap();
+        assert_eq!(
+            MarketStateHistory::series_filter("ADANIGREEN", &TimeFrame::FiveMinutes, doc! {"$gte": from, "$lte": to}),
+            doc! {
+                "meta.symbol": "ADANIGREEN",
+                "meta.market_time_frame": "FiveMinutes",
+                "candle_at": {"$gte": from, "$lte": to},
+            }
+        );
+        //deletes never filter on the time field
+        assert_eq!(
+            MarketStateHistory::meta_filter("ADANIGREEN", &TimeFrame::OneMinute),
+            doc! {
+                "meta.symbol": "ADANIGREEN",
+                "meta.market_time_frame": "OneMinute",
+            }
+        );
+    }
+
+    #[test]
+    fn snapshot_meta_is_stored_as_the_filter_reads_it() {
+        let market_state_snapshot_meta = MarketStateSnapshotMeta {
+            symbol: "ADANIGREEN".to_string(),
+            market_time_frame: TimeFrame::FiveMinutes,
+        };
+        let meta_document = mongodb::bson::to_document(&market_state_snapshot_meta).unwrap();
+        assert_eq!(meta_document, doc! {"symbol": "ADANIGREEN", "market_time_frame": "FiveMinutes"});
+    }
+}
@@ -1,4 +1,9 @@
 pub mod data_consumer;
 pub mod data_consumer_via_csv;
 pub mod data_processor;
-pub mod current_market_state;
\ No newline at end of file
+pub mod current_market_state;
+pub mod tick_parser;
+pub mod ingestion_config;
+pub mod candle_aggregator;
+pub mod indicators;
+pub mod market_state_history;
\ No newline at end of file
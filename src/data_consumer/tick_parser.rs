@@ -0,0 +1,392 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::common::{
+    date_parser,
+    enums::TimeFrame,
+    raw_stock::RawStock,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TickParseError {
+    HeaderRow, //not a failure => the row only updated the column mapping
+    MissingColumn(String),
+    MalformedField { column: String, value: String },
+    OutOfOrderTimestamp { symbol: String, previous_date: String, date: String },
+    UntradeableSymbol(String),
+}
+
+impl fmt::Display for TickParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for TickParseError {}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TickParseStats {
+    pub parsed_count: i64,
+    pub header_row_count: i64,
+    pub missing_column_count: i64,
+    pub malformed_field_count: i64,
+    pub out_of_order_timestamp_count: i64,
+    pub untradeable_symbol_count: i64,
+}
+
+impl TickParseStats {
+    fn record(&mut self, result: &Result<RawStock, TickParseError>) {
+        match result {
+            Ok(_) => self.parsed_count += 1,
+            Err(TickParseError::HeaderRow) => self.header_row_count += 1,
+            Err(TickParseError::MissingColumn(_)) => self.missing_column_count += 1,
+            Err(TickParseError::MalformedField { .. }) => self.malformed_field_count += 1,
+            Err(TickParseError::OutOfOrderTimestamp { .. }) => self.out_of_order_timestamp_count += 1,
+            Err(TickParseError::UntradeableSymbol(_)) => self.untradeable_symbol_count += 1,
+        }
+    }
+
+    pub fn rejected_count(&self) -> i64 {
+        self.missing_column_count + self.malformed_field_count + self.out_of_order_timestamp_count + self.untradeable_symbol_count
+    }
+}
+
+const DATE_COLUMN: &str = "date";
+const CLOSE_COLUMN: &str = "close";
+const HIGH_COLUMN: &str = "high";
+const LOW_COLUMN: &str = "low";
+const OPEN_COLUMN: &str = "open";
+const VOLUME_COLUMN: &str = "volume";
+const SYMBOL_COLUMN: &str = "symbol";
+
+//Accepts the three layouts the feed can send:
+//1. quoted CSV => "index,date,close,high,low,open,volume[,symbol]" (the whole line or single fields may be quoted)
+//2. JSON => {"symbol": .., "date": .., "open": .., "high": .., "low": .., "close": .., "volume": ..}
+//3. CSV header row => remaps the CSV columns for every following message of the stream
+pub struct TickParser {
+    time_frame: TimeFrame,
+    stream_symbol: Option<String>,
+    tradeable_symbols: Vec<String>,
+    column_mapping: HashMap<String, usize>,
+    last_date_by_symbol: HashMap<String, (i64, String)>,
+    pub tick_parse_stats: TickParseStats,
+}
+
+impl TickParser {
+    pub fn new(time_frame: TimeFrame, stream_symbol: Option<String>, tradeable_symbols: Vec<String>) -> TickParser {
+        TickParser {
+            time_frame,
+            stream_symbol,
+            tradeable_symbols,
+            column_mapping: TickParser::default_column_mapping(),
+            last_date_by_symbol: HashMap::new(),
+            tick_parse_stats: TickParseStats::default(),
+        }
+    }
+
+    //same stream settings with fresh columns and timestamps => a header row in an archived file can not remap the live stream
+    pub fn archive_parser(&self) -> TickParser {
+        TickParser::new(self.time_frame.clone(), self.stream_symbol.clone(), self.tradeable_symbols.clone())
+    }
+
+    fn default_column_mapping() -> HashMap<String, usize> {
+        [DATE_COLUMN, CLOSE_COLUMN, HIGH_COLUMN, LOW_COLUMN, OPEN_COLUMN, VOLUME_COLUMN, SYMBOL_COLUMN]
+            .iter()
+            .enumerate()
+            .map(|(index, column)| (column.to_string(), index + 1))
+            .collect()
+    }
+
+    pub fn parse(&mut self, message: &str) -> Result<RawStock, TickParseError> {
+        let result = self.parse_message(message.trim());
+        self.tick_parse_stats.record(&result);
+        result
+    }
+
+    fn parse_message(&mut self, message: &str) -> Result<RawStock, TickParseError> {
+        let raw_stock = if message.starts_with('{') {
+            self.parse_json(message)?
+        } else {
+            let fields = TickParser::split_csv(message)?;
+            if self.is_header_row(&fields) {
+                self.update_column_mapping(&fields);
+                return Err(TickParseError::HeaderRow);
+            }
+            self.parse_csv(&fields)?
+        };
+
+        self.validate(&raw_stock)?;
+        Ok(raw_stock)
+    }
+
+    //also used for candles that do not come through the socket, e.g. backfill, so the live stream cannot replay them
+    pub fn validate(&mut self, raw_stock: &RawStock) -> Result<(), TickParseError> {
+        if !self.tradeable_symbols.contains(&raw_stock.symbol) {
+            return Err(TickParseError::UntradeableSymbol(raw_stock.symbol.clone()));
+        }
+        self.check_timestamp_order(raw_stock)
+    }
+
+    fn split_csv(message: &str) -> Result<Vec<String>, TickParseError> {
+        //the feed wraps the whole line in quotes => strip them so the commas split the columns again
+        let message = if message.len() > 1 && message.starts_with('"') && message.ends_with('"') && !message[1..message.len() - 1].contains('"') {
+            &message[1..message.len() - 1]
+        } else {
+            message
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(message.as_bytes());
+        match reader.records().next() {
+            Some(Ok(record)) => Ok(record.iter().map(|field| field.trim().to_owned()).collect()),
+            Some(Err(e)) => Err(TickParseError::MalformedField {
+                column: "message".to_string(),
+                value: e.to_string(),
+            }),
+            None => Err(TickParseError::MissingColumn(DATE_COLUMN.to_string())),
+        }
+    }
+
+    fn is_header_row(&self, fields: &[String]) -> bool {
+        fields.iter().any(|field| field.eq_ignore_ascii_case(DATE_COLUMN))
+            && fields.iter().any(|field| field.eq_ignore_ascii_case(CLOSE_COLUMN))
+    }
+
+    fn update_column_mapping(&mut self, fields: &[String]) {
+        self.column_mapping = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| (field.to_lowercase(), index))
+            .collect();
+    }
+
+    fn parse_csv(&self, fields: &[String]) -> Result<RawStock, TickParseError> {
+        let symbol = match self.column_mapping.get(SYMBOL_COLUMN).and_then(|index| fields.get(*index)) {
+            Some(symbol) if !symbol.is_empty() => symbol.clone(),
+            _ => self
+                .stream_symbol
+                .clone()
+                .ok_or_else(|| TickParseError::MissingColumn(SYMBOL_COLUMN.to_string()))?,
+        };
+
+        Ok(RawStock::new(
+            symbol,
+            TickParser::parse_date(self.csv_field(fields, DATE_COLUMN)?)?,
+            TickParser::parse_number::<f32>(CLOSE_COLUMN, self.csv_field(fields, CLOSE_COLUMN)?)?,
+            TickParser::parse_number::<f32>(HIGH_COLUMN, self.csv_field(fields, HIGH_COLUMN)?)?,
+            TickParser::parse_number::<f32>(LOW_COLUMN, self.csv_field(fields, LOW_COLUMN)?)?,
+            TickParser::parse_number::<f32>(OPEN_COLUMN, self.csv_field(fields, OPEN_COLUMN)?)?,
+            TickParser::parse_number::<i32>(VOLUME_COLUMN, self.csv_field(fields, VOLUME_COLUMN)?)?,
+            self.time_frame.clone(),
+        ))
+    }
+
+    fn csv_field<'a>(&self, fields: &'a [String], column: &str) -> Result<&'a str, TickParseError> {
+        self.column_mapping
+            .get(column)
+            .and_then(|index| fields.get(*index))
+            .map(|field| field.as_str())
+            .ok_or_else(|| TickParseError::MissingColumn(column.to_string()))
+    }
+
+    fn parse_json(&self, message: &str) -> Result<RawStock, TickParseError> {
+        let value: Value = serde_json::from_str(message).map_err(|e| TickParseError::MalformedField {
+            column: "message".to_string(),
+            value: e.to_string(),
+        })?;
+
+        let symbol = match TickParser::json_field(&value, SYMBOL_COLUMN) {
+            Ok(symbol) => symbol,
+            Err(_) => self
+                .stream_symbol
+                .clone()
+                .ok_or_else(|| TickParseError::MissingColumn(SYMBOL_COLUMN.to_string()))?,
+        };
+
+        Ok(RawStock::new(
+            symbol,
+            TickParser::parse_date(TickParser::json_field(&value, DATE_COLUMN)?.as_str())?,
+            TickParser::parse_number::<f32>(CLOSE_COLUMN, TickParser::json_field(&value, CLOSE_COLUMN)?.as_str())?,
+            TickParser::parse_number::<f32>(HIGH_COLUMN, TickParser::json_field(&value, HIGH_COLUMN)?.as_str())?,
+            TickParser::parse_number::<f32>(LOW_COLUMN, TickParser::json_field(&value, LOW_COLUMN)?.as_str())?,
+            TickParser::parse_number::<f32>(OPEN_COLUMN, TickParser::json_field(&value, OPEN_COLUMN)?.as_str())?,
+            TickParser::parse_number::<i32>(VOLUME_COLUMN, TickParser::json_field(&value, VOLUME_COLUMN)?.as_str())?,
+            self.time_frame.clone(),
+        ))
+    }
+
+    //numbers may come as JSON numbers or as strings
+    fn json_field(value: &Value, column: &str) -> Result<String, TickParseError> {
+        match value.get(column) {
+            Some(Value::String(field)) => Ok(field.trim().to_owned()),
+            Some(Value::Number(field)) => Ok(field.to_string()),
+            Some(field) => Err(TickParseError::MalformedField {
+                column: column.to_string(),
+                value: field.to_string(),
+            }),
+            None => Err(TickParseError::MissingColumn(column.to_string())),
+        }
+    }
+
+    fn parse_date(date: &str) -> Result<String, TickParseError> {
+        date_parser::parse_date_in_stock_format(date).map_err(|_| TickParseError::MalformedField {
+            column: DATE_COLUMN.to_string(),
+            value: date.to_string(),
+        })
+    }
+
+    fn parse_number<T: std::str::FromStr>(column: &str, value: &str) -> Result<T, TickParseError> {
+        value.parse::<T>().map_err(|_| TickParseError::MalformedField {
+            column: column.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    //a repeated timestamp counts as out of order too, otherwise the same candle would be processed twice
+    fn check_timestamp_order(&mut self, raw_stock: &RawStock) -> Result<(), TickParseError> {
+        let date_in_seconds = date_parser::date_time_in_seconds(raw_stock.date.as_str());
+        if let Some((previous_date_in_seconds, previous_date)) = self.last_date_by_symbol.get(&raw_stock.symbol) {
+            if date_in_seconds <= *previous_date_in_seconds {
+                return Err(TickParseError::OutOfOrderTimestamp {
+                    symbol: raw_stock.symbol.clone(),
+                    previous_date: previous_date.clone(),
+                    date: raw_stock.date.clone(),
+                });
+            }
+        }
+        self.last_date_by_symbol
+            .insert(raw_stock.symbol.clone(), (date_in_seconds, raw_stock.date.clone()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick_parser(stream_symbol: Option<&str>) -> TickParser {
+        TickParser::new(
+            TimeFrame::OneMinute,
+            stream_symbol.map(|stream_symbol| stream_symbol.to_string()),
+            vec!["ADANIGREEN".to_string(), "TCS".to_string()],
+        )
+    }
+
+    fn raw_stock(symbol: &str, date: &str, close: f32, high: f32, low: f32, open: f32, volume: i32) -> RawStock {
+        RawStock::new(symbol.to_string(), date.to_string(), close, high, low, open, volume, TimeFrame::OneMinute)
+    }
+
+    #[test]
+    fn quoted_csv_row_is_parsed() {
+        let mut tick_parser = tick_parser(None);
+        assert_eq!(
+            tick_parser.parse("\"0,2023-01-02 09:15:00+0530,101.5,102,100.5,101,1500,ADANIGREEN\""),
+            Ok(raw_stock("ADANIGREEN", "2023-01-02 09:15:00+0530", 101.5, 102.0, 100.5, 101.0, 1500))
+        );
+        //without the symbol column the tick belongs to the stream's symbol
+        let mut tick_parser = self::tick_parser(Some("TCS"));
+        assert_eq!(
+            tick_parser.parse("1,\"2023-01-02 09:16:00+0530\",3301,3302,3299.5,3300,700"),
+            Ok(raw_stock("TCS", "2023-01-02 09:16:00+0530", 3301.0, 3302.0, 3299.5, 3300.0, 700))
+        );
+    }
+
+    #[test]
+    fn json_row_is_parsed_with_numbers_or_strings() {
+        let mut tick_parser = tick_parser(None);
+        assert_eq!(
+            tick_parser.parse(r#"{"symbol": "TCS", "date": "2023-01-02 09:15:00+0530", "open": 3300, "high": "3302", "low": 3299.5, "close": 3301, "volume": 700}"#),
+            Ok(raw_stock("TCS", "2023-01-02 09:15:00+0530", 3301.0, 3302.0, 3299.5, 3300.0, 700))
+        );
+    }
+
+    #[test]
+    fn header_row_remaps_the_following_rows() {
+        let mut tick_parser = tick_parser(None);
+        assert_eq!(tick_parser.parse("symbol,date,open,high,low,close,volume"), Err(TickParseError::HeaderRow));
+        assert_eq!(
+            tick_parser.parse("ADANIGREEN,2023-01-02 09:15:00+0530,101,102,100.5,101.5,1500"),
+            Ok(raw_stock("ADANIGREEN", "2023-01-02 09:15:00+0530", 101.5, 102.0, 100.5, 101.0, 1500))
+        );
+        assert_eq!(tick_parser.tick_parse_stats.header_row_count, 1);
+        assert_eq!(tick_parser.tick_parse_stats.rejected_count(), 0);
+    }
+
+    #[test]
+    fn malformed_numbers_and_dates_are_rejected() {
+        let mut tick_parser = tick_parser(None);
+        assert_eq!(
+            tick_parser.parse("0,2023-01-02 09:15:00+0530,101.5,abc,100.5,101,1500,ADANIGREEN"),
+            Err(TickParseError::MalformedField {
+                column: HIGH_COLUMN.to_string(),
+                value: "abc".to_string(),
+            })
+        );
+        assert_eq!(
+            tick_parser.parse("0,2023-01-02 09:15:00+0530,101.5,102,100.5,101,15.5,ADANIGREEN"),
+            Err(TickParseError::MalformedField {
+                column: VOLUME_COLUMN.to_string(),
+                value: "15.5".to_string(),
+            })
+        );
+        assert!(matches!(
+            tick_parser.parse("0,02/01/2023 09:15,101.5,102,100.5,101,1500,ADANIGREEN"),
+            Err(TickParseError::MalformedField { column, .. }) if column == DATE_COLUMN
+        ));
+        assert!(matches!(tick_parser.parse("{\"symbol\": \"TCS\","), Err(TickParseError::MalformedField { .. })));
+    }
+
+    #[test]
+    fn missing_fields_are_rejected() {
+        let mut tick_parser = tick_parser(Some("ADANIGREEN"));
+        assert_eq!(
+            tick_parser.parse("0,2023-01-02 09:15:00+0530,101.5,102,100.5"),
+            Err(TickParseError::MissingColumn(OPEN_COLUMN.to_string()))
+        );
+        assert_eq!(
+            tick_parser.parse(r#"{"symbol": "TCS", "date": "2023-01-02 09:15:00+0530", "open": 3300, "high": 3302, "low": 3299.5, "close": 3301}"#),
+            Err(TickParseError::MissingColumn(VOLUME_COLUMN.to_string()))
+        );
+        //no symbol column and no stream symbol to fall back on
+        let mut tick_parser = self::tick_parser(None);
+        assert_eq!(
+            tick_parser.parse("0,2023-01-02 09:15:00+0530,101.5,102,100.5,101,1500"),
+            Err(TickParseError::MissingColumn(SYMBOL_COLUMN.to_string()))
+        );
+    }
+
+    #[test]
+    fn every_outcome_is_counted() {
+        let mut tick_parser = tick_parser(None);
+        let messages = [
+            "date,close,high,low,open,volume,symbol",
+            "2023-01-02 09:15:00+0530,101.5,102,100.5,101,1500,ADANIGREEN",
+            "2023-01-02 09:16:00+0530,101.7,102,101.2,101.5,900,ADANIGREEN",
+            "2023-01-02 09:16:00+0530,101.7,102,101.2,101.5,900,ADANIGREEN", //repeated
+            "2023-01-02 09:14:00+0530,101.1,101.3,100.9,101,400,ADANIGREEN", //older
+            "2023-01-02 09:15:00+0530,3301,3302,3299.5,3300,700,TCS", //other symbols keep their own order
+            "2023-01-02 09:15:00+0530,55,56,54,55,100,INFY",
+            "2023-01-02 09:17:00+0530,x,102,101.2,101.5,900,ADANIGREEN",
+            "2023-01-02 09:17:00+0530,101.7,102",
+        ];
+        for message in messages {
+            let _ = tick_parser.parse(message);
+        }
+        assert_eq!(
+            tick_parser.tick_parse_stats,
+            TickParseStats {
+                parsed_count: 3,
+                header_row_count: 1,
+                missing_column_count: 1,
+                malformed_field_count: 1,
+                out_of_order_timestamp_count: 2,
+                untradeable_symbol_count: 1,
+            }
+        );
+        assert_eq!(tick_parser.tick_parse_stats.rejected_count(), 5);
+    }
+}
@@ -1,70 +1,256 @@
 pub mod algo_hub;
+pub mod backtest_stock;
+pub mod broker;
 pub mod common;
 pub mod data_consumer;
 pub mod order_manager;
 pub mod trade_watcher;
 pub mod user;
-use algo_hub::hammer_pattern::{self, HammerCandle};
-use common::redis_client::RedisClient;
-use data_consumer::current_market_state::CurrentMarketState;
-use futures::StreamExt;
+use broker::{
+    broker::Broker,
+    kite_broker::{KiteBroker, KiteConfig},
+    paper_broker::{PaperBroker, PaperBrokerConfig},
+};
+use algo_hub::{hammer_config::HammerConfig, shooting_star_config::ShootingStarConfig, strategy::{StrategyConfigs, StrategyRegistry}, strategy_state::StrategyStates};
+use backtest_stock::{
+    backtest_report::BacktestReport,
+    backtest_runner::BacktestRunner,
+    optimizer::{BacktestOptimizer, OptimizationObjective, ParameterGrid},
+};
+use common::{config_file, redis_client::RedisClient};
+use data_consumer::{
+    current_market_state::CurrentMarketState,
+    candle_aggregator::AggregatedCandle,
+    data_consumer::{ingest_data_via_candle_aggregator, ingest_data_via_stream},
+    data_consumer_via_csv,
+    indicators::{IndicatorConfigs, IndicatorLedger},
+    ingestion_config::IngestionConfig,
+    market_state_history::MarketStateHistory,
+};
 use order_manager::{
     order_dispatcher,
+    position_sizer::PositionSizer,
     pnl_state::{self, CurrentPnLState, PnLConfiguration},
+    position_book::PositionBook,
+    risk_gate::{RiskGate, RiskRejection},
     trade_signal_keeper::{self, TradeSignal},
 };
-use std::sync::{Mutex, Arc};
+use std::sync::Arc;
+use tokio::sync::broadcast;
 extern crate mongodb;
 extern crate tokio;
 use mongodb::{options::ClientOptions, Client};
 
-use crate::{
-    algo_hub::algo_dispatcher,
-    common::{
-        date_parser,
-        enums::{AlgoTypes, RootSystemConfig, ThreadJobType, ThreadWorkerConfig},
-        raw_stock::{RawStock, RawStockLedger},
-    }, trade_watcher::monitor_trade,
-};
+use crate::common::enums::{AlgoTypes, RootSystemConfig, ThreadJobType, ThreadWorkerConfig};
 use crate::{common::enums::TimeFrame, order_manager::order_dispatcher::Order};
+use trade_watcher::{square_off::run_square_off_cron, trade_watcher_config::TradeWatcherConfig};
 
-use std::error::Error;
-use tokio_tungstenite::connect_async;
-use url::Url;
 
 #[tokio::main]
 async fn main() {
     let mongo_url = "mongodb://localhost:27017";
-    let database_name = "algo_trading";
+    //cargo run -- backtest <1 min csv path> <5 min csv path> [trade capital]
+    //--symbols <a,b>, --stream <url>[=<symbol>] and --backfill-file <csv path> override ingestion.toml in every mode
+    let cli_args: Vec<String> = std::env::args().collect();
+    let args = IngestionConfig::positional_args(&cli_args);
+    let is_backtest = args.len() > 3 && args[1] == "backtest";
+    let database_name = if is_backtest { "algo_trading_backtest" } else { "algo_trading" };
+
+    //START -> sweep the hammer parameters over the CSV candles, fully in memory
+    //cargo run -- optimize <1 min csv path> <5 min csv path> [trade capital] [objective]
+    if args.len() > 3 && args[1] == "optimize" {
+        let stock_1_min_data = data_consumer_via_csv::read_1_min_data(args[2].as_str()).unwrap();
+        let stock_5_min_data = data_consumer_via_csv::read_5_min_data(args[3].as_str()).unwrap();
+        let replay_stocks = BacktestRunner::merge_in_timestamp_order(stock_1_min_data, stock_5_min_data);
+
+        let trade_capital = args.get(4).map(|capital| capital.parse::<i64>().unwrap()).unwrap_or(10000);
+        let objective = args
+            .get(5)
+            .and_then(|objective| OptimizationObjective::from_name(objective.as_str()))
+            .unwrap_or(OptimizationObjective::SharpeRatio);
+
+        let base_hammer_config = config_file::load_from_file::<Vec<HammerConfig>>("hammer_configs.toml", HammerConfig::COLLECTION_NAME)
+            .ok()
+            .and_then(|hammer_configs| hammer_configs.into_iter().next())
+            .unwrap_or_default();
+        let parameter_sets = ParameterGrid::default().to_parameter_sets(&base_hammer_config).unwrap_or_else(|e| {
+            eprintln!("Invalid parameter grid: {}", e);
+            std::process::exit(1);
+        });
+
+        let optimizer = BacktestOptimizer::new(objective, trade_capital, TradeWatcherConfig::load_config("trade_watcher.toml"));
+        let optimization_results = optimizer.optimize(&parameter_sets, &replay_stocks);
+        for optimization_result in optimization_results.iter().take(10) {
+            println!(
+                "Score {} => {:?} | {:?}",
+                optimization_result.score, optimization_result.parameter_set, optimization_result.metrics
+            );
+        }
+
+        //20 trading days to tune, the next 5 to validate
+        let walk_forward_results = optimizer.walk_forward(&parameter_sets, &replay_stocks, 20, 5);
+        for walk_forward_result in walk_forward_results.iter() {
+            println!(
+                "Walk forward {} - {} => in sample score {} | out of sample score {} ({} - {})",
+                walk_forward_result.in_sample_start_date,
+                walk_forward_result.in_sample_end_date,
+                walk_forward_result.in_sample_result.score,
+                walk_forward_result.out_of_sample_score,
+                walk_forward_result.out_of_sample_start_date,
+                walk_forward_result.out_of_sample_end_date
+            );
+        }
+
+        let optimization_output = serde_json::json!({
+            "optimization_results": optimization_results,
+            "walk_forward_results": walk_forward_results,
+        });
+        if let Err(e) = std::fs::write("optimization_results.json", optimization_output.to_string()) {
+            println!("Error while exporting the optimization results => {:?}", e);
+        }
+        return;
+    }
+    //END -> sweep the hammer parameters over the CSV candles, fully in memory
 
     let client_options = ClientOptions::parse(mongo_url).await.unwrap();
     let client = Client::with_options(client_options).unwrap();
 
     let db = client.database(database_name);
+    MarketStateHistory::create_collection(&db).await;
 
     // let stock_5_min_data = data_consumer_via_csv::read_5_min_data(FILE_5MIN_PATH).unwrap();
-    let hammer_candle_collection_name = "hammer_candles";
-    let hammer_candle_collection = db.collection::<HammerCandle>(hammer_candle_collection_name);
-
-    let hammer_ledger = hammer_pattern::HammerPatternUtil::new();
-
     //START -> add the current_market_state into the database
     let current_market_state_collection_name = "current_market_states";
     let current_market_state_collection = client
         .database(database_name)
         .collection::<CurrentMarketState>(current_market_state_collection_name);
 
+    let market_state_history = MarketStateHistory::new(&db);
+
     let orders_collection_name = "orders";
     let orders_collection = db.collection::<Order>(orders_collection_name);
 
     let trade_signal_collection_name = "trade_signals";
     let trade_signal_collection = db.collection::<TradeSignal>(trade_signal_collection_name);
     let trade_keeper = trade_signal_keeper::TradeSignalsKeeper::new();
+    let strategy_states = StrategyStates::new(); //every worker's registry records into this one handle
+
+    let redis_client = if is_backtest { RedisClient::get_backtest_instance() } else { RedisClient::get_instance() };
+
+    let hammer_config_collection = db.collection::<HammerConfig>(HammerConfig::COLLECTION_NAME);
+    let hammer_configs = HammerConfig::load_configs("hammer_configs.toml", hammer_config_collection)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid hammer configs: {}", e);
+            std::process::exit(1);
+        });
+    let shooting_star_config = ShootingStarConfig::load_config("shooting_star.toml");
+    let strategy_configs = StrategyConfigs::new()
+        .with_section(HammerConfig::COLLECTION_NAME, &hammer_configs)
+        .with_section(ShootingStarConfig::CONFIG_SECTION, &shooting_star_config);
+
+    //fail fast => without symbols no strategy can ever trade
+    let ingestion_config = IngestionConfig::load_config("ingestion.toml")
+        .apply_cli_args(&cli_args)
+        .and_then(|ingestion_config| ingestion_config.validate(!is_backtest).map(|_| ingestion_config))
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid ingestion config: {}", e);
+            std::process::exit(1);
+        });
+    let tradeable_symbols = ingestion_config.tradeable_symbols.clone();
+
+    //risk limits come from the latest pnl_configuration, otherwise the static defaults
+    let pnl_configuration_collection = db.collection::<PnLConfiguration>("pnl_configurations");
+    let pnl_configuration = PnLConfiguration::fetch_latest_config(pnl_configuration_collection)
+        .await
+        .unwrap_or_else(|| PnLConfiguration::new_default_config(tradeable_symbols.clone()));
+    //live orders only go to Zerodha when the Kite credentials are set, backtests always use the paper broker
+    let broker: Arc<dyn Broker> = match KiteConfig::from_env() {
+        Some(kite_config) if !is_backtest => Arc::new(KiteBroker::new(kite_config)),
+        _ => Arc::new(PaperBroker::new(PaperBrokerConfig::default())),
+    };
+    println!("Orders are placed with {}", broker.name());
+    let order_manager = order_dispatcher::OrderManager::new(
+        pnl_configuration.clone(),
+        db.collection::<CurrentPnLState>(order_dispatcher::OrderManager::PNL_STATE_COLLECTION_NAME),
+        broker,
+        PositionBook::new(), //every worker gets a clone of the order manager, they all share this book
+    );
+    //a backtest starts from an empty book, its replay resets the orders of earlier runs instead
+    if !is_backtest {
+        order_manager.position_book.restore_open_orders(&orders_collection).await;
+    }
+    let risk_gate = RiskGate::new(pnl_configuration, db.collection::<RiskRejection>(RiskGate::COLLECTION_NAME));
+    let position_sizer = PositionSizer::load_config("position_sizer.toml");
+    let trade_watcher_config = TradeWatcherConfig::load_config("trade_watcher.toml");
+    let indicator_configs = IndicatorConfigs::load_config("indicators.toml");
+
+    //START -> replay the CSV candles through the live pipeline
+    if is_backtest {
+        let stock_1_min_data = data_consumer_via_csv::read_1_min_data(args[2].as_str()).unwrap();
+        let stock_5_min_data = data_consumer_via_csv::read_5_min_data(args[3].as_str()).unwrap();
+
+        let mut backtest_runner = BacktestRunner::new(RootSystemConfig {
+            database_instance: db.clone(),
+            current_market_state_collection: current_market_state_collection.clone(),
+            market_state_history: market_state_history.clone(),
+            orders_collection: orders_collection.clone(),
+            trade_signal_collection: trade_signal_collection.clone(),
+            server_url: "".to_string(),
+            tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo, AlgoTypes::ShootingStarPatternAlgo],
+            tradeable_symbols: tradeable_symbols.clone(),
+            strategy_configs: strategy_configs.clone(),
+            strategy_states: strategy_states.clone(),
+            trade_keeper: trade_keeper.clone(),
+            order_manager: order_manager.clone(),
+            risk_gate: risk_gate.clone(),
+            position_sizer: position_sizer.clone(),
+            trade_watcher_config: trade_watcher_config.clone(),
+            indicator_ledger: IndicatorLedger::new(indicator_configs.clone()),
+        });
+        let closed_orders = backtest_runner
+            .run(stock_1_min_data, stock_5_min_data, redis_client)
+            .await;
+
+        for order in closed_orders.iter() {
+            println!("Closed order => {}", order);
+        }
+        println!("Backtest finished with {} closed orders", closed_orders.len());
+        for net_position in order_manager.position_book.net_positions() {
+            println!("Net position => {:?}", net_position);
+        }
+        for strategy_state in strategy_states.get_strategy_states() {
+            println!("Strategy state => {:?}", strategy_state);
+        }
+        for trade_signal in trade_keeper.get_trade_signals() {
+            if let Some(market_state_snapshot) = market_state_history.fetch_snapshot_for_trade_signal(&trade_signal).await {
+                let market_state = market_state_snapshot.market_state;
+                println!(
+                    "Market state at the {} signal on {} {} => trend {} sma {} rsi {:?} green {} red {}",
+                    trade_signal.strategy_instance,
+                    trade_signal.raw_stock.symbol,
+                    trade_signal.raw_stock.date,
+                    market_state.current_candle_market_trend,
+                    market_state.current_sma,
+                    market_state.indicators.rsi,
+                    market_state.last_consecutive_green_candle_count,
+                    market_state.last_consecutive_red_candle_count,
+                );
+            }
+        }
 
-    let order_manager = order_dispatcher::OrderManager::new();
-    let redis_client = RedisClient::get_instance();
-
-    let shared_order_ledger: Arc<Mutex<Vec<Order>>> = Arc::new(Mutex::new(Vec::new()));
+        let trade_capital = args.get(4).map(|capital| capital.parse::<i64>().unwrap()).unwrap_or(10000);
+        let backtest_report = BacktestReport::new(&closed_orders, trade_capital);
+        println!("Backtest report => {:?}", backtest_report.overall);
+        if let Err(e) = backtest_report.export_as_json("backtest_report.json") {
+            println!("Error while exporting the backtest report as JSON => {:?}", e);
+        }
+        if let Err(e) = backtest_report.export_as_csv("backtest_report.csv") {
+            println!("Error while exporting the backtest report as CSV => {:?}", e);
+        }
+        return;
+    }
+    //END -> replay the CSV candles through the live pipeline
 
     //START -> add new User into the database
     // let user_collection_name = "users";
@@ -89,7 +275,7 @@ async fn main() {
     //     .database(database_name)
     //     .collection::<PnLConfiguration>(pnl_configuration_collection_name);
 
-    // pnl_state::PnLConfiguration::new_static_config(pnl_configuration_collection.clone()).await;
+    // pnl_state::PnLConfiguration::new_static_config(pnl_configuration_collection.clone(), tradeable_symbols.clone()).await;
     //END -> add the pnl_configuration into the database
 
     //START -> add the current_pnl_state into the database
@@ -111,70 +297,101 @@ async fn main() {
     // .await;
     //END -> add the current_pnl_state into the database
 
-    let thread_worker_configs = vec![
-        ThreadWorkerConfig {
+    //the 1 min worker publishes every higher time frame bar it builds, the other workers subscribe to it
+    let (candle_broadcaster, _) = broadcast::channel::<AggregatedCandle>(1024);
+
+    //one 1 min socket worker per configured stream
+    let socket_worker_configs: Vec<ThreadWorkerConfig> = ingestion_config
+        .streams
+        .iter()
+        .map(|stream| ThreadWorkerConfig {
             thread_job_type: ThreadJobType::DataConsumerViaSocket,
             time_frame: TimeFrame::OneMinute,
+            stream_symbol: stream.stream_symbol.clone(),
+            reconnect_config: ingestion_config.reconnect.clone(),
+            candle_broadcaster: candle_broadcaster.clone(),
             root_system_config: RootSystemConfig {
                 database_instance: db.clone(),
-                hammer_candle_collection: hammer_candle_collection.clone(),
-                hammer_ledger: hammer_ledger.clone(),
                 current_market_state_collection: current_market_state_collection.clone(),
+                market_state_history: market_state_history.clone(),
                 orders_collection: orders_collection.clone(),
                 trade_signal_collection: trade_signal_collection.clone(),
-                server_url: "ws://localhost:5554".to_string(),
-                tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo],
+                server_url: stream.server_url.clone(),
+                tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo, AlgoTypes::ShootingStarPatternAlgo],
+                tradeable_symbols: tradeable_symbols.clone(),
+                strategy_configs: strategy_configs.clone(),
+                strategy_states: strategy_states.clone(),
                 trade_keeper: trade_keeper.clone(),
                 order_manager: order_manager.clone(),
-                shared_order_ledger: shared_order_ledger.clone(),
+                risk_gate: risk_gate.clone(),
+                position_sizer: position_sizer.clone(),
+                trade_watcher_config: trade_watcher_config.clone(),
+                indicator_ledger: IndicatorLedger::new(indicator_configs.clone()),
             },
-        }, //oneminute socket
-        // ThreadWorkerConfig{
-        //     server_url: "ws://localhost:5555".to_string(),
-        //     time_frame: TimeFrame::ThreeMinutes
-        // }, //threeminute socket
-        ThreadWorkerConfig {
-            thread_job_type: ThreadJobType::DataConsumerViaSocket,
-            time_frame: TimeFrame::FiveMinutes,
-            root_system_config: RootSystemConfig {
-                database_instance: db.clone(),
-                hammer_candle_collection: hammer_candle_collection.clone(),
-                hammer_ledger: hammer_ledger.clone(),
-                current_market_state_collection: current_market_state_collection.clone(),
-                orders_collection: orders_collection.clone(),
-                trade_signal_collection: trade_signal_collection.clone(),
-                server_url: "ws://localhost:5556".to_string(),
-                tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo],
-                trade_keeper: trade_keeper.clone(),
-                order_manager: order_manager.clone(),
-                shared_order_ledger: shared_order_ledger.clone(),
-            },
-        }, //fiveminute socket
-        ThreadWorkerConfig {
-            thread_job_type: ThreadJobType::TradeWatcherCron,
-            time_frame: TimeFrame::Infinity,
+        })
+        .collect();
+    let one_minute_worker_config = socket_worker_configs[0].clone(); //validate() guarantees one stream
+
+    //one consumer per higher time frame a strategy trades on, their bars are built by the 1 min socket worker
+    let subscribed_time_frames = StrategyRegistry::from_algo_types(
+        &one_minute_worker_config.root_system_config.tradeable_algo_types,
+        &strategy_configs,
+        &db,
+        StrategyStates::new(),
+    )
+    .time_frames();
+    let mut thread_worker_configs = socket_worker_configs;
+    for time_frame in subscribed_time_frames.into_iter().filter(|time_frame| *time_frame != TimeFrame::OneMinute) {
+        thread_worker_configs.push(ThreadWorkerConfig {
+            thread_job_type: ThreadJobType::DataConsumerViaCandleAggregator,
+            time_frame,
+            stream_symbol: None,
             root_system_config: RootSystemConfig {
-                database_instance: db.clone(),
-                hammer_candle_collection: hammer_candle_collection.clone(),
-                hammer_ledger: hammer_ledger.clone(),
-                current_market_state_collection: current_market_state_collection.clone(),
-                orders_collection: orders_collection.clone(),
-                trade_signal_collection: trade_signal_collection.clone(),
-                server_url: "ws://localhost:5557".to_string(),
-                tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo],
-                trade_keeper: trade_keeper.clone(),
-                order_manager: order_manager.clone(),
-                shared_order_ledger: shared_order_ledger.clone(),
+                server_url: "".to_string(), //bars come from the 1 min sockets
+                indicator_ledger: IndicatorLedger::new(indicator_configs.clone()),
+                ..one_minute_worker_config.root_system_config.clone()
             },
-        }, // "ws://localhost:5556", //fiveminute socket
-           // "ws://localhost:5557", //fifteenminute socket
-    ];
+            ..one_minute_worker_config.clone()
+        });
+    }
+    thread_worker_configs.push(ThreadWorkerConfig {
+        thread_job_type: ThreadJobType::TradeWatcherCron,
+        time_frame: TimeFrame::Infinity,
+        stream_symbol: None,
+        reconnect_config: ingestion_config.reconnect.clone(),
+        candle_broadcaster: candle_broadcaster.clone(),
+        root_system_config: RootSystemConfig {
+            database_instance: db.clone(),
+            current_market_state_collection: current_market_state_collection.clone(),
+            market_state_history: market_state_history.clone(),
+            orders_collection: orders_collection.clone(),
+            trade_signal_collection: trade_signal_collection.clone(),
+            server_url: "".to_string(), //no socket, the square off runs on the wall clock
+            tradeable_algo_types: vec![AlgoTypes::HammerPatternAlgo, AlgoTypes::ShootingStarPatternAlgo],
+            tradeable_symbols: tradeable_symbols.clone(),
+            strategy_configs: strategy_configs.clone(),
+            strategy_states: strategy_states.clone(),
+            trade_keeper: trade_keeper.clone(),
+            order_manager: order_manager.clone(),
+            risk_gate: risk_gate.clone(),
+            position_sizer: position_sizer.clone(),
+            trade_watcher_config: trade_watcher_config.clone(),
+            indicator_ledger: IndicatorLedger::new(indicator_configs.clone()),
+        },
+    });
 
     let tasks = thread_worker_configs
         .into_iter()
         .map(|thread_worker_config| {
             tokio::spawn(async move {
-                if let Err(e) = ingest_data_via_stream(thread_worker_config.clone(), redis_client).await
+                let result = match thread_worker_config.thread_job_type {
+                    ThreadJobType::DataConsumerViaCandleAggregator => {
+                        ingest_data_via_candle_aggregator(thread_worker_config.clone(), redis_client).await
+                    }
+                    ThreadJobType::TradeWatcherCron => run_square_off_cron(thread_worker_config.clone(), redis_client).await,
+                    _ => ingest_data_via_stream(thread_worker_config.clone(), redis_client).await,
+                };
+                if let Err(e) = result
                 {
                     eprintln!(
                         "Error connecting to {:?}: {}",
@@ -185,174 +402,6 @@ async fn main() {
         })
         .collect::<Vec<_>>();
 
-    //START -> Oneminute Socket reading code
-    async fn ingest_data_via_stream(
-        thread_worker_config: ThreadWorkerConfig,
-        redis_client: &Mutex<RedisClient>,
-    ) -> Result<(), Box<dyn Error>> {
-        if thread_worker_config.thread_job_type == ThreadJobType::TradeWatcherCron {
-            Ok(())
-        } else {
-            let RootSystemConfig {
-                database_instance,
-                hammer_candle_collection,
-                hammer_ledger,
-                current_market_state_collection,
-                orders_collection,
-                trade_signal_collection,
-                server_url,
-                tradeable_algo_types,
-                trade_keeper,
-                mut order_manager,
-                shared_order_ledger,
-            } = thread_worker_config.root_system_config;
-
-            let mut raw_stock_ledger = RawStockLedger::new();
-
-
-            let (mut ws_stream, _) = connect_async(Url::parse(&server_url).unwrap())
-                .await
-                .unwrap();
-
-            println!("Connected to WebSocket server: {}", server_url);
-
-            while let Some(msg) = ws_stream.next().await {
-                match msg {
-                    Ok(message) => {
-                        if message.is_text() {
-                            let text = message.to_text().unwrap();
-
-                            let splitted_text = text.split(",").collect::<Vec<&str>>();
-                            // println!("splitted_text: {:?}",splitted_text);
-                            // continue;
-                            let date =
-                                match date_parser::parse_date_in_stock_format(splitted_text[1]) {
-                                    Ok(date) => Some(date),
-                                    Err(e) => {
-                                        println!("Error while parsing date {:?}", e);
-                                        None
-                                    }
-                                };
-
-                            let close = match splitted_text[2].parse::<f32>() {
-                                Ok(close) => Some(close),
-                                Err(e) => {
-                                    println!("Error while parsing close {:?}", e);
-                                    None
-                                }
-                            };
-
-                            let high = match splitted_text[3].parse::<f32>() {
-                                Ok(high) => Some(high),
-                                Err(e) => {
-                                    println!("Error while parsing high {:?}", e);
-                                    None
-                                }
-                            };
-
-                            let low = match splitted_text[4].parse::<f32>() {
-                                Ok(low) => Some(low),
-                                Err(e) => {
-                                    println!("Error while parsing low {:?}", e);
-                                    None
-                                }
-                            };
-
-                            let open = match splitted_text[5].parse::<f32>() {
-                                Ok(open) => Some(open),
-                                Err(e) => {
-                                    println!("Error while parsing open {:?}", e);
-                                    None
-                                }
-                            };
-                            //removing "\"" from the end of the string to parse the volume correctly => &splitted_text[6][0..splitted_text[6].len()-1]
-                            let volume = match &splitted_text[6][0..splitted_text[6].len() - 1]
-                                .parse::<i32>()
-                            {
-                                Ok(volume) => Some(*volume),
-                                Err(e) => {
-                                    println!("Error while parsing volume {:?}", e);
-                                    None
-                                }
-                            };
-
-                            if date.is_none()
-                                || close.is_none()
-                                || high.is_none()
-                                || low.is_none()
-                                || open.is_none()
-                                || volume.is_none()
-                            {
-                                println!("Header or Some of the values are None");
-                                continue;
-                            }
-
-                            let raw_stock = RawStock::new(
-                                "ADANIGREEN".to_owned(),
-                                date.unwrap(),
-                                close.unwrap(),
-                                high.unwrap(),
-                                low.unwrap(),
-                                open.unwrap(),
-                                volume.unwrap(),
-                                thread_worker_config.time_frame.clone(),
-                            );
-
-                            // println!("Received on {} tick: {:?}",thread_worker_config.time_frame, text);
-
-                            raw_stock_ledger.add_raw_stock(raw_stock.clone());
-
-                            match thread_worker_config.time_frame {
-                                TimeFrame::FiveMinutes => {
-                                    CurrentMarketState::calculate_market_state(
-                                        &raw_stock,
-                                        thread_worker_config.time_frame.clone(),
-                                        &current_market_state_collection,
-                                        redis_client.clone(),
-                                        &raw_stock_ledger,
-                                        database_instance.clone(),
-                                    )
-                                    .await;
-    
-                                algo_dispatcher::ingest_raw_stock_data(
-                                    &raw_stock,
-                                    tradeable_algo_types.clone(),
-                                    hammer_ledger.clone(),
-                                    hammer_candle_collection.clone(),
-                                    trade_keeper.clone(),
-                                    trade_signal_collection.clone(),
-                                    order_manager.clone(),
-                                    orders_collection.clone(),
-                                    redis_client.clone(),
-                                    database_instance.clone(),
-                                    shared_order_ledger.clone(),
-                                )
-                                .await;
-                                },
-                                TimeFrame::OneMinute =>{
-                                    monitor_trade::check_for_exit_opportunity(&mut order_manager, raw_stock.clone(), redis_client.clone(), orders_collection.clone(), shared_order_ledger.clone()).await;
-                                    // let temp = shared_order_ledger.lock().unwrap().clone();
-                                    // println!("Shared Data => {:?}", shared_order_ledger.lock().unwrap());
-                                }
-                                _ => ()
-                            }
-
-                            
-                            // if hammer_ledger.fextch_hammer_pattern_ledger().len() > 0 {
-                            //     break;
-                            // }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error while receiving message: {:?}", e);
-                    }
-                }
-            }
-
-            Ok(())
-        }
-    }
-    //END -> Oneminute Socket reading code
     // return;
     futures::future::join_all(tasks).await;
 
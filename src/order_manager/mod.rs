@@ -1,3 +1,7 @@
 pub mod pnl_state;
 pub mod order_dispatcher;
-pub mod trade_signal_keeper;
\ No newline at end of file
+pub mod trade_signal_keeper;
+pub mod risk_gate;
+pub mod position_sizer;
+pub mod order_status;
+pub mod position_book;
\ No newline at end of file
@@ -1,25 +1,38 @@
-use super::trade_signal_keeper::TradeSignal;
-use crate::common::{
-    date_parser::new_current_date_time_in_desired_stock_datetime_format,
-    enums::{AlgoTypes, TradeType},
-    redis_client::RedisClient,
-    utils::{self, order_cache_key_formatter},
+use super::{
+    order_status::{OrderStatus, OrderStatusTransition, OrderTransitionError},
+    pnl_state::{CurrentPnLState, PnLConfiguration},
+    position_book::PositionBook,
+    risk_gate::RiskGate,
+    trade_signal_keeper::TradeSignal,
 };
-use mongodb::{bson::{doc, Document}, options::{FindOneOptions, UpdateOptions}, Collection};
+use crate::{
+    broker::broker::{Broker, BrokerError, BrokerOrder, BrokerOrderRequest, BrokerOrderStatus, BrokerOrderType},
+    common::{
+        date_parser,
+        enums::{AlgoTypes, ExitReason, TimeFrame, TradeType},
+        raw_stock::RawStock,
+        redis_client::RedisClient,
+        utils::{self, current_pnl_state_cache_key_formatter},
+    },
+};
+use mongodb::{bson::{doc, to_bson, Bson, Document}, options::{FindOneOptions, UpdateOptions}, Collection};
 use serde::{Deserialize, Serialize};
 use std::{fmt, sync::{Mutex, Arc}};
 
 // #[derive(Debug, Clone, PartialEq)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredOrder")]
 pub struct Order {
     pub symbol: String,
     pub trade_position_type: TradeType,
     pub trade_algo_type: AlgoTypes,
+    pub strategy_instance: String,
+    pub market_time_frame: TimeFrame,
     pub entry_price: f32,
     pub exit_price: f32,
     pub trade_sl: f32,
     pub trade_target: f32,
-    pub is_trade_open: bool,
+    pub is_trade_open: bool, //kept in sync with status => true while the order is PendingEntry, PartiallyFilled or Open
     pub qty: i32,
     pub total_price: f32,
     pub trade_taken_at: String,
@@ -27,6 +40,117 @@ pub struct Order {
     pub order_id: String,
     pub closing_profit: f32,
     pub is_profitable_trade: bool,
+    pub exit_reason: Option<ExitReason>,
+    pub status: OrderStatus,
+    pub status_history: Vec<OrderStatusTransition>,
+    pub filled_qty: i32,
+    pub initial_sl: f32, //SL of the signal, trade_sl moves with the exit policy
+    pub best_price: f32, //most favourable price since the entry filled
+    pub exited_qty: i32, //booked at partial targets
+    pub partial_exits: Vec<PartialExit>,
+    pub last_price: f32, //last 1 min close seen by the trade watcher, the square off exits there
+    pub is_ambiguous_exit: bool, //the exit candle spanned both SL and target, the intra candle exit policy picked one
+}
+
+//What is read back from Redis and Mongo. Orders saved before the status lifecycle have no status (nor strategy instance / time frame),
+//they are rebuilt from is_trade_open and the exit instead of all loading as PendingEntry
+#[derive(Deserialize)]
+struct StoredOrder {
+    symbol: String,
+    trade_position_type: TradeType,
+    trade_algo_type: AlgoTypes,
+    #[serde(default)]
+    strategy_instance: Option<String>,
+    #[serde(default)]
+    market_time_frame: Option<TimeFrame>,
+    entry_price: f32,
+    exit_price: f32,
+    trade_sl: f32,
+    trade_target: f32,
+    is_trade_open: bool,
+    qty: i32,
+    total_price: f32,
+    trade_taken_at: String,
+    trade_closed_at: String,
+    order_id: String,
+    closing_profit: f32,
+    is_profitable_trade: bool,
+    #[serde(default)]
+    exit_reason: Option<ExitReason>,
+    #[serde(default)]
+    status: Option<OrderStatus>,
+    #[serde(default)]
+    status_history: Vec<OrderStatusTransition>,
+    #[serde(default)]
+    filled_qty: i32,
+    #[serde(default)]
+    initial_sl: f32,
+    #[serde(default)]
+    best_price: f32,
+    #[serde(default)]
+    exited_qty: i32,
+    #[serde(default)]
+    partial_exits: Vec<PartialExit>,
+    #[serde(default)]
+    last_price: f32,
+    #[serde(default)]
+    is_ambiguous_exit: bool,
+}
+
+impl From<StoredOrder> for Order {
+    fn from(stored_order: StoredOrder) -> Order {
+        let status = match (stored_order.status, &stored_order.exit_reason) {
+            (Some(status), _) => status,
+            //legacy orders were placed as filled market orders
+            (None, _) if stored_order.is_trade_open => OrderStatus::Open,
+            (None, Some(exit_reason)) => OrderStatus::from_exit_reason(exit_reason),
+            //legacy exits were only ever at the SL or the target
+            (None, None) if stored_order.closing_profit > 0.0 => OrderStatus::ExitedByTarget,
+            (None, None) => OrderStatus::ExitedBySl,
+        };
+        let filled_qty = if stored_order.filled_qty == 0 && (status.has_position() || status.is_exited()) {
+            stored_order.qty
+        } else {
+            stored_order.filled_qty
+        };
+        Order {
+            symbol: stored_order.symbol,
+            trade_position_type: stored_order.trade_position_type,
+            strategy_instance: stored_order.strategy_instance.unwrap_or_else(|| stored_order.trade_algo_type.to_string()),
+            trade_algo_type: stored_order.trade_algo_type,
+            market_time_frame: stored_order.market_time_frame.unwrap_or(TimeFrame::FiveMinutes), //the only time frame traded before
+            entry_price: stored_order.entry_price,
+            exit_price: stored_order.exit_price,
+            trade_sl: stored_order.trade_sl,
+            trade_target: stored_order.trade_target,
+            is_trade_open: status.is_active(),
+            qty: stored_order.qty,
+            total_price: stored_order.total_price,
+            trade_taken_at: stored_order.trade_taken_at,
+            trade_closed_at: stored_order.trade_closed_at,
+            order_id: stored_order.order_id,
+            closing_profit: stored_order.closing_profit,
+            is_profitable_trade: stored_order.is_profitable_trade,
+            exit_reason: stored_order.exit_reason,
+            status,
+            status_history: stored_order.status_history,
+            filled_qty,
+            initial_sl: if stored_order.initial_sl > 0.0 { stored_order.initial_sl } else { stored_order.trade_sl },
+            best_price: stored_order.best_price,
+            exited_qty: stored_order.exited_qty,
+            partial_exits: stored_order.partial_exits,
+            last_price: stored_order.last_price,
+            is_ambiguous_exit: stored_order.is_ambiguous_exit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialExit {
+    pub qty: i32,
+    pub exit_price: f32,
+    pub exited_at: String,
+    pub profit: f32,
 }
 
 impl Order {
@@ -34,11 +158,13 @@ impl Order {
         symbol: String,
         trade_position_type: TradeType,
         trade_algo_type: AlgoTypes,
+        strategy_instance: String,
+        market_time_frame: TimeFrame,
         entry_price: f32,
         exit_price: f32,
         trade_sl: f32,
         trade_target: f32,
-        is_trade_open: bool,
+        status: OrderStatus,
         qty: i32,
         total_price: f32,
         trade_taken_at: String,
@@ -46,36 +172,220 @@ impl Order {
         order_id: String,
         closing_profit: f32,
         is_profitable_trade: bool,
+        exit_reason: Option<ExitReason>,
     ) -> Order {
         Order {
             symbol,
             trade_position_type,
             trade_algo_type,
+            strategy_instance,
+            market_time_frame,
             entry_price,
             exit_price,
             trade_sl,
             trade_target,
-            is_trade_open,
+            is_trade_open: status.is_active(),
             qty,
             total_price,
+            status_history: vec![OrderStatusTransition::new(status.clone(), trade_taken_at.clone())],
             trade_taken_at,
             trade_closed_at,
             order_id,
             closing_profit,
             is_profitable_trade,
+            exit_reason,
+            status,
+            filled_qty: 0,
+            initial_sl: trade_sl,
+            best_price: 0.0,
+            exited_qty: 0,
+            partial_exits: Vec::new(),
+            last_price: 0.0,
+            is_ambiguous_exit: false,
+        }
+    }
+
+    pub fn from_trade_signal(trade_signal: &TradeSignal, order_id: String) -> Order {
+        Order::new(
+            trade_signal.raw_stock.symbol.clone(),
+            trade_signal.trade_position_type.clone(),
+            trade_signal.trade_algo_type.clone(),
+            trade_signal.strategy_instance.clone(),
+            trade_signal.raw_stock.market_time_frame.clone(),
+            trade_signal.entry_price,
+            0.0,
+            trade_signal.trade_sl,
+            trade_signal.trade_target,
+            OrderStatus::PendingEntry, //rests until the broker fills the entry
+            trade_signal.qty,
+            trade_signal.total_price,
+            trade_signal.raw_stock.date.clone(), //candle time keeps replayed orders on the market clock
+            "".to_string(),
+            order_id,
+            0.0,
+            false,
+            None,
+        )
+    }
+
+    pub fn transition_to(&mut self, status: OrderStatus, transitioned_at: String) -> Result<(), OrderTransitionError> {
+        if !self.status.can_transition_to(&status) {
+            return Err(OrderTransitionError {
+                order_id: self.order_id.clone(),
+                from_status: self.status.clone(),
+                to_status: status,
+            });
+        }
+        self.is_trade_open = status.is_active();
+        self.status = status.clone();
+        self.status_history.push(OrderStatusTransition::new(status, transitioned_at));
+        Ok(())
+    }
+
+    //the entry price becomes the average fill price, a partial fill keeps the order PartiallyFilled
+    pub fn fill_entry(&mut self, filled_qty: i32, average_fill_price: f32, filled_at: String) -> Result<(), OrderTransitionError> {
+        let status = if filled_qty >= self.qty { OrderStatus::Open } else { OrderStatus::PartiallyFilled };
+        self.transition_to(status, filled_at)?;
+        self.filled_qty = filled_qty.min(self.qty);
+        if average_fill_price > 0.0 {
+            self.entry_price = average_fill_price;
+        }
+        self.total_price = self.entry_price * self.filled_qty as f32;
+        if self.best_price <= 0.0 {
+            self.best_price = self.entry_price;
+        }
+        Ok(())
+    }
+
+    //true => the entry moved on at the broker and the order has to be saved
+    pub fn sync_with_broker_order(&mut self, broker_order: &BrokerOrder) -> Result<bool, OrderTransitionError> {
+        if !matches!(self.status, OrderStatus::PendingEntry | OrderStatus::PartiallyFilled) {
+            return Ok(false);
+        }
+        match broker_order.status {
+            BrokerOrderStatus::Filled | BrokerOrderStatus::PartiallyFilled | BrokerOrderStatus::Cancelled
+                if broker_order.filled_qty > self.filled_qty =>
+            {
+                self.fill_entry(broker_order.filled_qty, broker_order.average_fill_price, broker_order.updated_at.clone())?;
+                Ok(true)
+            }
+            BrokerOrderStatus::Cancelled if self.status == OrderStatus::PendingEntry => {
+                self.transition_to(OrderStatus::Cancelled, broker_order.updated_at.clone())?;
+                Ok(true)
+            }
+            BrokerOrderStatus::Rejected => {
+                self.transition_to(OrderStatus::Rejected, broker_order.updated_at.clone())?;
+                Ok(true)
+            }
+            _ => Ok(false),
         }
     }
 
-    pub fn exit_trade(&mut self, exit_price: f32, trade_closed_at: String) -> () {
+    pub fn exit_trade(&mut self, exit_price: f32, trade_closed_at: String, exit_reason: ExitReason) -> Result<(), OrderTransitionError> {
+        self.transition_to(OrderStatus::from_exit_reason(&exit_reason), trade_closed_at.clone())?;
+        //a partially filled entry only exits what was filled, partial targets already booked their part
+        self.qty = self.filled_qty;
+        self.total_price = self.entry_price * self.qty as f32;
         self.exit_price = exit_price;
+        self.exit_reason = Some(exit_reason);
         self.trade_closed_at = trade_closed_at;
-        self.is_trade_open = false;
-        self.closing_profit = if self.trade_position_type == TradeType::Long {
-            (self.exit_price - self.entry_price) * self.qty as f32
+        let partial_exit_profit: f32 = self.partial_exits.iter().map(|partial_exit| partial_exit.profit).sum();
+        self.closing_profit = self.position_profit(exit_price, self.open_qty()) + partial_exit_profit;
+        self.is_profitable_trade = self.closing_profit > 0.0;
+        Ok(())
+    }
+
+    pub fn open_qty(&self) -> i32 {
+        self.filled_qty - self.exited_qty
+    }
+
+    fn position_profit(&self, exit_price: f32, qty: i32) -> f32 {
+        if self.trade_position_type == TradeType::Long {
+            (exit_price - self.entry_price) * qty as f32
+        } else {
+            (self.entry_price - exit_price) * qty as f32
+        }
+    }
+
+    //first transition that put a position on
+    pub fn entry_filled_at(&self) -> Option<&str> {
+        self.status_history
+            .iter()
+            .find(|transition| transition.status.has_position())
+            .map(|transition| transition.transitioned_at.as_str())
+    }
+
+    //entry price moved by move_percentage in the direction of the trade
+    pub fn price_after_move(&self, move_percentage: f32) -> f32 {
+        if self.trade_position_type == TradeType::Long {
+            self.entry_price * (1.0 + move_percentage / 100.0)
+        } else {
+            self.entry_price * (1.0 - move_percentage / 100.0)
+        }
+    }
+
+    pub fn favourable_move_percentage(&self) -> f32 {
+        if self.entry_price <= 0.0 || self.best_price <= 0.0 {
+            return 0.0;
+        }
+        if self.trade_position_type == TradeType::Long {
+            (self.best_price - self.entry_price) / self.entry_price * 100.0
+        } else {
+            (self.entry_price - self.best_price) / self.entry_price * 100.0
+        }
+    }
+
+    //true => the candle made a new high (long) / low (short) since the entry
+    pub fn update_best_price(&mut self, stock: &RawStock) -> bool {
+        let best_price = if self.trade_position_type == TradeType::Long {
+            self.best_price.max(stock.high)
+        } else if self.best_price <= 0.0 {
+            stock.low
         } else {
-            (self.entry_price - self.exit_price) * self.qty as f32
+            self.best_price.min(stock.low)
         };
-        self.is_profitable_trade = self.closing_profit > 0.0;
+        let is_updated = best_price != self.best_price;
+        self.best_price = best_price;
+        is_updated
+    }
+
+    //a stop only ever tightens, true => trade_sl moved
+    pub fn move_stop(&mut self, trade_sl: f32) -> bool {
+        let is_tighter = if self.trade_position_type == TradeType::Long {
+            trade_sl > self.trade_sl
+        } else {
+            trade_sl < self.trade_sl
+        };
+        if is_tighter {
+            self.trade_sl = trade_sl;
+        }
+        is_tighter
+    }
+
+    //partial exits can never square off more than the entry filled
+    pub fn is_exited_qty_valid(&self) -> bool {
+        self.exited_qty >= 0 && self.exited_qty <= self.filled_qty && self.filled_qty <= self.qty
+    }
+
+    pub fn book_partial_exit(&mut self, qty: i32, exit_price: f32, exited_at: String) {
+        let qty = qty.min(self.open_qty());
+        if qty <= 0 {
+            return;
+        }
+        self.exited_qty += qty;
+        self.partial_exits.push(PartialExit {
+            qty,
+            exit_price,
+            exited_at,
+            profit: self.position_profit(exit_price, qty),
+        });
+    }
+
+    //drops the partial exits booked after the first partial_exit_count, their qty is open again
+    pub fn rollback_partial_exits(&mut self, partial_exit_count: usize) {
+        for partial_exit in self.partial_exits.drain(partial_exit_count.min(self.partial_exits.len())..) {
+            self.exited_qty -= partial_exit.qty;
+        }
     }
 
     pub fn to_document(&self) -> Document {
@@ -83,6 +393,8 @@ impl Order {
             "symbol": self.symbol.clone(),
             "trade_position_type": self.trade_position_type.clone().to_string(),
             "trade_algo_type": self.trade_algo_type.clone().to_string(),
+            "strategy_instance": self.strategy_instance.clone(),
+            "market_time_frame": self.market_time_frame.clone().to_string(),
             "entry_price": self.entry_price.clone(),
             "exit_price": self.exit_price.clone(),
             "trade_sl": self.trade_sl.clone(),
@@ -95,6 +407,16 @@ impl Order {
             "order_id": self.order_id.clone(),
             "closing_profit": self.closing_profit.clone(),
             "is_profitable_trade": self.is_profitable_trade.clone(),
+            "exit_reason": self.exit_reason.as_ref().map(|exit_reason| exit_reason.to_string()),
+            "status": self.status.to_string(),
+            "status_history": to_bson(&self.status_history).unwrap_or(Bson::Array(Vec::new())),
+            "filled_qty": self.filled_qty,
+            "initial_sl": self.initial_sl,
+            "best_price": self.best_price,
+            "exited_qty": self.exited_qty,
+            "partial_exits": to_bson(&self.partial_exits).unwrap_or(Bson::Array(Vec::new())),
+            "last_price": self.last_price,
+            "is_ambiguous_exit": self.is_ambiguous_exit,
         }
     }
 }
@@ -110,33 +432,51 @@ impl fmt::Display for Order {
 
 #[derive(Debug, Clone)]
 pub struct OrderManager {
-    orders: Vec<Order>,
+    pub position_book: PositionBook,
+    pub pnl_configuration: PnLConfiguration,
+    pub current_pnl_state_collection: Collection<CurrentPnLState>,
+    pub broker: Arc<dyn Broker>,
 }
 
 impl OrderManager {
-    pub fn new() -> OrderManager {
-        OrderManager { orders: Vec::new() }
+    pub const PNL_STATE_COLLECTION_NAME: &'static str = "current_pnl_states";
+
+    pub fn new(pnl_configuration: PnLConfiguration, current_pnl_state_collection: Collection<CurrentPnLState>, broker: Arc<dyn Broker>, position_book: PositionBook) -> OrderManager {
+        OrderManager {
+            position_book,
+            pnl_configuration,
+            current_pnl_state_collection,
+            broker,
+        }
     }
 
     pub async fn check_and_dispatch_order(
         &mut self,
         trade_signal: TradeSignal,
+        risk_gate: &RiskGate,
         redis_client: &Mutex<RedisClient>,
         order_collection: Collection<Order>,
-        shared_order_ledger: Arc<Mutex<Vec<Order>>>
     ) -> () {
         let order_cache_key = utils::order_cache_key_formatter(
             &trade_signal.raw_stock.symbol,
-            &trade_signal.trade_algo_type,
+            &trade_signal.strategy_instance,
         );
+        let symbol = trade_signal.raw_stock.symbol.clone();
+        let strategy_instance = trade_signal.strategy_instance.clone();
+        //held until the order is in the book => a second signal can not place a duplicate broker order meanwhile
+        if !self.position_book.reserve_entry(&symbol, &strategy_instance) {
+            return;
+        }
         let order_exists = OrderManager::check_if_order_exists(
             order_cache_key.as_str(),
+            &trade_signal,
             redis_client,
             &order_collection,
         )
         .await;
 
         if order_exists {
+            self.position_book.release_entry(&symbol, &strategy_instance);
             // println!(
             //     "Order already exists for {} with algo type {}",
             //     trade_signal.raw_stock.symbol,
@@ -144,28 +484,43 @@ impl OrderManager {
             // );
             ()
         } else {
-            let order = Order::new(
+            let trade_date_only = date_parser::return_only_date_from_datetime(trade_signal.raw_stock.date.as_str());
+            let current_pnl_state = self.current_pnl_state(trade_date_only.as_str(), redis_client).await;
+            if !risk_gate.approve_trade_signal(&trade_signal, &current_pnl_state).await {
+                self.position_book.release_entry(&symbol, &strategy_instance);
+                return;
+            }
+
+            //entries rest at the broker as limit orders on the signal's entry price
+            let order_request = BrokerOrderRequest::new(
                 trade_signal.raw_stock.symbol.clone(),
-                trade_signal.trade_position_type,
-                trade_signal.trade_algo_type.clone(),
-                trade_signal.entry_price,
-                0.0,
-                trade_signal.trade_sl,
-                trade_signal.trade_target,
-                true,
+                trade_signal.trade_position_type.clone(),
+                BrokerOrderType::Limit,
                 trade_signal.qty,
-                trade_signal.total_price,
-                trade_signal.trade_signal_requested_at,
-                "".to_string(),
-                order_cache_key_formatter(
-                    trade_signal.raw_stock.symbol.as_str(),
-                    &trade_signal.trade_algo_type,
-                ), //This is the order id which will be generated by the Zerodha API once the order is placed
-                0.0,
-                false,
+                Some(trade_signal.entry_price),
+                order_cache_key.clone(),
+            );
+            let broker_order = match self.broker.place_order(order_request).await {
+                Ok(broker_order) => broker_order,
+                Err(e) => {
+                    println!("Error while placing the order for {} with {} => {}", order_cache_key, self.broker.name(), e);
+                    self.position_book.release_entry(&symbol, &strategy_instance);
+                    return;
+                }
+            };
+            println!("Order placed with {} => {} {}", self.broker.name(), broker_order.broker_order_id, broker_order.status);
+            //the broker holds the order now => it counts towards the day's trades even if saving it fails below
+            self.update_pnl_state(trade_date_only.as_str(), redis_client, |current_pnl_state| current_pnl_state.apply_order_opened())
+                .await;
+
+            let mut order = Order::from_trade_signal(
+                &trade_signal,
+                broker_order.broker_order_id.clone(), //order id generated by the broker
             );
+            if let Err(e) = order.sync_with_broker_order(&broker_order) {
+                println!("Error while applying the broker state to {} => {}", order.order_id, e);
+            }
 
-            //TODO:: add logic to call the Zerodha API to place the order
             match order_collection.insert_one(order.clone(), None).await {
                 Ok(_) => {
                     println!("Order added to the database");
@@ -186,24 +541,27 @@ impl OrderManager {
                     println!("Error while adding order to the database {}", e);
                 }
             }
-            // self.orders.push(order.clone());
-            shared_order_ledger.lock().unwrap().push(order.clone());
+            //the broker order exists either way => the book has to watch it, the reservation keeps it from being a duplicate
+            if !self.position_book.open_order(order.clone()) {
+                println!("Order {} duplicates an open order in the position book", order.order_id);
+            }
             ()
         }
     }
 
-    pub fn get_orders(&self) -> &Vec<Order> {
-        &self.orders
-    }
-
     async fn check_if_order_exists(
         cache_key: &str,
+        trade_signal: &TradeSignal,
         redis_client: &Mutex<RedisClient>,
         order_collection: &Collection<Order>,
     ) -> bool {
         let mut order_exists = match redis_client.lock().unwrap().get_data(cache_key) {
             Ok(data) => {
-                true
+                //closed orders stay in the cache, only an open one should block a new entry
+                match serde_json::from_str::<Order>(data.as_str()) {
+                    Ok(cached_order) => cached_order.is_trade_open,
+                    Err(_) => true,
+                }
             }
             Err(e) => {
                 println!(
@@ -220,7 +578,11 @@ impl OrderManager {
             return order_exists;
         }
 
-        let filter = doc! {"cache_key": cache_key.clone() };
+        let filter = doc! {
+            "symbol": trade_signal.raw_stock.symbol.clone(),
+            "strategy_instance": trade_signal.strategy_instance.clone(),
+            "is_trade_open": true
+        };
         let options: FindOneOptions = FindOneOptions::builder().build();
         order_exists = match order_collection.find_one(filter, options).await {
             Ok(Some(order)) => {
@@ -236,7 +598,7 @@ impl OrderManager {
         order_exists
     }
 
-    pub async fn exit_and_update_order(&mut self, order: &Order, exit_price: f32, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) -> Option<Order> {
+    pub async fn exit_and_update_order(&mut self, order: &Order, exit_price: f32, trade_closed_at: String, exit_reason: ExitReason, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) -> Option<Order> {
         // for (index, order_in_orders) in self.orders.iter().enumerate() {
         //     if order_in_orders.order_id == order.order_id {
         //         order_index = index;
@@ -244,52 +606,147 @@ impl OrderManager {
         //     }
         // }
 
-        //TODO: call the Zerodha API to exit the order
+        //the watcher and the square off can race for the same order, only one of them exits it
+        if !self.position_book.begin_exit(&order.order_id) {
+            println!("Order {} is already closed or being exited", order.order_id);
+            return None;
+        }
+        //the transition is checked before the broker position is touched => a rejected exit leaves it open
+        let mut new_order = order.clone();
+        if let Err(e) = new_order.exit_trade(exit_price, trade_closed_at, exit_reason.clone()) {
+            println!("Error while exiting the order => {}", e);
+            self.position_book.abort_exit(&order.order_id);
+            return None;
+        }
+        //the broker position is not known to be flat => the order stays open and is exited again on the next candle
+        if let Err(e) = self.close_broker_position(order).await {
+            println!("Error while closing the broker position of {} => {}", order.order_id, e);
+            self.position_book.abort_exit(&order.order_id);
+            return None;
+        }
+        OrderManager::save_order(&new_order, redis_client, order_collection).await;
+        self.position_book.update_order(new_order.clone());
 
-        let (closing_profit, is_profitable_trade) =
-            OrderManager::calculate_profit(order.clone(), exit_price);
+        //the PnL state belongs to the trading day the order was taken on
+        let trade_date_only = date_parser::return_only_date_from_datetime(new_order.trade_taken_at.as_str());
+        let closing_profit = new_order.closing_profit;
+        self.update_pnl_state(trade_date_only.as_str(), redis_client, |current_pnl_state| {
+            current_pnl_state.apply_order_closed(closing_profit, &exit_reason)
+        })
+        .await;
 
-        let new_order = Order::new(
-            order.symbol.clone(),
-            order.trade_position_type.clone(),
-            order.trade_algo_type.clone(),
-            order.entry_price.clone(),
-            exit_price,
-            order.trade_sl.clone(),
-            order.trade_target.clone(),
-            false,
-            order.qty.clone(),
-            order.total_price.clone(),
-            order.trade_taken_at.clone(),
-            new_current_date_time_in_desired_stock_datetime_format(),
-            order.order_id.clone(),
-            closing_profit,
-            is_profitable_trade,
-        );
+        Some(new_order)
+    }
 
-        let order_cache_key =
-            utils::order_cache_key_formatter(new_order.symbol.as_str(), &new_order.trade_algo_type);
+    //the book's copy of the day first => an update that is not saved yet is never read back over
+    async fn current_pnl_state(&self, trade_date_only: &str, redis_client: &Mutex<RedisClient>) -> CurrentPnLState {
+        match self.position_book.pnl_state(current_pnl_state_cache_key_formatter(trade_date_only).as_str()) {
+            Some(current_pnl_state) => current_pnl_state,
+            None => CurrentPnLState::fetch_or_create(trade_date_only, &self.pnl_configuration, &self.current_pnl_state_collection, redis_client).await,
+        }
+    }
 
+    //the dispatcher and the watcher both change the day's PnL => the change goes through the position book instead of a read, modify and save of their own
+    async fn update_pnl_state(&self, trade_date_only: &str, redis_client: &Mutex<RedisClient>, update: impl FnOnce(&mut CurrentPnLState)) {
+        let loaded_pnl_state = self.current_pnl_state(trade_date_only, redis_client).await;
+        let current_pnl_state = self.position_book.update_pnl_state(loaded_pnl_state, update);
+        self.position_book
+            .save_pnl_state(current_pnl_state.cache_key.as_str(), &self.current_pnl_state_collection, redis_client)
+            .await;
+    }
 
-        // let redis_client = RedisClient::get_instance();
-        let filter = doc! {"order_id": order_cache_key.clone() };
+    //pending and partially filled entries follow the broker, Some => the order changed and was saved
+    pub async fn refresh_entry_status(&mut self, order: &Order, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) -> Option<Order> {
+        let broker_order = match self.broker.order_status(order.order_id.clone()).await {
+            Ok(broker_order) => broker_order,
+            Err(e) => {
+                println!("Error while fetching the entry order {} from {} => {}", order.order_id, self.broker.name(), e);
+                return None;
+            }
+        };
+        let mut new_order = order.clone();
+        match new_order.sync_with_broker_order(&broker_order) {
+            Ok(true) => {
+                println!("Order {} moved to {}", new_order.order_id, new_order.status);
+                OrderManager::save_order(&new_order, redis_client, order_collection).await;
+                self.position_book.update_order(new_order.clone());
+                Some(new_order)
+            }
+            Ok(false) => None,
+            Err(e) => {
+                println!("Error while applying the broker state to {} => {}", order.order_id, e);
+                None
+            }
+        }
+    }
+
+    //unfilled in time => the broker order is cancelled, whatever got filled meanwhile stays as a position.
+    //None without a confirmed cancel => the entry may still fill, it stays pending and is expired again on the next candle
+    pub async fn expire_pending_entry(&mut self, order: &Order, expired_at: String, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) -> Option<Order> {
+        let broker_order = self.cancel_expired_entry(order).await?;
+
+        let mut new_order = order.clone();
+        let transition = if broker_order.filled_qty > 0 {
+            new_order.sync_with_broker_order(&broker_order).map(|_| ())
+        } else {
+            new_order.transition_to(OrderStatus::Expired, expired_at)
+        };
+        match transition {
+            Ok(_) => {
+                println!("Pending entry {} => {}", new_order.order_id, new_order.status);
+                OrderManager::save_order(&new_order, redis_client, order_collection).await;
+                self.position_book.update_order(new_order.clone());
+                Some(new_order)
+            }
+            Err(e) => {
+                println!("Error while expiring the entry => {}", e);
+                None
+            }
+        }
+    }
+
+    //the exit policy moved the stop or booked partial targets => the booked qty is squared off and the order saved
+    pub async fn update_managed_order(&mut self, order: &Order, previous_partial_exit_count: usize, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) {
+        if !order.is_exited_qty_valid() {
+            println!("Error while updating {} => exited qty {} is above the filled qty {} of {}", order.order_id, order.exited_qty, order.filled_qty, order.qty);
+            return;
+        }
+        let mut managed_order = order.clone();
+        self.place_partial_exit_orders(&mut managed_order, previous_partial_exit_count).await;
+        OrderManager::save_order(&managed_order, redis_client, order_collection).await;
+        self.position_book.update_order(managed_order);
+    }
+
+    //a partial exit only stays booked once the broker accepted its exit order.
+    //On the first rejection it and every later one are rolled back, the next candle books them again
+    async fn place_partial_exit_orders(&self, order: &mut Order, previous_partial_exit_count: usize) {
+        for partial_exit_index in previous_partial_exit_count..order.partial_exits.len() {
+            let partial_exit = order.partial_exits[partial_exit_index].clone();
+            if let Err(e) = self.place_exit_order(order, partial_exit.qty).await {
+                println!("Error while placing the exit order for {} => {}, partial target not booked", order.order_id, e);
+                order.rollback_partial_exits(partial_exit_index);
+                return;
+            }
+            println!("Partial target booked for {} => {} at {}", order.order_id, partial_exit.qty, partial_exit.exit_price);
+        }
+    }
+
+    async fn save_order(order: &Order, redis_client: &Mutex<RedisClient>, order_collection: &Collection<Order>) {
+        let order_cache_key = utils::order_cache_key_formatter(order.symbol.as_str(), &order.strategy_instance);
+
+        let filter = doc! {"order_id": order.order_id.clone()};
         let options = UpdateOptions::builder().build();
-        let order_document = doc!{"$set":new_order.clone().to_document()};
-        match order_collection.update_one(
-                filter,
-                order_document,            
-                options,
-            ).await {
-                Ok(result) => {
-                    println!("Successfully updated the order in MongoDB {:?}",result);
-                },
-                Err(e) => {
-                    println!("Error in MongoDB while updating the order: {:?} error {:?}", order.order_id,e);
-                }
+        let order_document = doc! {"$set": order.to_document()};
+        match order_collection.update_one(filter, order_document, options).await {
+            Ok(result) => {
+                println!("Successfully updated the order in MongoDB {:?}", result);
+            }
+            Err(e) => {
+                println!("Error in MongoDB while updating the order: {:?} error {:?}", order.order_id, e);
+            }
         }
-            
 
-        match redis_client.lock().unwrap().set_data(order_cache_key.as_str(), serde_json::to_string(&new_order.clone()).unwrap().as_str()) {
+        match redis_client.lock().unwrap().set_data(order_cache_key.as_str(), serde_json::to_string(order).unwrap().as_str()) {
             Ok(_) => {
                 println!("Order updated in Redis for order_id => {}", order_cache_key);
             }
@@ -297,17 +754,305 @@ impl OrderManager {
                 println!("Not able to update/delete the order_id {:?} with Error {:?}", order_cache_key, e);
             }
         }
-
-        Some(new_order)
-        //calculate current PnL
     }
 
-    fn calculate_profit(order: Order, exit_price: f32) -> (f32, bool) {
-        let profit = if order.trade_position_type == TradeType::Long {
-            (exit_price - order.entry_price) * order.qty as f32
-        } else {
-            (order.entry_price - exit_price) * order.qty as f32
+    //Some => the broker confirmed the entry is cancelled or filled, None => its state is unknown or it is still resting
+    async fn cancel_expired_entry(&self, order: &Order) -> Option<BrokerOrder> {
+        let broker_order = match self.broker.cancel_order(order.order_id.clone()).await {
+            Ok(broker_order) => broker_order,
+            Err(e) => {
+                println!("Error while cancelling the expired entry {} => {}", order.order_id, e);
+                match self.broker.order_status(order.order_id.clone()).await {
+                    Ok(broker_order) => broker_order,
+                    Err(e) => {
+                        println!("Error while fetching the expired entry {} from {} => {}", order.order_id, self.broker.name(), e);
+                        return None;
+                    }
+                }
+            }
         };
-        (profit, profit > 0.0)
+        if broker_order.filled_qty == 0 && broker_order.status != BrokerOrderStatus::Cancelled {
+            println!("Expired entry {} is still {} at {}", order.order_id, broker_order.status, self.broker.name());
+            return None;
+        }
+        Some(broker_order)
+    }
+
+    //the entry may still rest at the broker => cancel what is pending and square off what was filled.
+    //Err => the broker side is unknown or still open, the order must not be closed in the book
+    async fn close_broker_position(&self, order: &Order) -> Result<(), BrokerError> {
+        let mut entry_order = self.broker.order_status(order.order_id.clone()).await?;
+        if !entry_order.is_complete() {
+            entry_order = self.broker.cancel_order(order.order_id.clone()).await?;
+        }
+        //partial targets already squared off their part
+        let open_qty = entry_order.filled_qty - order.exited_qty;
+        if open_qty <= 0 {
+            return Ok(());
+        }
+        self.place_exit_order(order, open_qty).await
+    }
+
+    async fn place_exit_order(&self, order: &Order, qty: i32) -> Result<(), BrokerError> {
+        let exit_position_type = if order.trade_position_type == TradeType::Long { TradeType::Short } else { TradeType::Long };
+        let exit_request = BrokerOrderRequest::new(
+            order.symbol.clone(),
+            exit_position_type,
+            BrokerOrderType::Market,
+            qty,
+            None,
+            utils::order_cache_key_formatter(order.symbol.as_str(), &order.strategy_instance),
+        );
+        let exit_order = self.broker.place_order(exit_request).await?;
+        println!("Exit order placed with {} => {} {}", self.broker.name(), exit_order.broker_order_id, exit_order.status);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //shape of an order saved before the status lifecycle existed
+    fn legacy_order_json(is_trade_open: bool, closing_profit: f32) -> String {
+        format!(
+            r#"{{"symbol":"ADANIGREEN","trade_position_type":"Long","trade_algo_type":"HammerPatternAlgo","entry_price":100.0,"exit_price":0.0,"trade_sl":98.0,"trade_target":104.0,"is_trade_open":{},"qty":10,"total_price":1000.0,"trade_taken_at":"2023-08-10 09:30:00+0530","trade_closed_at":"","order_id":"ORDER_ADANIGREEN_HammerPatternAlgo","closing_profit":{},"is_profitable_trade":{}}}"#,
+            is_trade_open,
+            closing_profit,
+            closing_profit > 0.0
+        )
+    }
+
+    #[test]
+    fn legacy_open_order_loads_as_open_with_its_qty_filled() {
+        let order: Order = serde_json::from_str(legacy_order_json(true, 0.0).as_str()).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        assert!(order.is_trade_open);
+        assert_eq!(order.filled_qty, 10);
+        assert_eq!(order.strategy_instance, "HammerPatternAlgo");
+        assert_eq!(order.market_time_frame, TimeFrame::FiveMinutes);
+        assert_eq!(order.initial_sl, 98.0);
+        assert_eq!(order.last_price, 0.0);
+    }
+
+    #[test]
+    fn legacy_closed_orders_load_as_exited() {
+        let target_order: Order = serde_json::from_str(legacy_order_json(false, 40.0).as_str()).unwrap();
+        assert_eq!(target_order.status, OrderStatus::ExitedByTarget);
+        assert!(!target_order.is_trade_open);
+
+        let sl_order: Order = serde_json::from_str(legacy_order_json(false, -20.0).as_str()).unwrap();
+        assert_eq!(sl_order.status, OrderStatus::ExitedBySl);
+    }
+
+    #[test]
+    fn legacy_closed_order_with_exit_reason_uses_it() {
+        let order_json = legacy_order_json(false, 5.0).replace(r#""is_trade_open""#, r#""exit_reason":"TimeExit","is_trade_open""#);
+        let order: Order = serde_json::from_str(order_json.as_str()).unwrap();
+        assert_eq!(order.status, OrderStatus::ExitedByTime);
+    }
+
+    #[test]
+    fn legacy_open_order_with_last_price_keeps_it() {
+        let order_json = legacy_order_json(true, 0.0).replace(r#""is_trade_open""#, r#""last_price":101.5,"is_trade_open""#);
+        let order: Order = serde_json::from_str(order_json.as_str()).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.last_price, 101.5);
+    }
+
+    //the document is what the order updates $set in Mongo, every field has to survive a restart
+    #[test]
+    fn order_document_round_trips() {
+        let mut order: Order = serde_json::from_str(legacy_order_json(true, 0.0).as_str()).unwrap();
+        order.last_price = 101.5;
+        order.best_price = 102.0;
+        let order_document = order.to_document();
+        assert_eq!(order_document.get_f64("last_price"), Ok(101.5));
+        let loaded_order: Order = mongodb::bson::from_document(order_document).unwrap();
+        assert_eq!(loaded_order, order);
+    }
+
+    #[test]
+    fn stored_status_round_trips() {
+        let mut order: Order = serde_json::from_str(legacy_order_json(true, 0.0).as_str()).unwrap();
+        order.transition_to(OrderStatus::ExitedBySl, "2023-08-10 09:40:00+0530".to_string()).unwrap();
+        let loaded_order: Order = serde_json::from_str(serde_json::to_string(&order).unwrap().as_str()).unwrap();
+        assert_eq!(loaded_order, order);
+    }
+
+    //answers every call with the queued result of its kind, an empty queue => connection error
+    #[derive(Debug, Default)]
+    struct ScriptedBroker {
+        cancel_results: std::sync::Mutex<Vec<Result<BrokerOrder, BrokerError>>>,
+        status_results: std::sync::Mutex<Vec<Result<BrokerOrder, BrokerError>>>,
+        place_results: std::sync::Mutex<Vec<Result<BrokerOrder, BrokerError>>>,
+        placed_qtys: std::sync::Mutex<Vec<i32>>,
+    }
+
+    impl ScriptedBroker {
+        fn next_result(results: &std::sync::Mutex<Vec<Result<BrokerOrder, BrokerError>>>) -> Result<BrokerOrder, BrokerError> {
+            let mut results = results.lock().unwrap();
+            if results.is_empty() {
+                return Err(BrokerError::Connection("no scripted result".to_string()));
+            }
+            results.remove(0)
+        }
+    }
+
+    impl Broker for ScriptedBroker {
+        fn name(&self) -> String {
+            "ScriptedBroker".to_string()
+        }
+
+        fn place_order(&self, order_request: BrokerOrderRequest) -> futures::future::BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+            self.placed_qtys.lock().unwrap().push(order_request.qty);
+            Box::pin(async move { ScriptedBroker::next_result(&self.place_results) })
+        }
+
+        fn modify_order(&self, broker_order_id: String, _qty: Option<i32>, _limit_price: Option<f32>) -> futures::future::BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+            Box::pin(async move { Err(BrokerError::OrderNotModifiable(broker_order_id)) })
+        }
+
+        fn cancel_order(&self, _broker_order_id: String) -> futures::future::BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+            Box::pin(async move { ScriptedBroker::next_result(&self.cancel_results) })
+        }
+
+        fn order_status(&self, _broker_order_id: String) -> futures::future::BoxFuture<'_, Result<BrokerOrder, BrokerError>> {
+            Box::pin(async move { ScriptedBroker::next_result(&self.status_results) })
+        }
+
+        fn positions(&self) -> futures::future::BoxFuture<'_, Result<Vec<crate::broker::broker::BrokerPosition>, BrokerError>> {
+            Box::pin(async move { Ok(Vec::new()) })
+        }
+    }
+
+    fn broker_order(status: BrokerOrderStatus, filled_qty: i32) -> BrokerOrder {
+        BrokerOrder {
+            broker_order_id: "PAPER_1".to_string(),
+            symbol: "ADANIGREEN".to_string(),
+            trade_position_type: TradeType::Long,
+            order_type: BrokerOrderType::Limit,
+            qty: 10,
+            limit_price: Some(100.0),
+            tag: "ORDER_ADANIGREEN_HammerPatternAlgo".to_string(),
+            status,
+            filled_qty,
+            average_fill_price: 100.0,
+            commission: 0.0,
+            placed_at: "2023-08-10 09:30:00+0530".to_string(),
+            updated_at: "2023-08-10 09:30:00+0530".to_string(),
+        }
+    }
+
+    //the collection is never reached by the broker paths under test, the client only connects on the first operation
+    async fn order_manager(broker: Arc<ScriptedBroker>) -> OrderManager {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap();
+        OrderManager::new(
+            PnLConfiguration::new_default_config(vec!["ADANIGREEN".to_string()]),
+            mongo_client.database("algo_trading_test").collection(OrderManager::PNL_STATE_COLLECTION_NAME),
+            broker,
+            PositionBook::new(),
+        )
+    }
+
+    fn open_order() -> Order {
+        let mut order: Order = serde_json::from_str(legacy_order_json(true, 0.0).as_str()).unwrap();
+        order.order_id = "PAPER_1".to_string();
+        order
+    }
+
+    #[tokio::test]
+    async fn broker_failures_keep_the_position_open() {
+        //status unknown
+        let broker = Arc::new(ScriptedBroker::default());
+        assert!(order_manager(broker.clone()).await.close_broker_position(&open_order()).await.is_err());
+        assert!(broker.placed_qtys.lock().unwrap().is_empty());
+
+        //the resting entry could not be cancelled
+        let broker = Arc::new(ScriptedBroker::default());
+        broker.status_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::PartiallyFilled, 4)));
+        assert!(order_manager(broker.clone()).await.close_broker_position(&open_order()).await.is_err());
+        assert!(broker.placed_qtys.lock().unwrap().is_empty());
+
+        //the exit order was not placed
+        let broker = Arc::new(ScriptedBroker::default());
+        broker.status_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Filled, 10)));
+        assert!(order_manager(broker.clone()).await.close_broker_position(&open_order()).await.is_err());
+        assert_eq!(*broker.placed_qtys.lock().unwrap(), vec![10]);
+    }
+
+    #[tokio::test]
+    async fn filled_qty_left_after_partial_targets_is_squared_off() {
+        let broker = Arc::new(ScriptedBroker::default());
+        broker.status_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Open, 6)));
+        broker.cancel_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Cancelled, 6)));
+        broker.place_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Filled, 4)));
+        let mut order = open_order();
+        order.exited_qty = 2;
+        assert_eq!(order_manager(broker.clone()).await.close_broker_position(&order).await, Ok(()));
+        assert_eq!(*broker.placed_qtys.lock().unwrap(), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn expired_entry_is_only_confirmed_by_the_broker() {
+        let cases = vec![
+            //cancel, status after a failed cancel, confirmed filled qty
+            (Ok(broker_order(BrokerOrderStatus::Cancelled, 0)), None, Some(0)),
+            (Ok(broker_order(BrokerOrderStatus::Cancelled, 3)), None, Some(3)),
+            (Err(BrokerError::OrderNotModifiable("PAPER_1".to_string())), Some(Ok(broker_order(BrokerOrderStatus::Filled, 10))), Some(10)),
+            (Err(BrokerError::Connection("timeout".to_string())), Some(Ok(broker_order(BrokerOrderStatus::Cancelled, 0))), Some(0)),
+            (Err(BrokerError::Connection("timeout".to_string())), Some(Err(BrokerError::Connection("timeout".to_string()))), None),
+            (Err(BrokerError::Connection("timeout".to_string())), Some(Ok(broker_order(BrokerOrderStatus::Open, 0))), None),
+        ];
+        for (cancel_result, status_result, expected_filled_qty) in cases {
+            let broker = Arc::new(ScriptedBroker::default());
+            broker.cancel_results.lock().unwrap().push(cancel_result.clone());
+            if let Some(status_result) = status_result.clone() {
+                broker.status_results.lock().unwrap().push(status_result);
+            }
+            let confirmed_order = order_manager(broker).await.cancel_expired_entry(&open_order()).await;
+            assert_eq!(
+                confirmed_order.map(|broker_order| broker_order.filled_qty),
+                expected_filled_qty,
+                "cancel {:?} then status {:?}",
+                cancel_result,
+                status_result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn partial_exits_are_only_booked_once_the_broker_accepts_them() {
+        let mut order = open_order();
+        order.book_partial_exit(5, 101.0, "2023-08-10 09:45:00+0530".to_string());
+        order.book_partial_exit(3, 102.0, "2023-08-10 09:45:00+0530".to_string());
+        order.book_partial_exit(1, 103.0, "2023-08-10 09:45:00+0530".to_string());
+        assert_eq!(order.exited_qty, 9);
+
+        //the first exit order is accepted, the second is rejected => the second and third are rolled back
+        let broker = Arc::new(ScriptedBroker::default());
+        broker.place_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Filled, 5)));
+        let mut managed_order = order.clone();
+        order_manager(broker.clone()).await.place_partial_exit_orders(&mut managed_order, 0).await;
+        assert_eq!(*broker.placed_qtys.lock().unwrap(), vec![5, 3]);
+        assert_eq!(managed_order.partial_exits.len(), 1);
+        assert_eq!(managed_order.exited_qty, 5);
+        assert_eq!(managed_order.open_qty(), 5);
+
+        //partial exits booked on earlier candles are not placed again
+        let broker = Arc::new(ScriptedBroker::default());
+        broker.place_results.lock().unwrap().push(Ok(broker_order(BrokerOrderStatus::Filled, 1)));
+        let mut managed_order = order.clone();
+        order_manager(broker.clone()).await.place_partial_exit_orders(&mut managed_order, 2).await;
+        assert_eq!(*broker.placed_qtys.lock().unwrap(), vec![1]);
+        assert_eq!(managed_order, order);
+
+        //nothing accepted => the order is back to its previous state
+        let broker = Arc::new(ScriptedBroker::default());
+        let mut managed_order = order.clone();
+        order_manager(broker.clone()).await.place_partial_exit_orders(&mut managed_order, 0).await;
+        assert_eq!(*broker.placed_qtys.lock().unwrap(), vec![5]);
+        assert!(managed_order.partial_exits.is_empty());
+        assert_eq!(managed_order.exited_qty, 0);
     }
 }
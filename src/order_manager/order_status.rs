@@ -0,0 +1,174 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::enums::ExitReason;
+
+//Lifecycle of an Order:
+//PendingEntry -> PartiallyFilled -> Open -> ExitedBySl / ExitedByTarget / ExitedByTime / SquaredOff
+//PendingEntry can also end as Rejected, Cancelled or Expired (never filled in time), a partially filled entry exits with the filled qty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum OrderStatus {
+    #[default]
+    PendingEntry,
+    PartiallyFilled,
+    Open,
+    Rejected,
+    Cancelled,
+    Expired,
+    ExitedBySl,
+    ExitedByTarget,
+    ExitedByTime,
+    SquaredOff, //closed at the intraday cutoff
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl OrderStatus {
+    pub fn from_exit_reason(exit_reason: &ExitReason) -> OrderStatus {
+        match exit_reason {
+            ExitReason::TargetHit => OrderStatus::ExitedByTarget,
+            ExitReason::StopLossHit => OrderStatus::ExitedBySl,
+            ExitReason::TimeExit => OrderStatus::ExitedByTime,
+            ExitReason::SquareOff => OrderStatus::SquaredOff,
+        }
+    }
+
+    //still needs watching => blocks a new entry for the same strategy instance
+    pub fn is_active(&self) -> bool {
+        matches!(self, OrderStatus::PendingEntry | OrderStatus::PartiallyFilled | OrderStatus::Open)
+    }
+
+    //some qty was bought/sold => SL and target apply
+    pub fn has_position(&self) -> bool {
+        matches!(self, OrderStatus::PartiallyFilled | OrderStatus::Open)
+    }
+
+    pub fn is_exited(&self) -> bool {
+        matches!(self, OrderStatus::ExitedBySl | OrderStatus::ExitedByTarget | OrderStatus::ExitedByTime | OrderStatus::SquaredOff)
+    }
+
+    pub fn can_transition_to(&self, next_status: &OrderStatus) -> bool {
+        match self {
+            OrderStatus::PendingEntry => matches!(
+                next_status,
+                OrderStatus::PartiallyFilled | OrderStatus::Open | OrderStatus::Rejected | OrderStatus::Cancelled | OrderStatus::Expired
+            ),
+            OrderStatus::PartiallyFilled => next_status.is_exited() || matches!(next_status, OrderStatus::PartiallyFilled | OrderStatus::Open),
+            OrderStatus::Open => next_status.is_exited(),
+            OrderStatus::Rejected
+            | OrderStatus::Cancelled
+            | OrderStatus::Expired
+            | OrderStatus::ExitedBySl
+            | OrderStatus::ExitedByTarget
+            | OrderStatus::ExitedByTime
+            | OrderStatus::SquaredOff => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderStatusTransition {
+    pub status: OrderStatus,
+    pub transitioned_at: String,
+}
+
+impl OrderStatusTransition {
+    pub fn new(status: OrderStatus, transitioned_at: String) -> OrderStatusTransition {
+        OrderStatusTransition { status, transitioned_at }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderTransitionError {
+    pub order_id: String,
+    pub from_status: OrderStatus,
+    pub to_status: OrderStatus,
+}
+
+impl fmt::Display for OrderTransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "order {} can not move from {} to {}", self.order_id, self.from_status, self.to_status)
+    }
+}
+
+impl Error for OrderTransitionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_STATUSES: [OrderStatus; 10] = [
+        OrderStatus::PendingEntry,
+        OrderStatus::PartiallyFilled,
+        OrderStatus::Open,
+        OrderStatus::Rejected,
+        OrderStatus::Cancelled,
+        OrderStatus::Expired,
+        OrderStatus::ExitedBySl,
+        OrderStatus::ExitedByTarget,
+        OrderStatus::ExitedByTime,
+        OrderStatus::SquaredOff,
+    ];
+
+    fn allowed_next_statuses(status: &OrderStatus) -> Vec<OrderStatus> {
+        ALL_STATUSES.iter().filter(|next_status| status.can_transition_to(next_status)).cloned().collect()
+    }
+
+    #[test]
+    fn pending_entry_fills_or_ends_unfilled() {
+        assert_eq!(
+            allowed_next_statuses(&OrderStatus::PendingEntry),
+            vec![
+                OrderStatus::PartiallyFilled,
+                OrderStatus::Open,
+                OrderStatus::Rejected,
+                OrderStatus::Cancelled,
+                OrderStatus::Expired,
+            ]
+        );
+    }
+
+    #[test]
+    fn partially_filled_fills_further_or_exits() {
+        assert_eq!(
+            allowed_next_statuses(&OrderStatus::PartiallyFilled),
+            vec![
+                OrderStatus::PartiallyFilled,
+                OrderStatus::Open,
+                OrderStatus::ExitedBySl,
+                OrderStatus::ExitedByTarget,
+                OrderStatus::ExitedByTime,
+                OrderStatus::SquaredOff,
+            ]
+        );
+    }
+
+    #[test]
+    fn open_only_exits() {
+        assert_eq!(
+            allowed_next_statuses(&OrderStatus::Open),
+            vec![OrderStatus::ExitedBySl, OrderStatus::ExitedByTarget, OrderStatus::ExitedByTime, OrderStatus::SquaredOff]
+        );
+    }
+
+    #[test]
+    fn final_statuses_never_move() {
+        for status in ALL_STATUSES.iter().filter(|status| !status.is_active()) {
+            assert!(allowed_next_statuses(status).is_empty(), "{} should be final", status);
+        }
+    }
+
+    #[test]
+    fn exit_reasons_map_to_exited_statuses() {
+        assert_eq!(OrderStatus::from_exit_reason(&ExitReason::TargetHit), OrderStatus::ExitedByTarget);
+        assert_eq!(OrderStatus::from_exit_reason(&ExitReason::StopLossHit), OrderStatus::ExitedBySl);
+        assert_eq!(OrderStatus::from_exit_reason(&ExitReason::TimeExit), OrderStatus::ExitedByTime);
+        assert_eq!(OrderStatus::from_exit_reason(&ExitReason::SquareOff), OrderStatus::SquaredOff);
+        assert!(OrderStatus::SquaredOff.is_exited());
+    }
+}
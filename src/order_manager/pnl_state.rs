@@ -1,12 +1,19 @@
 use futures::TryStreamExt;
 use mongodb::{
     bson::doc,
-    options::FindOptions,
+    options::{FindOneOptions, FindOptions, UpdateOptions},
     Collection,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::common::{date_parser, enums::AlgoTypes};
+use std::sync::Mutex;
+
+use crate::common::{
+    date_parser,
+    enums::{AlgoTypes, ExitReason},
+    redis_client::RedisClient,
+    utils::current_pnl_state_cache_key_formatter,
+};
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CurrentPnLState {
@@ -25,6 +32,8 @@ pub struct CurrentPnLState {
     pub target_hit_percentage: f32,
     pub max_risk_capacity: i64,
     pub trade_capital: i64,
+    #[serde(default)]
+    pub cache_key: String,
 }
 
 impl CurrentPnLState {
@@ -44,6 +53,7 @@ impl CurrentPnLState {
         target_hit_percentage: f32,
         max_risk_capacity: i64,
         trade_capital: i64,
+        cache_key: String,
     ) -> CurrentPnLState {
         CurrentPnLState {
             start_trade_date,
@@ -61,6 +71,99 @@ impl CurrentPnLState {
             target_hit_percentage,
             max_risk_capacity,
             trade_capital,
+            cache_key,
+        }
+    }
+
+    //zeroed state for one trading day, limits copied from the configuration
+    pub fn from_pnl_configuration(pnl_configuration: &PnLConfiguration, trade_date_only: &str) -> CurrentPnLState {
+        CurrentPnLState::new(
+            trade_date_only.to_owned(),
+            trade_date_only.to_owned(),
+            0.0,
+            0.0,
+            pnl_configuration.targeted_pnl,
+            pnl_configuration.targeted_pnl_percentage,
+            0,
+            pnl_configuration.max_sl_hit_count,
+            0,
+            pnl_configuration.max_trade_count,
+            0,
+            pnl_configuration.max_target_hit_count,
+            0.0,
+            pnl_configuration.max_risk_capacity,
+            pnl_configuration.trade_capital,
+            current_pnl_state_cache_key_formatter(trade_date_only),
+        )
+    }
+
+    //Redis first, then MongoDB, otherwise a fresh state for the day
+    pub async fn fetch_or_create(
+        trade_date_only: &str,
+        pnl_configuration: &PnLConfiguration,
+        current_pnl_state_collection: &Collection<CurrentPnLState>,
+        redis_client: &Mutex<RedisClient>,
+    ) -> CurrentPnLState {
+        let cache_key = current_pnl_state_cache_key_formatter(trade_date_only);
+        if let Ok(data) = redis_client.lock().unwrap().get_data(cache_key.as_str()) {
+            if let Ok(current_pnl_state) = serde_json::from_str::<CurrentPnLState>(data.as_str()) {
+                return current_pnl_state;
+            }
+        }
+
+        match current_pnl_state_collection.find_one(doc! {"cache_key": cache_key.clone()}, None).await {
+            Ok(Some(current_pnl_state)) => current_pnl_state,
+            Ok(None) => CurrentPnLState::from_pnl_configuration(pnl_configuration, trade_date_only),
+            Err(e) => {
+                println!("Error while fetching current PnL state for {} => {:?}", cache_key, e);
+                CurrentPnLState::from_pnl_configuration(pnl_configuration, trade_date_only)
+            }
+        }
+    }
+
+    pub fn apply_order_opened(&mut self) {
+        self.current_trade_count += 1;
+    }
+
+    pub fn apply_order_closed(&mut self, closing_profit: f32, exit_reason: &ExitReason) {
+        self.current_pnl += closing_profit;
+        self.current_pnl_percentage = if self.trade_capital > 0 {
+            self.current_pnl / self.trade_capital as f32 * 100.0
+        } else {
+            0.0
+        };
+        match exit_reason {
+            ExitReason::TargetHit => self.current_target_hit_count += 1,
+            ExitReason::StopLossHit => self.current_sl_hit_count += 1,
+            ExitReason::TimeExit | ExitReason::SquareOff => (),
+        }
+        //share of the closed trades that reached the target
+        let closed_trade_count = self.current_target_hit_count + self.current_sl_hit_count;
+        self.target_hit_percentage = if closed_trade_count > 0 {
+            self.current_target_hit_count as f32 / closed_trade_count as f32 * 100.0
+        } else {
+            0.0
+        };
+    }
+
+    pub async fn save(&self, current_pnl_state_collection: &Collection<CurrentPnLState>, redis_client: &Mutex<RedisClient>) {
+        let filter = doc! {"cache_key": self.cache_key.clone()};
+        let options = UpdateOptions::builder().upsert(true).build();
+        match mongodb::bson::to_document(self) {
+            Ok(current_pnl_state_document) => {
+                if let Err(e) = current_pnl_state_collection
+                    .update_one(filter, doc! {"$set": current_pnl_state_document}, options)
+                    .await
+                {
+                    println!("Error while updating current PnL state in MongoDB => {:?}", e);
+                }
+            }
+            Err(e) => println!("Error while serialising current PnL state => {:?}", e),
+        }
+
+        match redis_client.lock().unwrap().set_data(self.cache_key.as_str(), serde_json::to_string(self).unwrap().as_str()) {
+            Ok(_) => (),
+            Err(e) => println!("Error while updating current PnL state in Redis => {:?}", e),
         }
     }
 
@@ -134,6 +237,7 @@ impl CurrentPnLState {
                     target_hit_percentage,
                     max_risk_capacity,
                     trade_capital,
+                    "".to_string(),
                 );
 
                 match current_pnl_state_collection
@@ -207,13 +311,12 @@ impl PnLConfiguration {
         }
     }
 
-    pub async fn new_static_config(pnl_configuration_collection: Collection<PnLConfiguration>) {
+    pub fn new_default_config(symbols: Vec<String>) -> PnLConfiguration {
         let created_at = date_parser::new_current_date_time_in_desired_stock_datetime_format();
         let start_trade_date =
             date_parser::new_current_date_time_in_desired_stock_datetime_format();
         let end_trade_date = date_parser::new_current_date_time_in_desired_stock_datetime_format();
         let max_trade_count = 5;
-        let symbols = vec!["ADANIGREEN".to_string()];
         let trading_algo_types = vec![AlgoTypes::HammerPatternAlgo];
         let max_sl_hit_count = 2;
         let targeted_pnl = 1000.0;
@@ -221,7 +324,7 @@ impl PnLConfiguration {
         let max_target_hit_count = 4;
         let max_risk_capacity = 500;
         let trade_capital = 10000;
-        let new_pnl_configuration = PnLConfiguration::new(
+        PnLConfiguration::new(
             created_at,
             start_trade_date,
             end_trade_date,
@@ -234,7 +337,23 @@ impl PnLConfiguration {
             max_target_hit_count,
             max_risk_capacity,
             trade_capital,
-        );
+        )
+    }
+
+    //latest configuration by created_at, None when the collection is empty or not reachable
+    pub async fn fetch_latest_config(pnl_configuration_collection: Collection<PnLConfiguration>) -> Option<PnLConfiguration> {
+        let options = FindOneOptions::builder().sort(doc! {"created_at": -1}).build();
+        match pnl_configuration_collection.find_one(doc! {}, options).await {
+            Ok(pnl_configuration) => pnl_configuration,
+            Err(e) => {
+                println!("Error while fetching PnL configuration: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn new_static_config(pnl_configuration_collection: Collection<PnLConfiguration>, symbols: Vec<String>) {
+        let new_pnl_configuration = PnLConfiguration::new_default_config(symbols);
 
         match pnl_configuration_collection
             .insert_one(new_pnl_configuration, None)
@@ -0,0 +1,452 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{enums::TradeType, redis_client::RedisClient};
+
+use super::{order_dispatcher::Order, pnl_state::CurrentPnLState};
+
+//net of every open order on a symbol, positive qty => long, negative => short
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetPosition {
+    pub symbol: String,
+    pub qty: i32,
+    pub average_price: f32, //of the side that is left after netting
+    pub realised_pnl: f32,  //closed orders and partial exits of the open ones
+    pub last_price: f32,
+}
+
+#[derive(Debug, Default)]
+struct PositionBookState {
+    open_orders: Vec<Order>,
+    closed_orders: Vec<Order>,
+    exiting_order_ids: HashSet<String>,
+    entering_keys: HashSet<(String, String)>, //symbol and strategy instance of entries being placed with the broker
+    pnl_states: HashMap<String, CurrentPnLState>, //keyed by the cache key of the trading day
+}
+
+//Single book of the orders placed in this process. Clones share the same book,
+//every operation takes the lock once so the dispatcher, the watcher and the square off never see half an update.
+#[derive(Debug, Clone, Default)]
+pub struct PositionBook {
+    position_book_state: Arc<Mutex<PositionBookState>>,
+    pnl_state_save_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl PositionBook {
+    pub fn new() -> PositionBook {
+        PositionBook::default()
+    }
+
+    //claims the entry of a symbol and strategy instance before its broker order is placed,
+    //false => an active order or another entry for them is already in the book
+    pub fn reserve_entry(&self, symbol: &str, strategy_instance: &str) -> bool {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        let has_open_order = position_book_state
+            .open_orders
+            .iter()
+            .any(|open_order| open_order.symbol == symbol && open_order.strategy_instance == strategy_instance);
+        !has_open_order && position_book_state.entering_keys.insert((symbol.to_string(), strategy_instance.to_string()))
+    }
+
+    //the entry was not placed => another signal can take it
+    pub fn release_entry(&self, symbol: &str, strategy_instance: &str) {
+        self.position_book_state
+            .lock()
+            .unwrap()
+            .entering_keys
+            .remove(&(symbol.to_string(), strategy_instance.to_string()));
+    }
+
+    //false => an active order for the same symbol and strategy instance is already in the book, a reserved entry is released either way
+    pub fn open_order(&self, order: Order) -> bool {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        position_book_state
+            .entering_keys
+            .remove(&(order.symbol.clone(), order.strategy_instance.clone()));
+        let is_duplicate = position_book_state
+            .open_orders
+            .iter()
+            .any(|open_order| open_order.symbol == order.symbol && open_order.strategy_instance == order.strategy_instance);
+        if is_duplicate {
+            return false;
+        }
+        position_book_state.open_orders.push(order);
+        true
+    }
+
+    //replaces the stored order, an order that is no longer active moves to the closed orders. false => not an open order
+    pub fn update_order(&self, order: Order) -> bool {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        let index = match position_book_state
+            .open_orders
+            .iter()
+            .position(|open_order| open_order.order_id == order.order_id)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        if order.status.is_active() {
+            //the last price is only ever set through mark_price
+            let last_price = position_book_state.open_orders[index].last_price;
+            position_book_state.open_orders[index] = Order { last_price, ..order };
+        } else {
+            position_book_state.open_orders.remove(index);
+            position_book_state.exiting_order_ids.remove(&order.order_id);
+            position_book_state.closed_orders.push(order);
+        }
+        true
+    }
+
+    //claims the exit of an open order, false => it is not open any more or another task is already exiting it
+    pub fn begin_exit(&self, order_id: &str) -> bool {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        let is_open = position_book_state.open_orders.iter().any(|open_order| open_order.order_id == order_id);
+        is_open && position_book_state.exiting_order_ids.insert(order_id.to_string())
+    }
+
+    //the exit failed => the order can be exited again
+    pub fn abort_exit(&self, order_id: &str) {
+        self.position_book_state.lock().unwrap().exiting_order_ids.remove(order_id);
+    }
+
+    pub fn mark_price(&self, symbol: &str, last_price: f32) {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        for open_order in position_book_state.open_orders.iter_mut().filter(|open_order| open_order.symbol == symbol) {
+            open_order.last_price = last_price;
+        }
+    }
+
+    pub fn open_orders(&self) -> Vec<Order> {
+        self.position_book_state.lock().unwrap().open_orders.clone()
+    }
+
+    pub fn open_orders_for_symbol(&self, symbol: &str) -> Vec<Order> {
+        self.position_book_state
+            .lock()
+            .unwrap()
+            .open_orders
+            .iter()
+            .filter(|open_order| open_order.symbol == symbol)
+            .cloned()
+            .collect()
+    }
+
+    //exited, expired, cancelled and rejected orders in the order they left the book
+    pub fn closed_orders(&self) -> Vec<Order> {
+        self.position_book_state.lock().unwrap().closed_orders.clone()
+    }
+
+    pub fn net_position(&self, symbol: &str) -> Option<NetPosition> {
+        let position_book_state = self.position_book_state.lock().unwrap();
+        PositionBook::calculate_net_position(&position_book_state, symbol)
+    }
+
+    pub fn net_positions(&self) -> Vec<NetPosition> {
+        let position_book_state = self.position_book_state.lock().unwrap();
+        let mut symbols: Vec<&String> = position_book_state
+            .open_orders
+            .iter()
+            .chain(position_book_state.closed_orders.iter())
+            .map(|order| &order.symbol)
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+            .into_iter()
+            .filter_map(|symbol| PositionBook::calculate_net_position(&position_book_state, symbol))
+            .collect()
+    }
+
+    //None => the day has not been loaded into the book yet
+    pub fn pnl_state(&self, cache_key: &str) -> Option<CurrentPnLState> {
+        self.position_book_state.lock().unwrap().pnl_states.get(cache_key).cloned()
+    }
+
+    //the change is applied under the book lock => an open and a close racing on the same day both land.
+    //loaded_pnl_state only seeds the day when the book does not hold it yet
+    pub fn update_pnl_state(&self, loaded_pnl_state: CurrentPnLState, update: impl FnOnce(&mut CurrentPnLState)) -> CurrentPnLState {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        let current_pnl_state = position_book_state
+            .pnl_states
+            .entry(loaded_pnl_state.cache_key.clone())
+            .or_insert(loaded_pnl_state);
+        update(current_pnl_state);
+        current_pnl_state.clone()
+    }
+
+    //saves run one at a time and write the latest state of the day => an older snapshot never overwrites a newer one
+    pub async fn save_pnl_state(&self, cache_key: &str, current_pnl_state_collection: &Collection<CurrentPnLState>, redis_client: &Mutex<RedisClient>) {
+        let _pnl_state_save_guard = self.pnl_state_save_lock.lock().await;
+        if let Some(current_pnl_state) = self.pnl_state(cache_key) {
+            current_pnl_state.save(current_pnl_state_collection, redis_client).await;
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut position_book_state = self.position_book_state.lock().unwrap();
+        position_book_state.open_orders.clear();
+        position_book_state.closed_orders.clear();
+        position_book_state.exiting_order_ids.clear();
+        position_book_state.entering_keys.clear();
+        position_book_state.pnl_states.clear();
+    }
+
+    //orders still open in Mongo from an earlier run, so a restart keeps watching and exiting them
+    pub async fn restore_open_orders(&self, order_collection: &Collection<Order>) {
+        let open_orders = match order_collection.find(doc! {"is_trade_open": true}, None).await {
+            Ok(cursor) => cursor.try_collect::<Vec<Order>>().await,
+            Err(e) => Err(e),
+        };
+        match open_orders {
+            Ok(open_orders) => {
+                for open_order in open_orders {
+                    if !self.open_order(open_order.clone()) {
+                        println!("Skipping the duplicate open order {} while restoring the position book", open_order.order_id);
+                    }
+                }
+                println!("Position book restored with {} open orders", self.open_orders().len());
+            }
+            Err(e) => {
+                println!("Error while restoring the position book from MongoDB => {:?}", e);
+            }
+        }
+    }
+
+    fn calculate_net_position(position_book_state: &PositionBookState, symbol: &str) -> Option<NetPosition> {
+        let mut long_qty = 0;
+        let mut long_turnover = 0.0;
+        let mut short_qty = 0;
+        let mut short_turnover = 0.0;
+        let mut realised_pnl = 0.0;
+        let mut last_price = 0.0;
+        let mut has_orders = false;
+
+        for order in position_book_state.open_orders.iter().filter(|order| order.symbol == symbol) {
+            has_orders = true;
+            last_price = order.last_price;
+            realised_pnl += order.partial_exits.iter().map(|partial_exit| partial_exit.profit).sum::<f32>();
+            if !order.status.has_position() {
+                continue;
+            }
+            if order.trade_position_type == TradeType::Long {
+                long_qty += order.open_qty();
+                long_turnover += order.entry_price * order.open_qty() as f32;
+            } else {
+                short_qty += order.open_qty();
+                short_turnover += order.entry_price * order.open_qty() as f32;
+            }
+        }
+        for order in position_book_state.closed_orders.iter().filter(|order| order.symbol == symbol) {
+            has_orders = true;
+            realised_pnl += order.closing_profit;
+        }
+        if !has_orders {
+            return None;
+        }
+
+        let qty = long_qty - short_qty;
+        let average_price = if qty > 0 {
+            long_turnover / long_qty as f32
+        } else if qty < 0 {
+            short_turnover / short_qty as f32
+        } else {
+            0.0
+        };
+        Some(NetPosition {
+            symbol: symbol.to_string(),
+            qty,
+            average_price,
+            realised_pnl,
+            last_price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::enums::{AlgoTypes, ExitReason, TimeFrame},
+        order_manager::{order_dispatcher::PartialExit, order_status::OrderStatus, pnl_state::PnLConfiguration},
+    };
+
+    fn test_order(order_id: &str, symbol: &str, strategy_instance: &str, trade_position_type: TradeType, entry_price: f32, filled_qty: i32) -> Order {
+        let mut order = Order::new(
+            symbol.to_string(),
+            trade_position_type,
+            AlgoTypes::HammerPatternAlgo,
+            strategy_instance.to_string(),
+            TimeFrame::FiveMinutes,
+            entry_price,
+            0.0,
+            entry_price * 0.95,
+            entry_price * 1.1,
+            OrderStatus::Open,
+            filled_qty,
+            entry_price * filled_qty as f32,
+            "2023-08-10 09:30:00+0530".to_string(),
+            "".to_string(),
+            order_id.to_string(),
+            0.0,
+            false,
+            None,
+        );
+        order.filled_qty = filled_qty;
+        order
+    }
+
+    #[test]
+    fn only_one_task_can_exit_an_order() {
+        let position_book = PositionBook::new();
+        position_book.open_order(test_order("PAPER_1", "ADANIGREEN", "Hammer", TradeType::Long, 100.0, 10));
+
+        assert!(position_book.begin_exit("PAPER_1"));
+        assert!(!position_book.begin_exit("PAPER_1"));
+        assert!(!position_book.begin_exit("PAPER_2"));
+
+        //a failed exit frees the order for the next attempt
+        position_book.abort_exit("PAPER_1");
+        assert!(position_book.begin_exit("PAPER_1"));
+    }
+
+    #[test]
+    fn exited_order_can_not_be_exited_again() {
+        let position_book = PositionBook::new();
+        let order = test_order("PAPER_1", "ADANIGREEN", "Hammer", TradeType::Long, 100.0, 10);
+        position_book.open_order(order.clone());
+        assert!(position_book.begin_exit("PAPER_1"));
+
+        let mut exited_order = order;
+        exited_order.exit_trade(110.0, "2023-08-10 10:00:00+0530".to_string(), ExitReason::TargetHit).unwrap();
+        assert!(position_book.update_order(exited_order));
+
+        assert!(position_book.open_orders().is_empty());
+        assert_eq!(position_book.closed_orders().len(), 1);
+        assert!(!position_book.begin_exit("PAPER_1"));
+        position_book.abort_exit("PAPER_1");
+        assert!(!position_book.begin_exit("PAPER_1"));
+    }
+
+    #[test]
+    fn reserved_entry_blocks_duplicates_until_opened_or_released() {
+        let position_book = PositionBook::new();
+        assert!(position_book.reserve_entry("ADANIGREEN", "Hammer"));
+        assert!(!position_book.reserve_entry("ADANIGREEN", "Hammer"));
+        assert!(position_book.reserve_entry("ADANIGREEN", "ShootingStar"));
+
+        position_book.release_entry("ADANIGREEN", "ShootingStar");
+        assert!(position_book.reserve_entry("ADANIGREEN", "ShootingStar"));
+
+        assert!(position_book.open_order(test_order("PAPER_1", "ADANIGREEN", "Hammer", TradeType::Long, 100.0, 10)));
+        assert!(!position_book.reserve_entry("ADANIGREEN", "Hammer"));
+        assert!(!position_book.open_order(test_order("PAPER_2", "ADANIGREEN", "Hammer", TradeType::Long, 100.0, 10)));
+    }
+
+    #[test]
+    fn net_position_nets_long_and_short_orders() {
+        let position_book = PositionBook::new();
+        position_book.open_order(test_order("PAPER_1", "ADANIGREEN", "Hammer", TradeType::Long, 100.0, 10));
+        position_book.open_order(test_order("PAPER_2", "ADANIGREEN", "Hammer_2", TradeType::Long, 110.0, 10));
+        position_book.open_order(test_order("PAPER_3", "ADANIGREEN", "ShootingStar", TradeType::Short, 120.0, 5));
+        position_book.mark_price("ADANIGREEN", 115.0);
+
+        let net_position = position_book.net_position("ADANIGREEN").unwrap();
+        assert_eq!(net_position.qty, 15);
+        assert_eq!(net_position.average_price, 105.0); //of the long side
+        assert_eq!(net_position.realised_pnl, 0.0);
+        assert_eq!(net_position.last_price, 115.0);
+        assert_eq!(position_book.net_position("TATASTEEL"), None);
+    }
+
+    #[test]
+    fn net_position_books_partial_and_closed_pnl() {
+        let position_book = PositionBook::new();
+        let mut partially_exited_order = test_order("PAPER_1", "ADANIGREEN", "Hammer", TradeType::Short, 100.0, 10);
+        partially_exited_order.exited_qty = 4;
+        partially_exited_order.partial_exits.push(PartialExit {
+            qty: 4,
+            exit_price: 95.0,
+            exited_at: "2023-08-10 09:45:00+0530".to_string(),
+            profit: 20.0,
+        });
+        position_book.open_order(partially_exited_order);
+
+        let mut closed_order = test_order("PAPER_2", "ADANIGREEN", "Hammer_2", TradeType::Long, 100.0, 10);
+        position_book.open_order(closed_order.clone());
+        closed_order.exit_trade(95.0, "2023-08-10 10:00:00+0530".to_string(), ExitReason::StopLossHit).unwrap();
+        position_book.update_order(closed_order);
+
+        //a pending entry has no position yet
+        let mut pending_order = test_order("PAPER_3", "TATASTEEL", "Hammer", TradeType::Long, 100.0, 0);
+        pending_order.status = OrderStatus::PendingEntry;
+        position_book.open_order(pending_order);
+
+        let net_positions = position_book.net_positions();
+        assert_eq!(net_positions.len(), 2);
+        assert_eq!(net_positions[0].symbol, "ADANIGREEN");
+        assert_eq!(net_positions[0].qty, -6);
+        assert_eq!(net_positions[0].average_price, 100.0);
+        assert_eq!(net_positions[0].realised_pnl, 20.0 - 50.0);
+        assert_eq!(net_positions[1].symbol, "TATASTEEL");
+        assert_eq!(net_positions[1].qty, 0);
+        assert_eq!(net_positions[1].average_price, 0.0);
+    }
+
+    fn day_pnl_state() -> CurrentPnLState {
+        CurrentPnLState::from_pnl_configuration(&PnLConfiguration::new_default_config(vec!["ADANIGREEN".to_string()]), "2023-08-10")
+    }
+
+    #[test]
+    fn open_and_close_loaded_from_the_same_state_both_land() {
+        let position_book = PositionBook::new();
+        let dispatcher_position_book = position_book.clone();
+        let watcher_position_book = position_book.clone();
+
+        //both read the day before either of them wrote, as the dispatcher and the watcher did with their own read, modify and save
+        let dispatcher_loaded_state = day_pnl_state();
+        let watcher_loaded_state = day_pnl_state();
+        dispatcher_position_book.update_pnl_state(dispatcher_loaded_state, |current_pnl_state| current_pnl_state.apply_order_opened());
+        watcher_position_book.update_pnl_state(watcher_loaded_state, |current_pnl_state| {
+            current_pnl_state.apply_order_closed(-50.0, &ExitReason::StopLossHit)
+        });
+
+        let current_pnl_state = position_book.pnl_state(day_pnl_state().cache_key.as_str()).unwrap();
+        assert_eq!(current_pnl_state.current_trade_count, 1);
+        assert_eq!(current_pnl_state.current_sl_hit_count, 1);
+        assert_eq!(current_pnl_state.current_pnl, -50.0);
+    }
+
+    #[test]
+    fn concurrent_opens_and_closes_are_all_counted() {
+        let position_book = PositionBook::new();
+        let dispatcher_position_book = position_book.clone();
+        let watcher_position_book = position_book.clone();
+
+        let dispatcher = std::thread::spawn(move || {
+            for _ in 0..200 {
+                dispatcher_position_book.update_pnl_state(day_pnl_state(), |current_pnl_state| current_pnl_state.apply_order_opened());
+            }
+        });
+        let watcher = std::thread::spawn(move || {
+            for _ in 0..200 {
+                watcher_position_book.update_pnl_state(day_pnl_state(), |current_pnl_state| {
+                    current_pnl_state.apply_order_closed(10.0, &ExitReason::TargetHit)
+                });
+            }
+        });
+        dispatcher.join().unwrap();
+        watcher.join().unwrap();
+
+        let current_pnl_state = position_book.pnl_state(day_pnl_state().cache_key.as_str()).unwrap();
+        assert_eq!(current_pnl_state.current_trade_count, 200);
+        assert_eq!(current_pnl_state.current_target_hit_count, 200);
+        assert_eq!(current_pnl_state.current_pnl, 2000.0);
+        assert_eq!(current_pnl_state.target_hit_percentage, 100.0);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::config_file;
+
+use super::trade_signal_keeper::TradeSignal;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SizingMethod {
+    FixedFractional, //risk_percentage of the capital is lost if the SL is hit
+    FixedNotional,   //same amount of money in every trade
+    VolatilityScaled, //risk_percentage of the capital against an ATR based stop distance
+}
+
+impl fmt::Display for SizingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+//Every missing field falls back to 1% fixed fractional risk without a Kelly cap
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PositionSizer {
+    pub sizing_method: SizingMethod,
+    pub risk_percentage: f32,
+    pub fixed_notional: f32,
+
+    //volatility scaled sizing, the ATR is the indicator ATR of the signal's time frame
+    pub atr_multiplier: f32,
+
+    //Kelly cap => the risk per trade never goes above kelly_fraction of the full Kelly bet
+    pub kelly_win_rate: Option<f32>,
+    pub kelly_payoff_ratio: Option<f32>,
+    pub kelly_fraction: f32,
+
+    //symbol => lot size, symbols not listed trade in single shares
+    pub lot_sizes: HashMap<String, i32>,
+}
+
+impl Default for PositionSizer {
+    fn default() -> PositionSizer {
+        PositionSizer {
+            sizing_method: SizingMethod::FixedFractional,
+            risk_percentage: 1.0,
+            fixed_notional: 2000.0,
+            atr_multiplier: 2.0,
+            kelly_win_rate: None,
+            kelly_payoff_ratio: None,
+            kelly_fraction: 0.5,
+            lot_sizes: HashMap::new(),
+        }
+    }
+}
+
+impl PositionSizer {
+    //.toml files hold a [position_sizer] table, .json files a plain object
+    pub fn load_config(file_path: &str) -> PositionSizer {
+        config_file::load_config(file_path, "position_sizer")
+    }
+
+    pub fn lot_size(&self, symbol: &str) -> i32 {
+        self.lot_sizes.get(symbol).copied().unwrap_or(1).max(1)
+    }
+
+    //full Kelly => W - (1 - W) / R, None when the inputs are not configured
+    pub fn kelly_percentage(&self) -> Option<f32> {
+        match (self.kelly_win_rate, self.kelly_payoff_ratio) {
+            (Some(win_rate), Some(payoff_ratio)) if payoff_ratio > 0.0 => {
+                Some(((win_rate - (1.0 - win_rate) / payoff_ratio) * 100.0).max(0.0))
+            }
+            _ => None,
+        }
+    }
+
+    //money at risk per trade after the Kelly cap, a negative risk setting risks nothing
+    pub fn risk_amount(&self, trade_capital: f32) -> f32 {
+        let risk_percentage = match self.kelly_percentage() {
+            Some(kelly_percentage) => self.risk_percentage.min(kelly_percentage * self.kelly_fraction),
+            None => self.risk_percentage,
+        };
+        (trade_capital * risk_percentage / 100.0).max(0.0)
+    }
+
+    //0 => the trade can not be sized within the capital (or the ATR is not available yet)
+    pub fn calculate_qty(&self, symbol: &str, entry_price: f32, trade_sl: f32, trade_capital: f32, average_true_range: Option<f32>) -> i32 {
+        if entry_price <= 0.0 || trade_capital <= 0.0 {
+            return 0;
+        }
+        let risk_amount = self.risk_amount(trade_capital);
+        let raw_qty = match self.sizing_method {
+            SizingMethod::FixedFractional => {
+                let stop_distance = (entry_price - trade_sl).abs();
+                if stop_distance <= 0.0 {
+                    return 0;
+                }
+                risk_amount / stop_distance
+            }
+            SizingMethod::FixedNotional => {
+                let qty = self.fixed_notional / entry_price;
+                //the Kelly cap still applies to the loss at the SL
+                let stop_distance = (entry_price - trade_sl).abs();
+                if self.kelly_percentage().is_some() && stop_distance > 0.0 {
+                    qty.min(risk_amount / stop_distance)
+                } else {
+                    qty
+                }
+            }
+            SizingMethod::VolatilityScaled => match average_true_range {
+                Some(average_true_range) if average_true_range > 0.0 => risk_amount / (average_true_range * self.atr_multiplier),
+                _ => return 0,
+            },
+        };
+
+        //never more than the capital can pay for, rounded down to whole lots
+        let raw_qty = raw_qty.min(trade_capital / entry_price).max(0.0);
+        let lot_size = self.lot_size(symbol);
+        (raw_qty as i32 / lot_size) * lot_size
+    }
+
+    //qty and total_price of the signal are replaced by the sized values
+    pub fn size_trade_signal(&self, trade_signal: &mut TradeSignal, trade_capital: f32, average_true_range: Option<f32>) {
+        trade_signal.qty = self.calculate_qty(
+            trade_signal.raw_stock.symbol.as_str(),
+            trade_signal.entry_price,
+            trade_signal.trade_sl,
+            trade_capital,
+            average_true_range,
+        );
+        trade_signal.total_price = trade_signal.entry_price * trade_signal.qty as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::common::{
+        enums::{AlgoTypes, TimeFrame, TradeType},
+        raw_stock::RawStock,
+    };
+
+    fn position_sizer(sizing_method: SizingMethod) -> PositionSizer {
+        PositionSizer {
+            sizing_method,
+            ..PositionSizer::default()
+        }
+    }
+
+    #[test]
+    fn fixed_fractional_risks_the_percentage_at_the_sl() {
+        //1% of 100000 => 1000 at risk over a stop distance of 4
+        assert_eq!(position_sizer(SizingMethod::FixedFractional).calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 250);
+        //short side uses the same distance
+        assert_eq!(position_sizer(SizingMethod::FixedFractional).calculate_qty("ADANIGREEN", 100.0, 104.0, 100000.0, None), 250);
+        //no stop distance => can not be sized
+        assert_eq!(position_sizer(SizingMethod::FixedFractional).calculate_qty("ADANIGREEN", 100.0, 100.0, 100000.0, None), 0);
+    }
+
+    #[test]
+    fn fixed_notional_puts_the_same_money_in_every_trade() {
+        let position_sizer = PositionSizer {
+            fixed_notional: 2050.0,
+            ..position_sizer(SizingMethod::FixedNotional)
+        };
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 50.0, 100000.0, None), 20);
+    }
+
+    #[test]
+    fn volatility_scaled_sizes_against_the_atr_stop() {
+        let position_sizer = PositionSizer {
+            atr_multiplier: 2.0,
+            ..position_sizer(SizingMethod::VolatilityScaled)
+        };
+        //1000 at risk over 2 x ATR 2.5
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, Some(2.5)), 200);
+        //twice the volatility => half the qty
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, Some(5.0)), 100);
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 0);
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, Some(0.0)), 0);
+    }
+
+    #[test]
+    fn kelly_caps_the_risk_percentage() {
+        //full Kelly 0.5 - 0.5 / 2 => 25%, half of it is 12.5% which is above the 1% risk
+        let uncapped_position_sizer = PositionSizer {
+            kelly_win_rate: Some(0.5),
+            kelly_payoff_ratio: Some(2.0),
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        assert_eq!(uncapped_position_sizer.kelly_percentage(), Some(25.0));
+        assert_eq!(uncapped_position_sizer.risk_amount(100000.0), 1000.0);
+
+        //a sixteenth of the 25% full Kelly caps the 2% risk at 1.5625%
+        let capped_position_sizer = PositionSizer {
+            risk_percentage: 2.0,
+            kelly_win_rate: Some(0.5),
+            kelly_payoff_ratio: Some(2.0),
+            kelly_fraction: 0.0625,
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        assert_eq!(capped_position_sizer.risk_amount(100000.0), 1562.5);
+        assert_eq!(capped_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 390);
+
+        //the cap also holds for fixed notional sizing
+        let capped_notional_position_sizer = PositionSizer {
+            sizing_method: SizingMethod::FixedNotional,
+            fixed_notional: 50000.0,
+            ..capped_position_sizer
+        };
+        assert_eq!(capped_notional_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 390);
+    }
+
+    #[test]
+    fn losing_edge_or_missing_inputs_disable_the_kelly_cap() {
+        let losing_position_sizer = PositionSizer {
+            kelly_win_rate: Some(0.2),
+            kelly_payoff_ratio: Some(1.0),
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        assert_eq!(losing_position_sizer.kelly_percentage(), Some(0.0));
+        assert_eq!(losing_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 0);
+
+        let partial_position_sizer = PositionSizer {
+            kelly_win_rate: Some(0.5),
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        assert_eq!(partial_position_sizer.kelly_percentage(), None);
+    }
+
+    #[test]
+    fn zero_or_negative_risk_sizes_to_nothing() {
+        let zero_risk_position_sizer = PositionSizer {
+            risk_percentage: 0.0,
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        assert_eq!(zero_risk_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 0);
+
+        let negative_risk_position_sizer = PositionSizer {
+            risk_percentage: -1.0,
+            ..position_sizer(SizingMethod::VolatilityScaled)
+        };
+        assert_eq!(negative_risk_position_sizer.risk_amount(100000.0), 0.0);
+        assert_eq!(negative_risk_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, Some(2.0)), 0);
+
+        let negative_notional_position_sizer = PositionSizer {
+            fixed_notional: -2000.0,
+            ..position_sizer(SizingMethod::FixedNotional)
+        };
+        assert_eq!(negative_notional_position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 100000.0, None), 0);
+
+        let position_sizer = position_sizer(SizingMethod::FixedFractional);
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 100.0, 96.0, 0.0, None), 0);
+        assert_eq!(position_sizer.calculate_qty("ADANIGREEN", 0.0, 96.0, 100000.0, None), 0);
+    }
+
+    #[test]
+    fn qty_is_capped_by_the_capital_and_rounded_to_lots() {
+        //1000 at risk over 0.5 => 2000 shares, but 100000 only buys 1000
+        assert_eq!(position_sizer(SizingMethod::FixedFractional).calculate_qty("ADANIGREEN", 100.0, 99.5, 100000.0, None), 1000);
+
+        let lot_position_sizer = PositionSizer {
+            lot_sizes: HashMap::from([("NIFTY".to_string(), 50), ("BROKEN".to_string(), 0)]),
+            ..position_sizer(SizingMethod::FixedFractional)
+        };
+        //249 shares round down to 4 lots of 50
+        assert_eq!(lot_position_sizer.calculate_qty("NIFTY", 100.0, 95.98, 100000.0, None), 200);
+        //less than one lot => nothing
+        assert_eq!(lot_position_sizer.calculate_qty("NIFTY", 100.0, 70.0, 100000.0, None), 0);
+        //a 0 lot size is treated as single shares
+        assert_eq!(lot_position_sizer.lot_size("BROKEN"), 1);
+        assert_eq!(lot_position_sizer.lot_size("ADANIGREEN"), 1);
+    }
+
+    #[test]
+    fn trade_signal_gets_the_sized_qty_and_total_price() {
+        let raw_stock = RawStock::new("ADANIGREEN".to_string(), "2023-08-10 09:30:00+0530".to_string(), 101.0, 102.0, 99.0, 100.0, 1000, TimeFrame::FiveMinutes);
+        let mut trade_signal = TradeSignal::new(
+            raw_stock,
+            TradeType::Long,
+            AlgoTypes::HammerPatternAlgo,
+            AlgoTypes::HammerPatternAlgo.to_string(),
+            "2023-08-10 09:35:00+0530".to_string(),
+            100.0,
+            96.0,
+            110.0,
+            10,
+            1000.0,
+            ObjectId::new(),
+            ObjectId::new(),
+        );
+        position_sizer(SizingMethod::FixedFractional).size_trade_signal(&mut trade_signal, 100000.0, None);
+        assert_eq!(trade_signal.qty, 250);
+        assert_eq!(trade_signal.total_price, 25000.0);
+    }
+}
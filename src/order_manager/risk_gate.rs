@@ -0,0 +1,248 @@
+use std::fmt;
+
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{date_parser, enums::AlgoTypes};
+
+use super::{pnl_state::{CurrentPnLState, PnLConfiguration}, trade_signal_keeper::TradeSignal};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RiskRejectionReason {
+    MaxTradeCountReached,
+    MaxSlHitCountReached,
+    MaxTargetHitCountReached,
+    TargetedPnlReached,
+    DailyLossLimitReached,
+    TradeRiskAboveCapacity,
+}
+
+impl fmt::Display for RiskRejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskRejection {
+    pub symbol: String,
+    pub trade_algo_type: AlgoTypes,
+    pub strategy_instance: String,
+    pub signal_candle_date: String,
+    pub entry_price: f32,
+    pub trade_sl: f32,
+    pub qty: i32,
+    pub trade_risk: f32,
+    pub reason: RiskRejectionReason,
+    pub rejected_at: String,
+}
+
+impl RiskRejection {
+    pub fn new(trade_signal: &TradeSignal, trade_risk: f32, reason: RiskRejectionReason) -> RiskRejection {
+        RiskRejection {
+            symbol: trade_signal.raw_stock.symbol.clone(),
+            trade_algo_type: trade_signal.trade_algo_type.clone(),
+            strategy_instance: trade_signal.strategy_instance.clone(),
+            signal_candle_date: trade_signal.raw_stock.date.clone(),
+            entry_price: trade_signal.entry_price,
+            trade_sl: trade_signal.trade_sl,
+            qty: trade_signal.qty,
+            trade_risk,
+            reason,
+            rejected_at: date_parser::new_current_date_time_in_desired_stock_datetime_format(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskGate {
+    pub pnl_configuration: PnLConfiguration,
+    risk_rejection_collection: Collection<RiskRejection>,
+}
+
+impl RiskGate {
+    pub const COLLECTION_NAME: &'static str = "risk_rejections";
+
+    pub fn new(pnl_configuration: PnLConfiguration, risk_rejection_collection: Collection<RiskRejection>) -> RiskGate {
+        RiskGate {
+            pnl_configuration,
+            risk_rejection_collection,
+        }
+    }
+
+    //amount lost if the SL is hit
+    pub fn calculate_trade_risk(trade_signal: &TradeSignal) -> f32 {
+        (trade_signal.entry_price - trade_signal.trade_sl).abs() * trade_signal.qty as f32
+    }
+
+    //the day stops after max_sl_hit_count full losses
+    pub fn daily_loss_limit(&self) -> f32 {
+        (self.pnl_configuration.max_sl_hit_count as i64 * self.pnl_configuration.max_risk_capacity) as f32
+    }
+
+    pub fn evaluate(&self, trade_signal: &TradeSignal, current_pnl_state: &CurrentPnLState) -> Result<(), RiskRejectionReason> {
+        let pnl_configuration = &self.pnl_configuration;
+        if current_pnl_state.current_trade_count >= pnl_configuration.max_trade_count {
+            return Err(RiskRejectionReason::MaxTradeCountReached);
+        }
+        if current_pnl_state.current_sl_hit_count >= pnl_configuration.max_sl_hit_count {
+            return Err(RiskRejectionReason::MaxSlHitCountReached);
+        }
+        if current_pnl_state.current_target_hit_count >= pnl_configuration.max_target_hit_count {
+            return Err(RiskRejectionReason::MaxTargetHitCountReached);
+        }
+        if current_pnl_state.current_pnl >= pnl_configuration.targeted_pnl {
+            return Err(RiskRejectionReason::TargetedPnlReached);
+        }
+        if -current_pnl_state.current_pnl >= self.daily_loss_limit() {
+            return Err(RiskRejectionReason::DailyLossLimitReached);
+        }
+        if RiskGate::calculate_trade_risk(trade_signal) > pnl_configuration.max_risk_capacity as f32 {
+            return Err(RiskRejectionReason::TradeRiskAboveCapacity);
+        }
+        Ok(())
+    }
+
+    //true => the signal may be dispatched, otherwise the rejection is recorded with its reason
+    pub async fn approve_trade_signal(&self, trade_signal: &TradeSignal, current_pnl_state: &CurrentPnLState) -> bool {
+        match self.evaluate(trade_signal, current_pnl_state) {
+            Ok(_) => true,
+            Err(reason) => {
+                println!(
+                    "Trade signal rejected for {} by {} => {} | {:?}",
+                    trade_signal.raw_stock.symbol, trade_signal.strategy_instance, reason, current_pnl_state
+                );
+                let risk_rejection = RiskRejection::new(trade_signal, RiskGate::calculate_trade_risk(trade_signal), reason);
+                if let Err(e) = self.risk_rejection_collection.insert_one(risk_rejection, None).await {
+                    println!("Error while adding risk rejection to the database {}", e);
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::common::{
+        enums::{TimeFrame, TradeType},
+        raw_stock::RawStock,
+    };
+
+    //default limits => 5 trades, 2 SL hits, 4 target hits, 1000 targeted PnL and 500 risk per trade, so a 1000 daily loss limit
+    async fn risk_gate() -> RiskGate {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:27017").await.unwrap();
+        RiskGate::new(
+            PnLConfiguration::new_default_config(vec!["ADANIGREEN".to_string()]),
+            mongo_client.database("algo_trading_test").collection(RiskGate::COLLECTION_NAME),
+        )
+    }
+
+    fn day_pnl_state(risk_gate: &RiskGate) -> CurrentPnLState {
+        CurrentPnLState::from_pnl_configuration(&risk_gate.pnl_configuration, "2023-08-10")
+    }
+
+    fn trade_signal(trade_position_type: TradeType, entry_price: f32, trade_sl: f32, qty: i32) -> TradeSignal {
+        TradeSignal::new(
+            RawStock::new("ADANIGREEN".to_string(), "2023-08-10 09:20:00+0530".to_string(), 100.0, 101.0, 99.0, 100.0, 1000, TimeFrame::FiveMinutes),
+            trade_position_type,
+            AlgoTypes::HammerPatternAlgo,
+            AlgoTypes::HammerPatternAlgo.to_string(),
+            "2023-08-10 09:20:00+0530".to_string(),
+            entry_price,
+            trade_sl,
+            entry_price * 2.0,
+            qty,
+            entry_price * qty as f32,
+            ObjectId::new(),
+            ObjectId::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn fresh_day_within_the_risk_capacity_is_approved() {
+        let risk_gate = risk_gate().await;
+        let current_pnl_state = day_pnl_state(&risk_gate);
+        //risk exactly at the capacity is still allowed, for longs and shorts
+        assert_eq!(risk_gate.evaluate(&trade_signal(TradeType::Long, 100.0, 50.0, 10), &current_pnl_state), Ok(()));
+        assert_eq!(risk_gate.evaluate(&trade_signal(TradeType::Short, 100.0, 150.0, 10), &current_pnl_state), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn trade_risk_above_the_capacity_is_rejected() {
+        let risk_gate = risk_gate().await;
+        let current_pnl_state = day_pnl_state(&risk_gate);
+        assert_eq!(RiskGate::calculate_trade_risk(&trade_signal(TradeType::Long, 100.0, 50.0, 11)), 550.0);
+        assert_eq!(
+            risk_gate.evaluate(&trade_signal(TradeType::Long, 100.0, 50.0, 11), &current_pnl_state),
+            Err(RiskRejectionReason::TradeRiskAboveCapacity)
+        );
+        assert_eq!(
+            risk_gate.evaluate(&trade_signal(TradeType::Short, 100.0, 150.5, 10), &current_pnl_state),
+            Err(RiskRejectionReason::TradeRiskAboveCapacity)
+        );
+    }
+
+    #[tokio::test]
+    async fn trade_count_stops_new_positions_once_reached() {
+        let risk_gate = risk_gate().await;
+        let mut current_pnl_state = day_pnl_state(&risk_gate);
+        let signal = trade_signal(TradeType::Long, 100.0, 95.0, 10);
+        for _ in 0..4 {
+            current_pnl_state.apply_order_opened();
+        }
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Ok(()));
+        current_pnl_state.apply_order_opened();
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Err(RiskRejectionReason::MaxTradeCountReached));
+    }
+
+    #[tokio::test]
+    async fn sl_and_target_hit_counts_stop_the_day_once_reached() {
+        let risk_gate = risk_gate().await;
+        let signal = trade_signal(TradeType::Long, 100.0, 95.0, 10);
+
+        let mut current_pnl_state = day_pnl_state(&risk_gate);
+        current_pnl_state.current_sl_hit_count = 1;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Ok(()));
+        current_pnl_state.current_sl_hit_count = 2;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Err(RiskRejectionReason::MaxSlHitCountReached));
+
+        let mut current_pnl_state = day_pnl_state(&risk_gate);
+        current_pnl_state.current_target_hit_count = 3;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Ok(()));
+        current_pnl_state.current_target_hit_count = 4;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Err(RiskRejectionReason::MaxTargetHitCountReached));
+    }
+
+    #[tokio::test]
+    async fn pnl_limits_stop_the_day_once_reached() {
+        let risk_gate = risk_gate().await;
+        let signal = trade_signal(TradeType::Long, 100.0, 95.0, 10);
+        assert_eq!(risk_gate.daily_loss_limit(), 1000.0);
+
+        let mut current_pnl_state = day_pnl_state(&risk_gate);
+        current_pnl_state.current_pnl = 999.5;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Ok(()));
+        current_pnl_state.current_pnl = 1000.0;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Err(RiskRejectionReason::TargetedPnlReached));
+
+        current_pnl_state.current_pnl = -999.5;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Ok(()));
+        current_pnl_state.current_pnl = -1000.0;
+        assert_eq!(risk_gate.evaluate(&signal, &current_pnl_state), Err(RiskRejectionReason::DailyLossLimitReached));
+    }
+
+    #[tokio::test]
+    async fn day_limits_are_checked_before_the_trade_risk() {
+        let risk_gate = risk_gate().await;
+        let mut current_pnl_state = day_pnl_state(&risk_gate);
+        current_pnl_state.current_pnl = -1200.0;
+        assert_eq!(
+            risk_gate.evaluate(&trade_signal(TradeType::Long, 100.0, 50.0, 20), &current_pnl_state),
+            Err(RiskRejectionReason::DailyLossLimitReached)
+        );
+    }
+}
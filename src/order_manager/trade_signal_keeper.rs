@@ -1,3 +1,5 @@
+use std::sync::{Arc, Mutex};
+
 use mongodb::{Collection, bson::oid::ObjectId};
 
 use crate::common::{raw_stock::RawStock, enums::{AlgoTypes, TradeType}};
@@ -8,6 +10,7 @@ pub struct TradeSignal{
     pub raw_stock: RawStock,
     pub trade_position_type: TradeType,
     pub trade_algo_type: AlgoTypes,
+    pub strategy_instance: String,
     pub trade_signal_requested_at: String,
     pub entry_price: f32,
     pub trade_sl: f32, 
@@ -20,11 +23,12 @@ pub struct TradeSignal{
 }
 
 impl TradeSignal{
-    pub fn new(raw_stock: RawStock,trade_position_type: TradeType, trade_algo_type: AlgoTypes, trade_signal_requested_at: String,  entry_price: f32, trade_sl: f32, trade_target: f32, qty: i32, total_price: f32, id: ObjectId, algo_id: ObjectId ) -> TradeSignal {
+    pub fn new(raw_stock: RawStock,trade_position_type: TradeType, trade_algo_type: AlgoTypes, strategy_instance: String, trade_signal_requested_at: String,  entry_price: f32, trade_sl: f32, trade_target: f32, qty: i32, total_price: f32, id: ObjectId, algo_id: ObjectId ) -> TradeSignal {
         TradeSignal {
             raw_stock,
             trade_position_type,
             trade_algo_type,
+            strategy_instance,
             trade_signal_requested_at,
             entry_price,
             trade_sl,
@@ -49,15 +53,16 @@ impl TradeSignal{
     }
 }
 
+//clones share the same signal history, so every worker adds to and reads from one list
 #[derive(Debug, Clone, Default)]
 pub struct TradeSignalsKeeper{
-    trade_signals: Vec<TradeSignal>,
+    trade_signals: Arc<Mutex<Vec<TradeSignal>>>,
 }
 
 impl TradeSignalsKeeper{
     pub fn new() -> TradeSignalsKeeper {
         TradeSignalsKeeper {
-            trade_signals: Vec::new(),
+            trade_signals: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -72,12 +77,16 @@ impl TradeSignalsKeeper{
             }
         }
 
-        self.trade_signals.push(trade_signal.clone());
+        self.trade_signals.lock().unwrap().push(trade_signal.clone());
+
+    }
 
+    pub fn get_trade_signals(&self) -> Vec<TradeSignal> {
+        self.trade_signals.lock().unwrap().clone()
     }
 
-    pub fn get_trade_signals(&self) -> &Vec<TradeSignal> {
-        &self.trade_signals
+    pub fn clear(&self) {
+        self.trade_signals.lock().unwrap().clear();
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,291 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{date_parser, enums::TradeType, raw_stock::RawStock},
+    order_manager::order_dispatcher::Order,
+};
+
+use super::trade_watcher_config::TradeWatcherConfig;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialTarget {
+    pub move_percentage: f32, //favourable move from the entry price
+    pub exit_percentage: f32, //share of the filled qty booked at that move
+}
+
+//How an open order is managed on top of its fixed SL and target, one policy per AlgoTypes.
+//Every missing field leaves that part switched off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExitPolicy {
+    pub trailing_sl_percentage: Option<f32>,      //SL trails the best price by this percentage
+    pub trailing_sl_atr_multiple: Option<f32>,    //SL trails the best price by this many 1 min ATRs
+    pub break_even_move_percentage: Option<f32>,  //SL moves to the entry price after this favourable move
+    pub partial_targets: Vec<PartialTarget>,      //booked in the given order
+    pub max_holding_candles: Option<i64>,         //1 min candles since the entry filled
+    pub exit_at_time: Option<String>,             //IST, e.g. "15:10:00"
+}
+
+impl ExitPolicy {
+    //checked at the close of the candle, the order exits at the close price
+    pub fn is_time_exit_due(&self, order: &Order, stock: &RawStock) -> bool {
+        let candle_close = match date_parser::parse_date_time_in_stock_format(stock.date.as_str()) {
+            Some(candle_start) => candle_start + Duration::minutes(1),
+            None => return false,
+        };
+        if let Some(exit_at_time) = self.exit_at_time.as_deref().and_then(TradeWatcherConfig::parse_time) {
+            if candle_close.time() >= exit_at_time {
+                return true;
+            }
+        }
+        let entry_filled_at = order
+            .entry_filled_at()
+            .and_then(date_parser::parse_date_time_in_stock_format);
+        match (self.max_holding_candles, entry_filled_at) {
+            (Some(max_holding_candles), Some(entry_filled_at)) if max_holding_candles > 0 => {
+                (candle_close - entry_filled_at).num_minutes() >= max_holding_candles
+            }
+            _ => false,
+        }
+    }
+
+    //Runs after the exit check, so whatever this candle changes only protects the order from the next candle on.
+    //true => partial targets were booked or the stop moved and the order has to be saved
+    pub fn apply(&self, order: &mut Order, stock: &RawStock, average_true_range: Option<f32>) -> bool {
+        let mut is_updated = self.book_partial_targets(order, stock);
+        is_updated |= order.update_best_price(stock);
+        if let Some(trade_sl) = self.find_adjusted_stop(order, average_true_range) {
+            is_updated |= order.move_stop(trade_sl);
+        }
+        is_updated
+    }
+
+    fn book_partial_targets(&self, order: &mut Order, stock: &RawStock) -> bool {
+        let mut is_booked = false;
+        for partial_target in self.partial_targets.iter().skip(order.partial_exits.len()) {
+            let target_price = order.price_after_move(partial_target.move_percentage);
+            let is_reached = if order.trade_position_type == TradeType::Long {
+                stock.high >= target_price
+            } else {
+                stock.low <= target_price
+            };
+            //at least one share stays open for the final target or SL
+            let qty = ((order.filled_qty as f32 * partial_target.exit_percentage / 100.0).round() as i32).min(order.open_qty() - 1);
+            if !is_reached || qty <= 0 {
+                break;
+            }
+            order.book_partial_exit(qty, target_price, stock.date.clone());
+            is_booked = true;
+        }
+        is_booked
+    }
+
+    //tightest stop of break-even and the trailing rules, the order only takes it if it is tighter than the current SL
+    fn find_adjusted_stop(&self, order: &Order, average_true_range: Option<f32>) -> Option<f32> {
+        let direction = if order.trade_position_type == TradeType::Long { 1.0 } else { -1.0 };
+        let mut candidate_stops = Vec::new();
+        if let Some(break_even_move_percentage) = self.break_even_move_percentage {
+            if order.favourable_move_percentage() >= break_even_move_percentage {
+                candidate_stops.push(order.entry_price);
+            }
+        }
+        if let Some(trailing_sl_percentage) = self.trailing_sl_percentage {
+            candidate_stops.push(order.best_price * (1.0 - direction * trailing_sl_percentage / 100.0));
+        }
+        if let (Some(trailing_sl_atr_multiple), Some(average_true_range)) = (self.trailing_sl_atr_multiple, average_true_range) {
+            candidate_stops.push(order.best_price - direction * trailing_sl_atr_multiple * average_true_range);
+        }
+
+        candidate_stops.into_iter().reduce(|tightest_stop, candidate_stop| {
+            if order.trade_position_type == TradeType::Long {
+                tightest_stop.max(candidate_stop)
+            } else {
+                tightest_stop.min(candidate_stop)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::enums::{AlgoTypes, TimeFrame},
+        order_manager::order_status::OrderStatus,
+    };
+
+    //10 filled at 100 on the 09:30 candle
+    fn filled_order(trade_position_type: TradeType) -> Order {
+        let (trade_sl, trade_target) = if trade_position_type == TradeType::Long { (95.0, 110.0) } else { (105.0, 90.0) };
+        let mut order = Order::new(
+            "ADANIGREEN".to_string(),
+            trade_position_type,
+            AlgoTypes::HammerPatternAlgo,
+            AlgoTypes::HammerPatternAlgo.to_string(),
+            TimeFrame::FiveMinutes,
+            100.0,
+            0.0,
+            trade_sl,
+            trade_target,
+            OrderStatus::PendingEntry,
+            10,
+            1000.0,
+            "2023-08-10 09:25:00+0530".to_string(),
+            "".to_string(),
+            "PAPER_1".to_string(),
+            0.0,
+            false,
+            None,
+        );
+        order.fill_entry(10, 100.0, "2023-08-10 09:30:00+0530".to_string()).unwrap();
+        order
+    }
+
+    fn test_candle(date: &str, high: f32, low: f32) -> RawStock {
+        RawStock::new("ADANIGREEN".to_string(), date.to_string(), (high + low) / 2.0, high, low, (high + low) / 2.0, 1000, TimeFrame::OneMinute)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "expected {} got {}", expected, actual);
+    }
+
+    #[test]
+    fn percentage_trailing_stop_follows_the_best_price_and_never_loosens() {
+        let exit_policy = ExitPolicy { trailing_sl_percentage: Some(2.0), ..ExitPolicy::default() };
+        let mut order = filled_order(TradeType::Long);
+        assert!(exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 110.0, 105.0), None));
+        assert_close(order.trade_sl, 107.8);
+        assert_eq!(order.initial_sl, 95.0);
+
+        //a pullback keeps the stop where it is
+        assert!(!exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 108.0, 106.0), None));
+        assert_close(order.trade_sl, 107.8);
+
+        let mut short_order = filled_order(TradeType::Short);
+        assert!(exit_policy.apply(&mut short_order, &test_candle("2023-08-10 09:31:00+0530", 95.0, 90.0), None));
+        assert_close(short_order.trade_sl, 91.8);
+        assert!(!exit_policy.apply(&mut short_order, &test_candle("2023-08-10 09:32:00+0530", 93.0, 91.0), None));
+        assert_close(short_order.trade_sl, 91.8);
+    }
+
+    #[test]
+    fn atr_trailing_stop_needs_the_atr() {
+        let exit_policy = ExitPolicy { trailing_sl_atr_multiple: Some(1.5), ..ExitPolicy::default() };
+        let mut order = filled_order(TradeType::Long);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 110.0, 105.0), None);
+        assert_eq!(order.trade_sl, 95.0);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 110.0, 105.0), Some(2.0));
+        assert_close(order.trade_sl, 107.0);
+
+        let mut short_order = filled_order(TradeType::Short);
+        exit_policy.apply(&mut short_order, &test_candle("2023-08-10 09:31:00+0530", 95.0, 90.0), Some(2.0));
+        assert_close(short_order.trade_sl, 93.0);
+    }
+
+    #[test]
+    fn break_even_moves_the_stop_to_the_entry_after_the_move() {
+        let exit_policy = ExitPolicy { break_even_move_percentage: Some(1.0), ..ExitPolicy::default() };
+        let mut order = filled_order(TradeType::Long);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 100.9, 99.0), None);
+        assert_eq!(order.trade_sl, 95.0);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 101.0, 99.0), None);
+        assert_eq!(order.trade_sl, 100.0);
+
+        let mut short_order = filled_order(TradeType::Short);
+        exit_policy.apply(&mut short_order, &test_candle("2023-08-10 09:31:00+0530", 100.0, 99.0), None);
+        assert_eq!(short_order.trade_sl, 100.0);
+    }
+
+    #[test]
+    fn tightest_rule_wins() {
+        let exit_policy = ExitPolicy {
+            trailing_sl_percentage: Some(5.0),
+            break_even_move_percentage: Some(1.0),
+            ..ExitPolicy::default()
+        };
+        //trailing => 103 * 0.95 = 97.85, break-even => 100
+        let mut order = filled_order(TradeType::Long);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 103.0, 100.0), None);
+        assert_eq!(order.trade_sl, 100.0);
+        //trailing => 110 * 0.95 = 104.5
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 110.0, 104.0), None);
+        assert_close(order.trade_sl, 104.5);
+    }
+
+    #[test]
+    fn partial_targets_are_booked_in_order_and_keep_one_share_open() {
+        let exit_policy = ExitPolicy {
+            partial_targets: vec![
+                PartialTarget { move_percentage: 1.0, exit_percentage: 50.0 },
+                PartialTarget { move_percentage: 2.0, exit_percentage: 30.0 },
+                PartialTarget { move_percentage: 3.0, exit_percentage: 100.0 },
+            ],
+            ..ExitPolicy::default()
+        };
+        let mut order = filled_order(TradeType::Long);
+        assert!(exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 101.5, 100.0), None));
+        assert_eq!(order.exited_qty, 5);
+        assert_close(order.partial_exits[0].exit_price, 101.0);
+        assert_close(order.partial_exits[0].profit, 5.0);
+
+        //the second and third targets on one candle, the last one leaves a single share
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 104.0, 101.0), None);
+        assert_eq!(order.partial_exits.len(), 3);
+        assert_eq!(order.partial_exits[1].qty, 3);
+        assert_eq!(order.partial_exits[2].qty, 1);
+        assert_eq!(order.exited_qty, 9);
+        assert_eq!(order.open_qty(), 1);
+        assert!(order.is_exited_qty_valid());
+
+        //nothing left to book
+        assert!(!exit_policy.apply(&mut order, &test_candle("2023-08-10 09:33:00+0530", 104.0, 103.5), None));
+        assert_eq!(order.exited_qty, 9);
+    }
+
+    #[test]
+    fn short_partial_target_is_booked_below_the_entry() {
+        let exit_policy = ExitPolicy {
+            partial_targets: vec![PartialTarget { move_percentage: 1.0, exit_percentage: 50.0 }],
+            ..ExitPolicy::default()
+        };
+        let mut order = filled_order(TradeType::Short);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:31:00+0530", 100.0, 99.5), None);
+        assert_eq!(order.exited_qty, 0);
+        exit_policy.apply(&mut order, &test_candle("2023-08-10 09:32:00+0530", 100.0, 98.5), None);
+        assert_eq!(order.exited_qty, 5);
+        assert_close(order.partial_exits[0].exit_price, 99.0);
+    }
+
+    #[test]
+    fn exited_qty_above_the_filled_qty_is_invalid() {
+        let mut order = filled_order(TradeType::Long);
+        order.book_partial_exit(20, 105.0, "2023-08-10 09:31:00+0530".to_string());
+        assert_eq!(order.exited_qty, 10);
+        assert!(order.is_exited_qty_valid());
+        order.exited_qty = 11;
+        assert!(!order.is_exited_qty_valid());
+    }
+
+    #[test]
+    fn time_exit_after_max_holding_candles() {
+        let exit_policy = ExitPolicy { max_holding_candles: Some(5), ..ExitPolicy::default() };
+        let order = filled_order(TradeType::Long);
+        assert!(!exit_policy.is_time_exit_due(&order, &test_candle("2023-08-10 09:33:00+0530", 101.0, 100.0)));
+        assert!(exit_policy.is_time_exit_due(&order, &test_candle("2023-08-10 09:34:00+0530", 101.0, 100.0)));
+
+        //no time exit before the entry filled
+        let mut pending_order = filled_order(TradeType::Long);
+        pending_order.status_history.retain(|transition| transition.status == OrderStatus::PendingEntry);
+        assert!(!exit_policy.is_time_exit_due(&pending_order, &test_candle("2023-08-10 10:34:00+0530", 101.0, 100.0)));
+    }
+
+    #[test]
+    fn time_exit_at_the_exit_time() {
+        let exit_policy = ExitPolicy { exit_at_time: Some("15:10:00".to_string()), ..ExitPolicy::default() };
+        let order = filled_order(TradeType::Long);
+        assert!(!exit_policy.is_time_exit_due(&order, &test_candle("2023-08-10 15:08:00+0530", 101.0, 100.0)));
+        assert!(exit_policy.is_time_exit_due(&order, &test_candle("2023-08-10 15:09:00+0530", 101.0, 100.0)));
+        assert!(!ExitPolicy::default().is_time_exit_due(&order, &test_candle("2023-08-10 15:29:00+0530", 101.0, 100.0)));
+    }
+}
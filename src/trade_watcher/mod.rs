@@ -1 +1,4 @@
-pub mod monitor_trade;
\ No newline at end of file
+pub mod exit_policy;
+pub mod monitor_trade;
+pub mod square_off;
+pub mod trade_watcher_config;
\ No newline at end of file
@@ -0,0 +1,167 @@
+use std::{error::Error, sync::Mutex, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, Utc};
+use mongodb::Collection;
+
+use crate::{
+    common::{date_parser, enums::{ExitReason, ThreadWorkerConfig}, redis_client::RedisClient},
+    order_manager::{order_dispatcher::{Order, OrderManager}, order_status::OrderStatus},
+};
+
+use super::trade_watcher_config::TradeWatcherConfig;
+
+//the 1 min worker squares off on the candle closing at the cutoff, the cron only catches what it missed (no candles, worker down)
+const SQUARE_OFF_GRACE_IN_SECONDS: i64 = 60;
+const IST_OFFSET_IN_SECONDS: i32 = 5 * 60 * 60 + 30 * 60;
+
+//TradeWatcherCron job => wakes up every square_off_check_interval_in_seconds and squares off once per trading day
+pub async fn run_square_off_cron(thread_worker_config: ThreadWorkerConfig, redis_client: &Mutex<RedisClient>) -> Result<(), Box<dyn Error>> {
+    let mut root_system_config = thread_worker_config.root_system_config.clone();
+    let trade_watcher_config = root_system_config.trade_watcher_config.clone();
+    let square_off_time = match trade_watcher_config.square_off_time() {
+        Some(square_off_time) => square_off_time,
+        None => {
+            println!("No square off time configured, square off cron stopped");
+            return Ok(());
+        }
+    };
+    let ist_offset = FixedOffset::east_opt(IST_OFFSET_IN_SECONDS).ok_or("invalid IST offset")?;
+    let check_interval = StdDuration::from_secs(trade_watcher_config.square_off_check_interval_in_seconds.max(1));
+    let mut last_square_off_date: Option<NaiveDate> = None;
+
+    println!("Square off cron started for {} IST", square_off_time);
+    loop {
+        tokio::time::sleep(check_interval).await;
+
+        let now = Utc::now().with_timezone(&ist_offset);
+        if !is_square_off_due(&trade_watcher_config, now, square_off_time, last_square_off_date) {
+            continue;
+        }
+
+        let squared_off_count = square_off_open_orders(
+            &mut root_system_config.order_manager,
+            date_parser::format_date_time_in_stock_format(&now),
+            redis_client,
+            &root_system_config.orders_collection,
+        )
+        .await;
+        println!("Square off done for {} => {} orders closed", now.date_naive(), squared_off_count);
+        last_square_off_date = Some(now.date_naive());
+    }
+}
+
+//once per trading day, from the grace period after the cutoff on
+fn is_square_off_due(trade_watcher_config: &TradeWatcherConfig, now: DateTime<FixedOffset>, square_off_time: NaiveTime, last_square_off_date: Option<NaiveDate>) -> bool {
+    trade_watcher_config.is_trading_day(now.date_naive())
+        && last_square_off_date != Some(now.date_naive())
+        && (now - Duration::seconds(SQUARE_OFF_GRACE_IN_SECONDS)).time() >= square_off_time
+}
+
+//Every order still open in the position book is closed => pending entries are cancelled, positions exit at their last known price
+pub async fn square_off_open_orders(
+    order_manager: &mut OrderManager,
+    squared_off_at: String,
+    redis_client: &Mutex<RedisClient>,
+    order_collection: &Collection<Order>,
+) -> usize {
+    let mut squared_off_count = 0;
+    for mut order in order_manager.position_book.open_orders() {
+        //an entry filled meanwhile comes back with a position and is exited right after
+        if order.status == OrderStatus::PendingEntry {
+            if let Some(updated_order) = order_manager.expire_pending_entry(&order, squared_off_at.clone(), redis_client, order_collection).await {
+                order = updated_order;
+            }
+        }
+        if !order.status.has_position() {
+            continue;
+        }
+
+        if order_manager
+            .exit_and_update_order(&order, square_off_price(&order), squared_off_at.clone(), ExitReason::SquareOff, redis_client, order_collection)
+            .await
+            .is_some()
+        {
+            squared_off_count += 1;
+        }
+    }
+    squared_off_count
+}
+
+//the last price the trade watcher saw, no price since the entry => the entry price
+fn square_off_price(order: &Order) -> f32 {
+    if order.last_price > 0.0 {
+        return order.last_price;
+    }
+    println!("No price seen for {} since the entry, squaring off at the entry price", order.order_id);
+    order.entry_price
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::{AlgoTypes, TimeFrame, TradeType};
+
+    fn ist_time(date: &str) -> DateTime<FixedOffset> {
+        date_parser::parse_date_time_in_stock_format(date).unwrap()
+    }
+
+    fn open_order(last_price: f32) -> Order {
+        let mut order = Order::new(
+            "ADANIGREEN".to_string(),
+            TradeType::Long,
+            AlgoTypes::HammerPatternAlgo,
+            AlgoTypes::HammerPatternAlgo.to_string(),
+            TimeFrame::FiveMinutes,
+            100.0,
+            0.0,
+            95.0,
+            110.0,
+            OrderStatus::Open,
+            10,
+            1000.0,
+            "2023-08-10 09:30:00+0530".to_string(),
+            "".to_string(),
+            "ORDER_ADANIGREEN_HammerPatternAlgo".to_string(),
+            0.0,
+            true,
+            None,
+        );
+        order.last_price = last_price;
+        order
+    }
+
+    #[test]
+    fn cron_squares_off_once_from_one_minute_after_the_cutoff() {
+        let trade_watcher_config = TradeWatcherConfig {
+            trading_holidays: vec!["2023-08-15".to_string()],
+            ..TradeWatcherConfig::default()
+        };
+        let square_off_time = trade_watcher_config.square_off_time().unwrap();
+        let cases = [
+            //now, last square off date, due
+            ("2023-08-10 15:15:00+0530", None, false), //the 1 min worker squares off on the candle closing now
+            ("2023-08-10 15:15:59+0530", None, false),
+            ("2023-08-10 15:16:00+0530", None, true),
+            ("2023-08-10 15:29:00+0530", None, true),
+            ("2023-08-10 15:16:00+0530", NaiveDate::from_ymd_opt(2023, 8, 10), false),
+            ("2023-08-10 15:16:00+0530", NaiveDate::from_ymd_opt(2023, 8, 9), true),
+            ("2023-08-12 15:16:00+0530", None, false), //Saturday
+            ("2023-08-15 15:16:00+0530", None, false), //listed holiday
+        ];
+        for (now, last_square_off_date, expected_due) in cases {
+            assert_eq!(
+                is_square_off_due(&trade_watcher_config, ist_time(now), square_off_time, last_square_off_date),
+                expected_due,
+                "at {} after {:?}",
+                now,
+                last_square_off_date
+            );
+        }
+    }
+
+    #[test]
+    fn orders_without_a_price_square_off_at_their_entry() {
+        assert_eq!(square_off_price(&open_order(103.5)), 103.5);
+        assert_eq!(square_off_price(&open_order(0.0)), 100.0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{collections::HashMap, fmt};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{config_file, date_parser, enums::AlgoTypes, raw_stock::RawStock};
+
+use super::exit_policy::ExitPolicy;
+
+//which level was hit first when one candle spans both the SL and the target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum IntraCandleExitPolicy {
+    #[default]
+    Pessimistic,   //SL
+    Optimistic,    //target
+    OpenProximity, //the level closer to the candle open
+}
+
+impl fmt::Display for IntraCandleExitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+//Every missing field falls back to the defaults below
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TradeWatcherConfig {
+    //1 min candles a pending entry may rest after its signal candle closed, 0 => until the cutoff
+    pub max_pending_entry_candles: i64,
+    //pending entries still unfilled at this time (IST) expire
+    pub entry_cutoff_time: String,
+    //keyed by AlgoTypes name, algo types without a policy only exit at the fixed SL and target
+    pub exit_policies: HashMap<String, ExitPolicy>,
+    //every open order is closed at this time (IST), empty => no square off
+    pub square_off_time: String,
+    pub square_off_check_interval_in_seconds: u64,
+    //exchange holidays as YYYY-MM-DD, the square off cron skips them like weekends
+    pub trading_holidays: Vec<String>,
+    pub intra_candle_exit_policy: IntraCandleExitPolicy,
+    //SL exits are market orders on the trigger and slip against us, targets rest as limits and do not
+    pub stop_loss_slippage_percentage: f32,
+}
+
+impl Default for TradeWatcherConfig {
+    fn default() -> TradeWatcherConfig {
+        TradeWatcherConfig {
+            max_pending_entry_candles: 15,
+            entry_cutoff_time: "15:15:00".to_string(),
+            exit_policies: HashMap::new(),
+            square_off_time: "15:15:00".to_string(),
+            square_off_check_interval_in_seconds: 30,
+            trading_holidays: Vec::new(),
+            intra_candle_exit_policy: IntraCandleExitPolicy::Pessimistic,
+            stop_loss_slippage_percentage: 0.0,
+        }
+    }
+}
+
+impl TradeWatcherConfig {
+    //.toml files hold a [trade_watcher] table, .json files a plain object
+    pub fn load_config(file_path: &str) -> TradeWatcherConfig {
+        config_file::load_config(file_path, "trade_watcher")
+    }
+
+    pub fn parse_time(time: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()
+    }
+
+    pub fn entry_cutoff_time(&self) -> NaiveTime {
+        TradeWatcherConfig::parse_time(self.entry_cutoff_time.as_str()).unwrap_or_else(|| NaiveTime::from_hms_opt(15, 15, 0).unwrap())
+    }
+
+    pub fn square_off_time(&self) -> Option<NaiveTime> {
+        TradeWatcherConfig::parse_time(self.square_off_time.as_str())
+    }
+
+    //the candle closing at the square off time is the last one an order is held through
+    pub fn is_square_off_due(&self, stock: &RawStock) -> bool {
+        match (self.square_off_time(), date_parser::parse_date_time_in_stock_format(stock.date.as_str())) {
+            (Some(square_off_time), Some(candle_start)) => (candle_start + Duration::minutes(1)).time() >= square_off_time,
+            _ => false,
+        }
+    }
+
+    pub fn is_trading_day(&self, trade_date: NaiveDate) -> bool {
+        let is_weekend = matches!(trade_date.weekday(), Weekday::Sat | Weekday::Sun);
+        let is_holiday = self
+            .trading_holidays
+            .iter()
+            .any(|trading_holiday| NaiveDate::parse_from_str(trading_holiday.as_str(), "%Y-%m-%d").ok() == Some(trade_date));
+        !is_weekend && !is_holiday
+    }
+
+    pub fn exit_policy(&self, algo_type: &AlgoTypes) -> Option<&ExitPolicy> {
+        self.exit_policies.get(&algo_type.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::TimeFrame;
+
+    fn write_config_file(file_name: &str, file_content: &str) -> String {
+        let file_path = std::env::temp_dir().join(file_name);
+        std::fs::write(&file_path, file_content).unwrap();
+        file_path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn toml_table_is_read_and_missing_fields_use_defaults() {
+        let file_path = write_config_file("trade_watcher_config_test.toml", "[trade_watcher]\nmax_pending_entry_candles = 5\nintra_candle_exit_policy = \"Optimistic\"\n");
+        let trade_watcher_config = TradeWatcherConfig::load_config(file_path.as_str());
+        assert_eq!(trade_watcher_config.max_pending_entry_candles, 5);
+        assert_eq!(trade_watcher_config.intra_candle_exit_policy, IntraCandleExitPolicy::Optimistic);
+        assert_eq!(trade_watcher_config.entry_cutoff_time, TradeWatcherConfig::default().entry_cutoff_time);
+    }
+
+    #[test]
+    fn json_object_is_read() {
+        let file_path = write_config_file("trade_watcher_config_test.json", r#"{"square_off_time": "15:20:00"}"#);
+        let trade_watcher_config = TradeWatcherConfig::load_config(file_path.as_str());
+        assert_eq!(trade_watcher_config.square_off_time(), NaiveTime::from_hms_opt(15, 20, 0));
+    }
+
+    #[test]
+    fn missing_file_or_table_falls_back_to_defaults() {
+        assert_eq!(TradeWatcherConfig::load_config("does_not_exist.toml"), TradeWatcherConfig::default());
+        let file_path = write_config_file("trade_watcher_config_other_table_test.toml", "[position_sizer]\nrisk_percentage = 2.0\n");
+        assert_eq!(TradeWatcherConfig::load_config(file_path.as_str()), TradeWatcherConfig::default());
+    }
+
+    #[test]
+    fn weekends_and_listed_holidays_are_not_trading_days() {
+        let trade_watcher_config = TradeWatcherConfig {
+            trading_holidays: vec!["2024-01-26".to_string(), "not a date".to_string()],
+            ..TradeWatcherConfig::default()
+        };
+        assert!(trade_watcher_config.is_trading_day(NaiveDate::from_ymd_opt(2024, 1, 25).unwrap()));
+        assert!(!trade_watcher_config.is_trading_day(NaiveDate::from_ymd_opt(2024, 1, 26).unwrap()));
+        assert!(!trade_watcher_config.is_trading_day(NaiveDate::from_ymd_opt(2024, 1, 27).unwrap()));
+        assert!(!trade_watcher_config.is_trading_day(NaiveDate::from_ymd_opt(2024, 1, 28).unwrap()));
+        assert!(trade_watcher_config.is_trading_day(NaiveDate::from_ymd_opt(2024, 1, 29).unwrap()));
+    }
+
+    #[test]
+    fn square_off_is_due_from_the_candle_closing_at_the_cutoff() {
+        let trade_watcher_config = TradeWatcherConfig::default();
+        let cases = [
+            ("2023-08-10 15:13:00+0530", false),
+            ("2023-08-10 15:14:00+0530", true), //closes at 15:15:00
+            ("2023-08-10 15:20:00+0530", true),
+            ("2023-08-10 09:15:00+0530", false),
+            ("2023-08-10", false), //unparsable candle date
+        ];
+        for (candle_date, expected_due) in cases {
+            let stock = RawStock::new("ADANIGREEN".to_string(), candle_date.to_string(), 100.0, 101.0, 99.0, 100.0, 1000, TimeFrame::OneMinute);
+            assert_eq!(trade_watcher_config.is_square_off_due(&stock), expected_due, "{}", candle_date);
+        }
+
+        //HH:MM is accepted, an empty time disables the square off
+        let stock = RawStock::new("ADANIGREEN".to_string(), "2023-08-10 15:19:00+0530".to_string(), 100.0, 101.0, 99.0, 100.0, 1000, TimeFrame::OneMinute);
+        let trade_watcher_config = TradeWatcherConfig {
+            square_off_time: "15:20".to_string(),
+            ..TradeWatcherConfig::default()
+        };
+        assert!(trade_watcher_config.is_square_off_due(&stock));
+        let trade_watcher_config = TradeWatcherConfig {
+            square_off_time: "".to_string(),
+            ..TradeWatcherConfig::default()
+        };
+        assert!(!trade_watcher_config.is_square_off_due(&stock));
+    }
+}